@@ -0,0 +1,82 @@
+use battlesnake_game_types::types::*;
+
+use battlesnake_rs::a_prime::APrimeCalculable;
+use battlesnake_rs::bitboard::BitGame;
+use battlesnake_rs::flood_fill::VoronoiCalculable;
+use battlesnake_rs::*;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pprof::criterion::{Output, PProfProfiler};
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let mut g = c.benchmark_group("bitboard");
+
+    let fixtures = [
+        (
+            "start_of_game",
+            include_str!("../fixtures/start_of_game.json"),
+        ),
+        (
+            "a-prime-food-maze",
+            include_str!("../fixtures/a-prime-food-maze.json"),
+        ),
+    ];
+
+    for (fixture, game_json) in fixtures {
+        let game: Game = serde_json::from_str(game_json).unwrap();
+
+        let id_map = build_snake_id_map(&game);
+        let compact = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        g.bench_function(format!("a-prime food distance {}", fixture), |b| {
+            b.iter(|| {
+                let compact = black_box(&compact);
+                compact.shortest_distance(
+                    &compact.get_head_as_native_position(compact.you_id()),
+                    &compact.get_all_food_as_native_positions(),
+                    None,
+                )
+            })
+        });
+
+        g.bench_function(format!("bitboard food distance {}", fixture), |b| {
+            b.iter(|| {
+                let compact = black_box(&compact);
+                let bits = BitGame::<_, 1>::from_game(compact).unwrap();
+
+                let head = bits.geometry.mask(&[compact
+                    .position_from_native(compact.get_head_as_native_position(compact.you_id()))]);
+                let food: Vec<_> = compact
+                    .get_all_food_as_native_positions()
+                    .into_iter()
+                    .map(|f| compact.position_from_native(f))
+                    .collect();
+
+                bits.distance(head, bits.geometry.mask(&food))
+            })
+        });
+
+        g.bench_function(format!("flood fill reachable area {}", fixture), |b| {
+            b.iter(|| {
+                let compact = black_box(&compact);
+                compact.reachable_area(compact.you_id())
+            })
+        });
+
+        g.bench_function(format!("bitboard reachable area {}", fixture), |b| {
+            b.iter(|| {
+                let compact = black_box(&compact);
+                BitGame::<_, 1>::from_game(compact)
+                    .unwrap()
+                    .reachable_area(compact.you_id())
+            })
+        });
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = criterion_benchmark
+}
+criterion_main!(benches);
@@ -2,6 +2,7 @@ use battlesnake_game_types::{
     compact_representation::CellBoard, types::build_snake_id_map, wire_representation::Game,
 };
 use battlesnake_rs::devious_devin_full::{minmax_bench_entry, minmax_deepened_bench_entry};
+use battlesnake_rs::evaluator::StandardEvaluator;
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use pprof::criterion::{Output, PProfProfiler};
@@ -54,6 +55,7 @@ fn bench_minmax_to_turn(c: &mut Criterion, max_turns: usize) {
             battlesnake_rs::devious_devin_eval::minmax_deepened_bench_entry(
                 black_box(game_state),
                 max_turns,
+                &StandardEvaluator::default(),
             )
         })
     });
@@ -0,0 +1,64 @@
+use battlesnake_game_types::types::*;
+
+use battlesnake_rs::a_prime::APrimeCalculable;
+use battlesnake_rs::evaluator::{AreaControlEvaluator, Evaluator, StandardEvaluator};
+use battlesnake_rs::*;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pprof::criterion::{Output, PProfProfiler};
+
+/// What a single leaf of the search costs to score, next to the plain A* food lookup the
+/// original evaluation was built on
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let mut g = c.benchmark_group("evaluator");
+
+    let fixtures = [
+        (
+            "start_of_game",
+            include_str!("../fixtures/start_of_game.json"),
+        ),
+        (
+            "a-prime-food-maze",
+            include_str!("../fixtures/a-prime-food-maze.json"),
+        ),
+        (
+            "three_snakes",
+            include_str!("../fixtures/three_snakes.json"),
+        ),
+    ];
+
+    for (fixture, game_json) in fixtures {
+        let game: Game = serde_json::from_str(game_json).unwrap();
+
+        let id_map = build_snake_id_map(&game);
+        let compact = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        g.bench_function(format!("baseline a-prime food distance {}", fixture), |b| {
+            b.iter(|| {
+                let compact = black_box(&compact);
+                compact.shortest_distance(
+                    &compact.get_head_as_native_position(compact.you_id()),
+                    &compact.get_all_food_as_native_positions(),
+                    None,
+                )
+            })
+        });
+
+        g.bench_function(format!("standard evaluator {}", fixture), |b| {
+            b.iter(|| StandardEvaluator::default().score(black_box(&compact)))
+        });
+
+        g.bench_function(format!("area control evaluator {}", fixture), |b| {
+            b.iter(|| AreaControlEvaluator::default().score(black_box(&compact)))
+        });
+    }
+
+    g.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = criterion_benchmark
+}
+criterion_main!(benches);
@@ -2,6 +2,9 @@ use battlesnake_game_types::compact_representation::{CellBoard, CellIndex, CellN
 use battlesnake_game_types::types::*;
 use battlesnake_game_types::wire_representation::{Game, Position};
 
+use crate::flood_fill::turns_until_vacated;
+use crate::rules::{Rules, WRAPPED_RULESET_NAME};
+
 use rustc_hash::FxHashMap;
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
@@ -13,11 +16,52 @@ const HEURISTIC_MAX: i32 = 500;
 pub struct APrimeResult<T> {
     best_cost: i32,
     paths_from: FxHashMap<T, Option<T>>,
+    /// How many turns it takes to get to each cell along `paths_from`
+    arrival_turns: FxHashMap<T, usize>,
     best_target: T,
 }
 
-pub struct APrimeOptions {
+pub struct APrimeOptions<'a> {
     pub food_penalty: i32,
+    /// Extra cost of stepping out of a hazard, on top of the usual `NEIGHBOR_DISTANCE`
+    pub hazard_penalty: i32,
+    /// Paths can go off one edge of the board and come back on the opposite one. A wire `Game`
+    /// also turns this on by itself when its ruleset is `wrapped`
+    pub wrapped: bool,
+    /// Body segments stop being walls once they have moved out of the way by the turn we get
+    /// to them, instead of blocking the path forever
+    pub receding_tails: bool,
+    /// Added to the cost of stepping onto a cell, for anything the search can't know about by
+    /// itself. Like staying out of reach of a bigger snake's head
+    pub extra_cost: Option<&'a dyn Fn(&Position) -> i32>,
+    /// Cells this returns `false` for are never stepped onto, targets included. Snake bodies
+    /// are walls whether or not this is set
+    pub passable: Option<&'a dyn Fn(&Position) -> bool>,
+}
+
+impl Default for APrimeOptions<'_> {
+    fn default() -> Self {
+        Self {
+            food_penalty: 0,
+            hazard_penalty: HAZARD_PENALTY,
+            wrapped: false,
+            receding_tails: false,
+            extra_cost: None,
+            passable: None,
+        }
+    }
+}
+
+impl APrimeOptions<'_> {
+    /// Charges a hazard by the health it costs, so a path through the hazard is only worth it
+    /// when going around would take longer than the health it saves
+    pub fn for_rules(rules: &Rules) -> Self {
+        Self {
+            hazard_penalty: rules.hazard_damage_per_turn.max(0),
+            wrapped: rules.wrapped,
+            ..Default::default()
+        }
+    }
 }
 
 pub trait APrimeNextDirection: APrimeCalculable {
@@ -36,8 +80,15 @@ impl APrimeNextDirection for Game {
         targets: &[Self::NativePositionType],
         options: Option<APrimeOptions>,
     ) -> Option<Move> {
+        let wrapped = options.as_ref().map_or(false, |o| o.wrapped) || is_wrapped(self);
         let shortest_path = self.shortest_path(start, targets, options);
-        let next_coordinate = shortest_path.get(1);
+        let next_coordinate = shortest_path.get(1).map(|(c, _)| c);
+
+        if wrapped {
+            let (width, height) = (self.get_width() as i32, self.get_height() as i32);
+            return next_coordinate.and_then(|c| wrapped_move_between(start, c, width, height));
+        }
+
         let start_vec = start.to_vector();
 
         next_coordinate.map(|c| Move::from_vector(c.sub_vec(start_vec).to_vector()))
@@ -55,12 +106,13 @@ pub trait APrimeCalculable: PositionGettableGame + NeighborDeterminableGame {
             .map(|r| r.best_cost)
     }
 
+    /// The cells from `start` to the closest target, each with the turn we would get there on
     fn shortest_path(
         &self,
         start: &Self::NativePositionType,
         targets: &[Self::NativePositionType],
         options: Option<APrimeOptions>,
-    ) -> Vec<Self::NativePositionType> {
+    ) -> Vec<(Self::NativePositionType, usize)> {
         let result = self.a_prime_inner(start, targets, options);
 
         let mut path = vec![];
@@ -74,7 +126,8 @@ pub trait APrimeCalculable: PositionGettableGame + NeighborDeterminableGame {
                     "Somehow we didn't look at this node during a-prime, but its still in the path",
                 );
 
-                path.push(c);
+                let turn = result.arrival_turns[&c];
+                path.push((c, turn));
             }
         }
 
@@ -89,6 +142,34 @@ pub trait APrimeCalculable: PositionGettableGame + NeighborDeterminableGame {
         targets: &[Self::NativePositionType],
         options: Option<APrimeOptions>,
     ) -> Option<APrimeResult<Self::NativePositionType>>;
+
+    /// The cells a search can step to from `coordinate`, before ruling out the ones that are in
+    /// the way
+    fn search_neighbors(
+        &self,
+        coordinate: &Self::NativePositionType,
+        options: &APrimeOptions,
+    ) -> Vec<Self::NativePositionType>;
+
+    /// What it costs a search to step off of `coordinate`
+    fn step_cost(&self, coordinate: &Self::NativePositionType, options: &APrimeOptions) -> i32;
+
+    /// What the `extra_cost` callback charges for stepping onto `cell`
+    fn extra_cost(&self, cell: &Self::NativePositionType, options: &APrimeOptions) -> i32 {
+        options
+            .extra_cost
+            .map_or(0, |f| f(&self.position_from_native(cell.clone())))
+    }
+
+    /// Whether the `passable` callback lets a search step onto `cell`
+    fn is_passable(&self, cell: &Self::NativePositionType, options: &APrimeOptions) -> bool {
+        options
+            .passable
+            .map_or(true, |f| f(&self.position_from_native(cell.clone())))
+    }
+
+    /// Cells a search can step onto but never through, unless they are what it is looking for
+    fn is_obstacle(&self, cell: &Self::NativePositionType) -> bool;
 }
 
 // The priority queue depends on `Ord`.
@@ -128,7 +209,16 @@ impl<T: CellNum, const BOARD_SIZE: usize, const MAX_SNAKES: usize> APrimeCalcula
         targets: &[Self::NativePositionType],
         options: Option<APrimeOptions>,
     ) -> Option<APrimeResult<Self::NativePositionType>> {
-        let options = options.unwrap_or(APrimeOptions { food_penalty: 0 });
+        let options = options.unwrap_or_default();
+        let (width, height) = (self.get_width() as i32, self.get_height() as i32);
+        let wrapped_targets: Vec<Position> = if options.wrapped {
+            targets
+                .iter()
+                .map(|t| t.into_position(width as u8))
+                .collect()
+        } else {
+            vec![]
+        };
         let mut paths_from: FxHashMap<Self::NativePositionType, Option<Self::NativePositionType>> =
             FxHashMap::default();
 
@@ -147,35 +237,60 @@ impl<T: CellNum, const BOARD_SIZE: usize, const MAX_SNAKES: usize> APrimeCalcula
         known_score.insert(*start, 0);
         paths_from.insert(*start, None);
 
+        let vacated = if options.receding_tails {
+            turns_until_vacated(self)
+        } else {
+            FxHashMap::default()
+        };
+        let mut arrival_turns: FxHashMap<Self::NativePositionType, usize> = FxHashMap::default();
+        arrival_turns.insert(*start, 0);
+
         while let Some(Node { cost, coordinate }) = to_search.pop() {
             if targets.contains(&coordinate) {
                 return Some(APrimeResult {
                     best_cost: cost,
                     paths_from,
+                    arrival_turns,
                     best_target: coordinate,
                 });
             }
 
-            let neighbor_distance = if self.cell_is_hazard(coordinate) {
-                HAZARD_PENALTY + NEIGHBOR_DISTANCE
-            } else if self.cell_is_food(coordinate) {
-                NEIGHBOR_DISTANCE + options.food_penalty
-            } else {
-                NEIGHBOR_DISTANCE
-            };
+            let neighbor_distance = self.step_cost(&coordinate, &options);
 
             let tentative = known_score.get(&coordinate).unwrap_or(&i32::MAX) + neighbor_distance;
-            let neighbors = self.neighbors(&coordinate);
+            let arrival_turn = arrival_turns[&coordinate] + 1;
+            let neighbors = self.search_neighbors(&coordinate, &options);
             for neighbor in neighbors
                 .into_iter()
-                .filter(|n| targets.contains(n) || !self.cell_is_snake_body_piece(coordinate))
+                .filter(|n| {
+                    targets.contains(n)
+                        || if options.receding_tails {
+                            !still_occupied(&vacated, n, arrival_turn)
+                        } else {
+                            !self.is_obstacle(n)
+                        }
+                })
+                .filter(|n| self.is_passable(n, &options))
             {
+                let tentative = tentative + self.extra_cost(&neighbor, &options);
                 if &tentative < known_score.get(&neighbor).unwrap_or(&i32::MAX) {
                     known_score.insert(neighbor, tentative);
                     paths_from.insert(neighbor, Some(coordinate));
+                    arrival_turns.insert(neighbor, arrival_turn);
                     to_search.push(Node {
                         coordinate: neighbor,
-                        cost: tentative + hueristic(&neighbor, targets).unwrap_or(HEURISTIC_MAX),
+                        cost: tentative
+                            + if options.wrapped {
+                                wrapped_hueristic(
+                                    &neighbor.into_position(width as u8),
+                                    &wrapped_targets,
+                                    width,
+                                    height,
+                                )
+                            } else {
+                                hueristic(&neighbor, targets, width as u8)
+                            }
+                            .unwrap_or(HEURISTIC_MAX),
                     });
                 }
             }
@@ -183,6 +298,48 @@ impl<T: CellNum, const BOARD_SIZE: usize, const MAX_SNAKES: usize> APrimeCalcula
 
         None
     }
+
+    fn search_neighbors(
+        &self,
+        coordinate: &Self::NativePositionType,
+        options: &APrimeOptions,
+    ) -> Vec<Self::NativePositionType> {
+        if !options.wrapped {
+            return self.neighbors(coordinate);
+        }
+
+        let (width, height) = (self.get_width() as i32, self.get_height() as i32);
+        wrapped_neighbors(&coordinate.into_position(width as u8), width, height)
+            .into_iter()
+            .map(|n| CellIndex::new(n, width as u8))
+            .collect()
+    }
+
+    fn step_cost(&self, coordinate: &Self::NativePositionType, options: &APrimeOptions) -> i32 {
+        if self.cell_is_hazard(*coordinate) {
+            options.hazard_penalty + NEIGHBOR_DISTANCE
+        } else if self.cell_is_food(*coordinate) {
+            NEIGHBOR_DISTANCE + options.food_penalty
+        } else {
+            NEIGHBOR_DISTANCE
+        }
+    }
+
+    fn is_obstacle(&self, cell: &Self::NativePositionType) -> bool {
+        self.cell_is_snake_body_piece(*cell)
+    }
+}
+
+/// Whether a body segment is still in the way on the turn we get to it. A segment is gone once
+/// as many turns have passed as it takes the snake's tail to get there
+fn still_occupied<T: std::hash::Hash + Eq>(
+    vacated: &FxHashMap<T, usize>,
+    cell: &T,
+    arrival_turn: usize,
+) -> bool {
+    vacated
+        .get(cell)
+        .map_or(false, |turns| *turns > arrival_turn)
 }
 
 pub fn dist_between_new<T: PositionGettableGame>(
@@ -200,9 +357,9 @@ pub fn dist_between(a: &Position, b: &Position) -> i32 {
     (a.x - b.x).abs() + (a.y - b.y).abs()
 }
 
-fn hueristic<T: CellNum>(start: &CellIndex<T>, targets: &[CellIndex<T>]) -> Option<i32> {
-    let width = ((11 * 11) as f32).sqrt() as u8;
-
+/// `width` is the board's real width, `CellIndex`s are row major so this works for boards that
+/// aren't square too
+fn hueristic<T: CellNum>(start: &CellIndex<T>, targets: &[CellIndex<T>], width: u8) -> Option<i32> {
     targets
         .iter()
         .map(|coor| dist_between(&coor.into_position(width), &start.into_position(width)))
@@ -213,6 +370,75 @@ fn hueristic_wire(start: &Position, targets: &[Position]) -> Option<i32> {
     targets.iter().map(|coor| dist_between(coor, start)).min()
 }
 
+fn is_wrapped(game: &Game) -> bool {
+    game.game.ruleset.name == WRAPPED_RULESET_NAME
+}
+
+/// Manhattan distance on a board where each axis wraps around, so going the other way around
+/// can be shorter
+pub fn wrapped_dist_between(a: &Position, b: &Position, width: i32, height: i32) -> i32 {
+    let dx = (a.x - b.x).abs();
+    let dy = (a.y - b.y).abs();
+
+    dx.min(width - dx) + dy.min(height - dy)
+}
+
+fn wrapped_hueristic(
+    start: &Position,
+    targets: &[Position],
+    width: i32,
+    height: i32,
+) -> Option<i32> {
+    targets
+        .iter()
+        .map(|coor| wrapped_dist_between(coor, start, width, height))
+        .min()
+}
+
+const WRAPPED_MOVES: [(Move, i32, i32); 4] = [
+    (Move::Up, 0, 1),
+    (Move::Down, 0, -1),
+    (Move::Left, -1, 0),
+    (Move::Right, 1, 0),
+];
+
+fn wrapped_step(pos: &Position, dx: i32, dy: i32, width: i32, height: i32) -> Position {
+    Position {
+        x: (pos.x + dx).rem_euclid(width),
+        y: (pos.y + dy).rem_euclid(height),
+    }
+}
+
+/// The neighbors of a cell on a wrapped board, where cells on an edge are next to the cells on
+/// the opposite edge
+pub fn wrapped_neighbors(pos: &Position, width: i32, height: i32) -> Vec<Position> {
+    WRAPPED_MOVES
+        .iter()
+        .map(|(_, dx, dy)| wrapped_step(pos, *dx, *dy, width, height))
+        .collect()
+}
+
+/// Each move from a cell on a wrapped board, with the cell it lands on
+pub fn wrapped_destinations(pos: &Position, width: i32, height: i32) -> Vec<(Move, Position)> {
+    WRAPPED_MOVES
+        .iter()
+        .map(|(m, dx, dy)| (*m, wrapped_step(pos, *dx, *dy, width, height)))
+        .collect()
+}
+
+/// The move that takes us from `from` to the neighboring cell `to`, including across an edge
+pub fn wrapped_move_between(
+    from: &Position,
+    to: &Position,
+    width: i32,
+    height: i32,
+) -> Option<Move> {
+    WRAPPED_MOVES
+        .iter()
+        .find(|(_, dx, dy)| wrapped_step(from, *dx, *dy, width, height) == *to)
+        .map(|(m, _, _)| *m)
+}
+
 impl APrimeCalculable for Game {
     fn a_prime_inner(
         &self,
@@ -220,7 +446,9 @@ impl APrimeCalculable for Game {
         targets: &[Position],
         options: Option<APrimeOptions>,
     ) -> Option<APrimeResult<Position>> {
-        let options = options.unwrap_or(APrimeOptions { food_penalty: 0 });
+        let options = options.unwrap_or_default();
+        let wrapped = options.wrapped || is_wrapped(self);
+        let (width, height) = (self.get_width() as i32, self.get_height() as i32);
         let mut paths_from: FxHashMap<Position, Option<Position>> = FxHashMap::default();
 
         if targets.is_empty() {
@@ -238,39 +466,55 @@ impl APrimeCalculable for Game {
         known_score.insert(*start, 0);
         paths_from.insert(*start, None);
 
+        let vacated = if options.receding_tails {
+            turns_until_vacated(self)
+        } else {
+            FxHashMap::default()
+        };
+        let mut arrival_turns: FxHashMap<Self::NativePositionType, usize> = FxHashMap::default();
+        arrival_turns.insert(*start, 0);
+
         while let Some(Node { cost, coordinate }) = to_search.pop() {
             if targets.contains(&coordinate) {
                 return Some(APrimeResult {
                     best_cost: cost,
                     paths_from,
+                    arrival_turns,
                     best_target: coordinate,
                 });
             }
 
-            let neighbor_distance = if self.board.hazards.contains(&coordinate) {
-                HAZARD_PENALTY + NEIGHBOR_DISTANCE
-            } else if self.board.food.contains(&coordinate) {
-                NEIGHBOR_DISTANCE + options.food_penalty
-            } else {
-                NEIGHBOR_DISTANCE
-            };
+            let neighbor_distance = self.step_cost(&coordinate, &options);
 
             let tentative = known_score.get(&coordinate).unwrap_or(&i32::MAX) + neighbor_distance;
-            for neighbor in self.neighbors(&coordinate).into_iter().filter(|n| {
-                targets.contains(n)
-                    || self
-                        .board
-                        .snakes
-                        .iter()
-                        .all(|snake| !snake.body.contains(n))
-            }) {
+            let arrival_turn = arrival_turns[&coordinate] + 1;
+            let neighbors = self.search_neighbors(&coordinate, &options);
+            for neighbor in neighbors
+                .into_iter()
+                .filter(|n| {
+                    targets.contains(n)
+                        || if options.receding_tails {
+                            !still_occupied(&vacated, n, arrival_turn)
+                        } else {
+                            !self.is_obstacle(n)
+                        }
+                })
+                .filter(|n| self.is_passable(n, &options))
+            {
+                let tentative = tentative + self.extra_cost(&neighbor, &options);
                 if &tentative < known_score.get(&neighbor).unwrap_or(&i32::MAX) {
                     known_score.insert(neighbor, tentative);
                     paths_from.insert(neighbor, Some(coordinate));
+                    arrival_turns.insert(neighbor, arrival_turn);
                     to_search.push(Node {
                         coordinate: neighbor,
                         cost: tentative
-                            + hueristic_wire(&neighbor, targets).unwrap_or(HEURISTIC_MAX),
+                            + if wrapped {
+                                wrapped_hueristic(&neighbor, targets, width, height)
+                            } else {
+                                hueristic_wire(&neighbor, targets)
+                            }
+                            .unwrap_or(HEURISTIC_MAX),
                     });
                 }
             }
@@ -278,6 +522,32 @@ impl APrimeCalculable for Game {
 
         None
     }
+
+    fn search_neighbors(&self, coordinate: &Position, options: &APrimeOptions) -> Vec<Position> {
+        if options.wrapped || is_wrapped(self) {
+            let (width, height) = (self.get_width() as i32, self.get_height() as i32);
+            wrapped_neighbors(coordinate, width, height)
+        } else {
+            self.neighbors(coordinate)
+        }
+    }
+
+    fn step_cost(&self, coordinate: &Position, options: &APrimeOptions) -> i32 {
+        if self.board.hazards.contains(coordinate) {
+            options.hazard_penalty + NEIGHBOR_DISTANCE
+        } else if self.board.food.contains(coordinate) {
+            NEIGHBOR_DISTANCE + options.food_penalty
+        } else {
+            NEIGHBOR_DISTANCE
+        }
+    }
+
+    fn is_obstacle(&self, cell: &Position) -> bool {
+        self.board
+            .snakes
+            .iter()
+            .any(|snake| snake.body.contains(cell))
+    }
 }
 
 #[cfg(test)]
@@ -287,9 +557,7 @@ mod tests {
         compact_representation::CellBoard4Snakes11x11, wire_representation::Game,
     };
 
-    fn cell_index_from_position_default_width(pos: Position) -> CellIndex<u8> {
-        let width = ((11 * 11) as f32).sqrt() as u8;
-
+    fn cell_index_from_position(pos: Position, width: u8) -> CellIndex<u8> {
         CellIndex::new(pos, width)
     }
 
@@ -297,11 +565,9 @@ mod tests {
     fn test_heuristic() {
         assert_eq!(
             hueristic(
-                &cell_index_from_position_default_width(Position { x: 1, y: 1 }),
-                &[cell_index_from_position_default_width(Position {
-                    x: 2,
-                    y: 2
-                })]
+                &cell_index_from_position(Position { x: 1, y: 1 }, 11),
+                &[cell_index_from_position(Position { x: 2, y: 2 }, 11)],
+                11
             ),
             Some(2)
         );
@@ -311,12 +577,13 @@ mod tests {
     fn test_multi_target_heuristic() {
         assert_eq!(
             hueristic(
-                &cell_index_from_position_default_width(Position { x: 1, y: 1 }),
+                &cell_index_from_position(Position { x: 1, y: 1 }, 11),
                 &[
-                    cell_index_from_position_default_width(Position { x: 3, y: 3 }),
-                    cell_index_from_position_default_width(Position { x: 4, y: 4 }),
-                    cell_index_from_position_default_width(Position { x: 5, y: 5 }),
-                ]
+                    cell_index_from_position(Position { x: 3, y: 3 }, 11),
+                    cell_index_from_position(Position { x: 4, y: 4 }, 11),
+                    cell_index_from_position(Position { x: 5, y: 5 }, 11),
+                ],
+                11
             ),
             Some(4)
         );
@@ -349,11 +616,11 @@ mod tests {
 
         assert_eq!(
             compact.shortest_distance(
-                &cell_index_from_position_default_width(Position { x: 1, y: 1 }),
+                &cell_index_from_position(Position { x: 1, y: 1 }, 11),
                 &[
-                    cell_index_from_position_default_width(Position { x: 3, y: 3 }),
-                    cell_index_from_position_default_width(Position { x: 4, y: 4 }),
-                    cell_index_from_position_default_width(Position { x: 5, y: 5 }),
+                    cell_index_from_position(Position { x: 3, y: 3 }, 11),
+                    cell_index_from_position(Position { x: 4, y: 4 }, 11),
+                    cell_index_from_position(Position { x: 5, y: 5 }, 11),
                 ],
                 None
             ),
@@ -380,11 +647,8 @@ mod tests {
 
         assert_eq!(
             compact.shortest_distance(
-                &cell_index_from_position_default_width(Position { x: 5, y: 4 }),
-                &[cell_index_from_position_default_width(Position {
-                    x: 7,
-                    y: 10
-                }),],
+                &cell_index_from_position(Position { x: 5, y: 4 }, 11),
+                &[cell_index_from_position(Position { x: 7, y: 10 }, 11),],
                 None
             ),
             Some(8)
@@ -416,11 +680,272 @@ mod tests {
         );
 
         dbg!(&compact_path);
-        let width = ((11 * 11) as f32).sqrt() as u8;
-        let compact_path_as_wire: Vec<Position> = compact_path
+        let width = compact.get_width() as u8;
+        let compact_path_as_wire: Vec<(Position, usize)> = compact_path
             .into_iter()
-            .map(|x| x.into_position(width))
+            .map(|(x, turn)| (x.into_position(width), turn))
             .collect();
         assert_eq!(wire_path, compact_path_as_wire);
     }
+
+    fn single_snake_game(width: u32, height: u32) -> Game {
+        let snake = r#"{"id":"you","name":"","health":86,"body":[{"x":0,"y":0}],"latency":0,"head":{"x":0,"y":0},"length":1,"shout":""}"#;
+        let board_json = format!(
+            r#"{{"game":{{"id":"","ruleset":{{"name":"standard","version":"v1.0.17"}},"timeout":500}},"turn":10,"board":{{"height":{},"width":{},"food":[],"hazards":[],"snakes":[{}]}},"you":{}}}"#,
+            height, width, snake, snake
+        );
+
+        serde_json::from_str(&board_json).unwrap()
+    }
+
+    #[test]
+    fn test_heuristic_uses_the_board_width() {
+        let start = Position { x: 1, y: 5 };
+        let target = Position { x: 6, y: 0 };
+
+        for width in [7, 11, 19] {
+            assert_eq!(
+                hueristic(
+                    &cell_index_from_position(start, width),
+                    &[cell_index_from_position(target, width)],
+                    width
+                ),
+                Some(10)
+            );
+        }
+    }
+
+    #[test]
+    fn test_every_best_cell_board_size_matches_the_wire_distance() {
+        use battlesnake_game_types::compact_representation::{BestCellBoard, ToBestCellBoard};
+
+        for size in [7, 11, 15, 19, 25] {
+            let game = single_snake_game(size, size);
+            let start = Position { x: 1, y: 1 };
+            let target = Position {
+                x: size as i32 - 1,
+                y: size as i32 - 2,
+            };
+            let expected = game.shortest_distance(&start, &[target], None);
+            assert_eq!(expected, Some(dist_between(&start, &target)));
+
+            let width = size as u8;
+            macro_rules! compact_distance {
+                ($board:expr) => {{
+                    let start = CellIndex::new(start, width);
+                    let targets = [CellIndex::new(target, width)];
+
+                    assert_eq!(
+                        hueristic(&start, &targets, width),
+                        Some(dist_between(&start.into_position(width), &target))
+                    );
+                    $board.shortest_distance(&start, &targets, None)
+                }};
+            }
+
+            let actual = match game.to_best_cell_board().unwrap() {
+                BestCellBoard::Standard(b) => compact_distance!(b),
+                BestCellBoard::LargestU8(b) => compact_distance!(b),
+                BestCellBoard::Large(b) => compact_distance!(b),
+                BestCellBoard::Silly(b) => compact_distance!(b),
+            };
+
+            assert_eq!(actual, expected, "{}x{} board", size, size);
+        }
+    }
+
+    #[test]
+    fn test_receding_tails_open_up_a_path() {
+        let board_json = r#"{"game":{"id":"","ruleset":{"name":"standard","version":"v1.0.17"},"timeout":500},"turn":10,"board":{"height":11,"width":11,"food":[],"hazards":[],"snakes":[{"id":"you","name":"","health":90,"body":[{"x":0,"y":0},{"x":1,"y":0},{"x":1,"y":1},{"x":1,"y":2},{"x":0,"y":2}],"latency":0,"head":{"x":0,"y":0},"length":5,"shout":""}]},"you":{"id":"you","name":"","health":90,"body":[{"x":0,"y":0},{"x":1,"y":0},{"x":1,"y":1},{"x":1,"y":2},{"x":0,"y":2}],"latency":0,"head":{"x":0,"y":0},"length":5,"shout":""}}"#;
+        let game: Game = serde_json::from_str(board_json).unwrap();
+        let head = Position { x: 0, y: 0 };
+        let target = [Position { x: 0, y: 3 }];
+        let receding = || {
+            Some(APrimeOptions {
+                receding_tails: true,
+                ..Default::default()
+            })
+        };
+
+        // Our tail is on (0, 2), and it's gone by the time we get there
+        assert_eq!(game.shortest_distance(&head, &target, None), None);
+        assert_eq!(game.shortest_distance(&head, &target, receding()), Some(3));
+        assert_eq!(
+            game.shortest_path(&head, &target, receding()),
+            vec![
+                (head, 0),
+                (Position { x: 0, y: 1 }, 1),
+                (Position { x: 0, y: 2 }, 2),
+                (Position { x: 0, y: 3 }, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrapped_paths_cross_the_edge() {
+        let board_json = r#"{"game":{"id":"","ruleset":{"name":"wrapped","version":"v1.0.22"},"timeout":500},"turn":10,"board":{"height":11,"width":11,"food":[{"x":10,"y":5}],"hazards":[],"snakes":[{"id":"","name":"","health":86,"body":[{"x":0,"y":5}],"latency":0,"head":{"x":0,"y":5},"length":1,"shout":""}]},"you":{"id":"","name":"","health":86,"body":[{"x":0,"y":5}],"latency":0,"head":{"x":0,"y":5},"length":1,"shout":""}}"#;
+        let game: Game = serde_json::from_str(board_json).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let head = Position { x: 0, y: 5 };
+        let food = [Position { x: 10, y: 5 }];
+
+        assert_eq!(game.shortest_distance(&head, &food, None), Some(1));
+        assert_eq!(
+            game.shortest_path_next_direction(&head, &food, None),
+            Some(Move::Left)
+        );
+
+        let compact: CellBoard4Snakes11x11 =
+            battlesnake_game_types::compact_representation::CellBoard::convert_from_game(
+                game, &id_map,
+            )
+            .unwrap();
+        let wrapped = || {
+            Some(APrimeOptions {
+                wrapped: true,
+                ..Default::default()
+            })
+        };
+
+        assert_eq!(
+            compact.shortest_distance(
+                &cell_index_from_position(head, 11),
+                &[cell_index_from_position(food[0], 11)],
+                wrapped()
+            ),
+            Some(1)
+        );
+        assert_eq!(
+            compact.shortest_distance(
+                &cell_index_from_position(head, 11),
+                &[cell_index_from_position(food[0], 11)],
+                None
+            ),
+            Some(10)
+        );
+    }
+
+    #[test]
+    fn test_wrapped_move_between() {
+        assert_eq!(
+            wrapped_move_between(&Position { x: 0, y: 0 }, &Position { x: 0, y: 10 }, 11, 11),
+            Some(Move::Down)
+        );
+        assert_eq!(
+            wrapped_move_between(&Position { x: 10, y: 3 }, &Position { x: 0, y: 3 }, 11, 11),
+            Some(Move::Right)
+        );
+        assert_eq!(
+            wrapped_move_between(&Position { x: 4, y: 4 }, &Position { x: 6, y: 4 }, 11, 11),
+            None
+        );
+        assert_eq!(
+            wrapped_dist_between(&Position { x: 0, y: 0 }, &Position { x: 10, y: 10 }, 11, 11),
+            2
+        );
+    }
+
+    #[test]
+    fn test_hazard_penalty_comes_from_the_rules() {
+        let board_json = r#"{"game":{"id":"","ruleset":{"name":"royale","version":"v1.0.17"},"timeout":500},"turn":60,"board":{"height":11,"width":11,"food":[],"hazards":[{"x":1,"y":0}],"snakes":[{"id":"","name":"","health":86,"body":[{"x":10,"y":10}],"latency":0,"head":{"x":10,"y":10},"length":1,"shout":""}]},"you":{"id":"","name":"","health":86,"body":[{"x":10,"y":10}],"latency":0,"head":{"x":10,"y":10},"length":1,"shout":""}}"#;
+        let game: Game = serde_json::from_str(board_json).unwrap();
+        let start = Position { x: 0, y: 0 };
+        let target = [Position { x: 2, y: 0 }];
+
+        assert_eq!(game.shortest_distance(&start, &target, None), Some(3));
+        assert_eq!(
+            game.shortest_distance(
+                &start,
+                &target,
+                Some(APrimeOptions::for_rules(&Rules::default()))
+            ),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn test_passable_callback_walls_off_cells() {
+        let game = single_snake_game(11, 11);
+        let id_map = build_snake_id_map(&game);
+        let start = Position { x: 0, y: 5 };
+        let target = Position { x: 6, y: 5 };
+
+        // The only gap in the wall down x = 3 is at the bottom of the board
+        let wall = |p: &Position| p.x != 3 || p.y == 0;
+        let through_the_gap = || {
+            Some(APrimeOptions {
+                passable: Some(&wall),
+                ..Default::default()
+            })
+        };
+
+        assert_eq!(game.shortest_distance(&start, &[target], None), Some(6));
+        assert_eq!(
+            game.shortest_distance(&start, &[target], through_the_gap()),
+            Some(16)
+        );
+
+        let compact: CellBoard4Snakes11x11 =
+            battlesnake_game_types::compact_representation::CellBoard::convert_from_game(
+                game.clone(),
+                &id_map,
+            )
+            .unwrap();
+        assert_eq!(
+            compact.shortest_distance(
+                &cell_index_from_position(start, 11),
+                &[cell_index_from_position(target, 11)],
+                through_the_gap()
+            ),
+            Some(16)
+        );
+
+        let nowhere = |_: &Position| false;
+        assert_eq!(
+            game.shortest_distance(
+                &start,
+                &[target],
+                Some(APrimeOptions {
+                    passable: Some(&nowhere),
+                    ..Default::default()
+                })
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_default_options_charge_hazards_for_leaving_them() {
+        let board_json = r#"{"game":{"id":"","ruleset":{"name":"royale","version":"v1.0.17"},"timeout":500},"turn":60,"board":{"height":11,"width":11,"food":[],"hazards":[{"x":0,"y":0},{"x":2,"y":0}],"snakes":[{"id":"","name":"","health":86,"body":[{"x":10,"y":10}],"latency":0,"head":{"x":10,"y":10},"length":1,"shout":""}]},"you":{"id":"","name":"","health":86,"body":[{"x":10,"y":10}],"latency":0,"head":{"x":10,"y":10},"length":1,"shout":""}}"#;
+        let game: Game = serde_json::from_str(board_json).unwrap();
+
+        // The hazard we start in costs extra to leave, the one we end in doesn't
+        assert_eq!(
+            game.shortest_distance(&Position { x: 0, y: 0 }, &[Position { x: 1, y: 0 }], None),
+            Some(NEIGHBOR_DISTANCE + HAZARD_PENALTY)
+        );
+        assert_eq!(
+            game.shortest_distance(&Position { x: 1, y: 0 }, &[Position { x: 2, y: 0 }], None),
+            Some(NEIGHBOR_DISTANCE)
+        );
+    }
+
+    #[test]
+    fn test_extra_cost_is_charged_for_stepping_onto_a_cell() {
+        let game = single_snake_game(11, 11);
+        let start = Position { x: 0, y: 5 };
+        let target = [Position { x: 4, y: 5 }];
+
+        let row_is_dangerous = |p: &Position| if p.y == 5 { 10 } else { 0 };
+        let options = || {
+            Some(APrimeOptions {
+                extra_cost: Some(&row_is_dangerous),
+                ..Default::default()
+            })
+        };
+
+        // Straight along the row would cost 44, so we step off it and only pay for the target
+        assert_eq!(game.shortest_distance(&start, &target, options()), Some(16));
+        assert_ne!(game.shortest_path(&start, &target, options())[1].0.y, 5);
+    }
 }
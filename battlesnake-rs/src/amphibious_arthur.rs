@@ -3,19 +3,22 @@ use std::collections::VecDeque;
 use super::*;
 
 pub trait MoveToAndSpawn: NeighborDeterminableGame + PositionGettableGame {
-    fn move_to_and_opponent_sprawl(&self, coor: &Self::NativePositionType) -> Self;
+    fn move_to_and_opponent_sprawl(&self, coor: &Self::NativePositionType, rules: &Rules) -> Self;
 }
 
 use battlesnake_game_types::types::{
-    HeadGettableGame, HealthGettableGame, NeighborDeterminableGame, PositionGettableGame,
+    FoodGettableGame, HeadGettableGame, HealthGettableGame, LengthGettableGame,
+    NeighborDeterminableGame, PositionGettableGame, SizeDeterminableGame, SnakeBodyGettableGame,
     YouDeterminableGame,
 };
 use rand::seq::SliceRandom;
 
+use crate::safety::{HazardQueryable, SafetyAnalysis};
+
 impl MoveToAndSpawn for Game {
-    fn move_to_and_opponent_sprawl(&self, coor: &Position) -> Self {
+    fn move_to_and_opponent_sprawl(&self, coor: &Position, rules: &Rules) -> Self {
         let mut cloned = self.clone();
-        cloned.move_to(coor, &self.you.id);
+        cloned.move_to(coor, &self.you.id, rules);
 
         let opponents = cloned
             .board
@@ -39,6 +42,7 @@ fn score<
     game_state: &T,
     coor: &T::NativePositionType,
     times_to_recurse: u8,
+    rules: &Rules,
 ) -> i64 {
     const PREFERRED_HEALTH: i64 = 80;
     let you_id = game_state.you_id();
@@ -64,9 +68,10 @@ fn score<
         .into_iter()
         .map(|c| {
             score(
-                &game_state.move_to_and_opponent_sprawl(coor),
+                &game_state.move_to_and_opponent_sprawl(coor, rules),
                 &c,
                 times_to_recurse - 1,
+                rules,
             )
         })
         .sum();
@@ -76,6 +81,7 @@ fn score<
 
 pub struct AmphibiousArthur<T> {
     game: T,
+    rules: Rules,
 }
 
 impl<
@@ -84,7 +90,12 @@ impl<
             + HeadGettableGame
             + YouDeterminableGame
             + MoveToAndSpawn
-            + HealthGettableGame,
+            + HealthGettableGame
+            + LengthGettableGame
+            + SnakeBodyGettableGame
+            + FoodGettableGame
+            + SizeDeterminableGame
+            + HazardQueryable,
     > BattlesnakeAI for AmphibiousArthur<T>
 {
     fn make_move(&self) -> Result<MoveOutput, Box<dyn std::error::Error + Send + Sync>> {
@@ -96,9 +107,13 @@ impl<
             Ok(Ok(x)) => x,
             _ => 5,
         };
+        // Only score the moves that are as safe as we can get this turn
+        let safest = SafetyAnalysis::for_you(&self.game, &self.rules)
+            .filter_moves(&possible.iter().map(|(dir, _)| *dir).collect::<Vec<_>>());
         let next_move = possible
             .iter()
-            .max_by_key(|(_mv, coor)| score(&self.game, coor, recursion_limit));
+            .filter(|(dir, _)| safest.contains(dir))
+            .max_by_key(|(_mv, coor)| score(&self.game, coor, recursion_limit, &self.rules));
 
         let stuck_response: MoveOutput = MoveOutput {
             r#move: format!("{}", Move::Up),
@@ -122,7 +137,14 @@ impl BattlesnakeFactory for AmphibiousArthurFactory {
     }
 
     fn from_wire_game(&self, game: Game) -> BoxedSnake {
-        Box::new(AmphibiousArthur { game })
+        self.from_wire_game_with_context(game, GameContext::default())
+    }
+
+    fn from_wire_game_with_context(&self, game: Game, context: GameContext) -> BoxedSnake {
+        Box::new(AmphibiousArthur {
+            game,
+            rules: context.rules,
+        })
     }
 
     fn about(&self) -> AboutMe {
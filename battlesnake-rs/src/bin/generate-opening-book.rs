@@ -0,0 +1,112 @@
+//! Fills an opening book by searching deep on each starting position, and on every position
+//! the opponents can reply with for the first few turns
+//!
+//! Usage: generate-opening-book <book.json> <turns> <search-turns> <start.json>...
+//!
+//! An existing book is added to rather than replaced, so runs can be split up. Positions it
+//! already has keep their move, but get filled in deeper if this run asks for more turns. The
+//! ruleset settings are read from each start file, the same way the server reads them from a
+//! request
+
+use battlesnake_game_types::compact_representation::{CellBoard, CellBoard4Snakes11x11};
+use battlesnake_game_types::types::*;
+use battlesnake_game_types::wire_representation::Game;
+use battlesnake_rs::devious_devin_eval::minmax_bench_entry;
+use battlesnake_rs::devious_devin_mutable::Instruments;
+use battlesnake_rs::evaluator::AreaControlEvaluator;
+use battlesnake_rs::opening_book::OpeningBook;
+use battlesnake_rs::rules::Rules;
+
+use itertools::Itertools;
+
+/// Our move from a deep search, or `None` for boards the compact search can't handle
+fn book_move(game: &Game, rules: &Rules, search_turns: usize) -> Option<Move> {
+    let id_map = build_snake_id_map(game);
+    let board: CellBoard4Snakes11x11 = CellBoard::convert_from_game(game.clone(), &id_map).ok()?;
+    let evaluator = AreaControlEvaluator { rules: *rules };
+
+    minmax_bench_entry(board, search_turns, &evaluator).direction_for(board.you_id())
+}
+
+/// Every combination of moves the opponents can make that doesn't run straight into a body
+fn opponent_replies(game: &Game) -> Vec<Vec<(String, Move)>> {
+    game.get_snake_ids()
+        .into_iter()
+        .filter(|id| id != game.you_id() && game.is_alive(id))
+        .map(|id| {
+            let moves: Vec<_> = game
+                .possible_moves(&game.get_head_as_native_position(&id))
+                .into_iter()
+                .filter(|(_, c)| !game.position_is_snake_body(*c))
+                .map(|(dir, _)| (id.clone(), dir))
+                .collect();
+            moves
+        })
+        .multi_cartesian_product()
+        .collect()
+}
+
+fn fill(book: &mut OpeningBook, game: Game, rules: &Rules, turns: usize, search_turns: usize) {
+    if turns == 0 || game.is_over() {
+        return;
+    }
+    // Another branch or an earlier run may have got here already, but not as deep
+    if book
+        .turns_filled(&game, rules)
+        .map_or(false, |filled| filled >= turns)
+    {
+        return;
+    }
+
+    let m = match book
+        .lookup(&game, rules)
+        .or_else(|| book_move(&game, rules, search_turns))
+    {
+        Some(m) => m,
+        None => return,
+    };
+    book.insert(&game, rules, m, turns);
+    println!("{} turns left: {} ({} positions)", turns, m, book.len());
+
+    for replies in opponent_replies(&game) {
+        let moves = std::iter::once((game.you_id().clone(), vec![m]))
+            .chain(replies.into_iter().map(|(id, dir)| (id, vec![dir])))
+            .collect();
+
+        if let Some((_, next)) = game
+            .simulate_with_moves(&Instruments, moves)
+            .into_iter()
+            .next()
+        {
+            fill(book, next, rules, turns - 1, search_turns);
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 5 {
+        return Err(
+            "Usage: generate-opening-book <book.json> <turns> <search-turns> <start.json>..."
+                .into(),
+        );
+    }
+
+    let book_path = &args[1];
+    let turns: usize = args[2].parse()?;
+    let search_turns: usize = args[3].parse()?;
+
+    let mut book = OpeningBook::load(book_path).unwrap_or_default();
+
+    for start_path in &args[4..] {
+        let body: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(start_path)?)?;
+        let rules = Rules::from_request_body(&body);
+        let game: Game = serde_json::from_value(body)?;
+        fill(&mut book, game, &rules, turns, search_turns);
+    }
+
+    book.save(book_path)?;
+    println!("Saved {} positions to {}", book.len(), book_path);
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+//! Solves duel endings exactly and writes them to a tablebase file
+//!
+//! Usage: generate-tablebase <tablebase.bin> <budget> <position.json>...
+//!
+//! Every position that gets fully solved on the way to each root is kept. An existing
+//! tablebase is added to rather than replaced, so runs can be split up
+
+use battlesnake_game_types::compact_representation::{CellBoard, CellBoard4Snakes11x11};
+use battlesnake_game_types::types::*;
+use battlesnake_game_types::wire_representation::Game;
+use battlesnake_rs::search_deadline::SearchDeadline;
+use battlesnake_rs::tablebase::Tablebase;
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        return Err("Usage: generate-tablebase <tablebase.bin> <budget> <position.json>...".into());
+    }
+
+    let tablebase_path = &args[1];
+    let budget: usize = args[2].parse()?;
+
+    let mut tablebase = Tablebase::load(tablebase_path).unwrap_or_default();
+
+    for position_path in &args[3..] {
+        let game: Game = serde_json::from_str(&std::fs::read_to_string(position_path)?)?;
+        let id_map = build_snake_id_map(&game);
+        let board: CellBoard4Snakes11x11 =
+            CellBoard::convert_from_game(game, &id_map).map_err(|e| e.to_string())?;
+
+        let mut players: Vec<_> = board
+            .get_snake_ids()
+            .into_iter()
+            .filter(|s| board.is_alive(s))
+            .collect();
+        players.sort_by_key(|s| if s == board.you_id() { -1 } else { 1 });
+
+        match tablebase.solve(&board, &players, None, &SearchDeadline::unbounded(), budget) {
+            Some(entry) => println!("{}: {:?}", position_path, entry),
+            None => println!("{}: ran out of budget", position_path),
+        }
+        println!("{} positions solved so far", tablebase.len());
+    }
+
+    tablebase.save(tablebase_path)?;
+    println!("Saved {} positions to {}", tablebase.len(), tablebase_path);
+
+    Ok(())
+}
@@ -0,0 +1,468 @@
+use battlesnake_game_types::types::*;
+use battlesnake_game_types::wire_representation::Position;
+
+use crate::flood_fill::turns_until_vacated;
+
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
+
+/// One bit per cell of the board, cell `y * width + x` lives in bit `% 128` of word `/ 128`
+///
+/// A single word fits an 11x11 board, and bigger boards need more of them. Cells past the end
+/// of the board are always kept clear
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitBoard<const WORDS: usize> {
+    words: [u128; WORDS],
+}
+
+pub type BitBoard11x11 = BitBoard<1>;
+pub type BitBoard19x19 = BitBoard<3>;
+pub type BitBoard25x25 = BitBoard<5>;
+
+impl<const WORDS: usize> Default for BitBoard<WORDS> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<const WORDS: usize> BitBoard<WORDS> {
+    pub const CAPACITY: usize = 128 * WORDS;
+
+    pub fn empty() -> Self {
+        Self { words: [0; WORDS] }
+    }
+
+    pub fn set(&mut self, index: usize) {
+        self.words[index / 128] |= 1u128 << (index % 128);
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        self.words[index / 128] & (1u128 << (index % 128)) != 0
+    }
+
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    /// The index of every set bit, lowest first
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, word)| {
+            (0..128)
+                .filter(move |bit| word & (1u128 << bit) != 0)
+                .map(move |bit| i * 128 + bit)
+        })
+    }
+
+    /// Moves every bit `n` cells up in index, carrying between words. `n` must be less than 128
+    fn shift_up(&self, n: usize) -> Self {
+        if n == 0 {
+            return *self;
+        }
+
+        let mut shifted = Self::empty();
+        for i in (0..WORDS).rev() {
+            shifted.words[i] = self.words[i] << n;
+            if i > 0 {
+                shifted.words[i] |= self.words[i - 1] >> (128 - n);
+            }
+        }
+
+        shifted
+    }
+
+    /// Moves every bit `n` cells down in index, carrying between words. `n` must be less than
+    /// 128
+    fn shift_down(&self, n: usize) -> Self {
+        if n == 0 {
+            return *self;
+        }
+
+        let mut shifted = Self::empty();
+        for i in 0..WORDS {
+            shifted.words[i] = self.words[i] >> n;
+            if i + 1 < WORDS {
+                shifted.words[i] |= self.words[i + 1] << (128 - n);
+            }
+        }
+
+        shifted
+    }
+}
+
+impl<const WORDS: usize> BitOr for BitBoard<WORDS> {
+    type Output = Self;
+
+    fn bitor(mut self, rhs: Self) -> Self {
+        self |= rhs;
+        self
+    }
+}
+
+impl<const WORDS: usize> BitOrAssign for BitBoard<WORDS> {
+    fn bitor_assign(&mut self, rhs: Self) {
+        for (w, r) in self.words.iter_mut().zip(rhs.words.iter()) {
+            *w |= r;
+        }
+    }
+}
+
+impl<const WORDS: usize> BitAnd for BitBoard<WORDS> {
+    type Output = Self;
+
+    fn bitand(mut self, rhs: Self) -> Self {
+        self &= rhs;
+        self
+    }
+}
+
+impl<const WORDS: usize> BitAndAssign for BitBoard<WORDS> {
+    fn bitand_assign(&mut self, rhs: Self) {
+        for (w, r) in self.words.iter_mut().zip(rhs.words.iter()) {
+            *w &= r;
+        }
+    }
+}
+
+/// Flips every bit, including the ones past the end of the board. Mask with
+/// `BitGeometry::full` before counting
+impl<const WORDS: usize> Not for BitBoard<WORDS> {
+    type Output = Self;
+
+    fn not(mut self) -> Self {
+        for w in self.words.iter_mut() {
+            *w = !*w;
+        }
+        self
+    }
+}
+
+/// The masks we need to move bits around a board of a given size without them falling off one
+/// edge and coming back on the next row
+#[derive(Debug, Clone, Copy)]
+pub struct BitGeometry<const WORDS: usize> {
+    pub width: usize,
+    pub height: usize,
+    /// Every cell on the board
+    pub full: BitBoard<WORDS>,
+    not_first_column: BitBoard<WORDS>,
+    not_last_column: BitBoard<WORDS>,
+}
+
+impl<const WORDS: usize> BitGeometry<WORDS> {
+    /// `None` when the board has more cells than `WORDS` can hold
+    pub fn new(width: usize, height: usize) -> Option<Self> {
+        if width == 0 || width >= 128 || width * height > BitBoard::<WORDS>::CAPACITY {
+            return None;
+        }
+
+        let mut full = BitBoard::empty();
+        let mut not_first_column = BitBoard::empty();
+        let mut not_last_column = BitBoard::empty();
+        for y in 0..height {
+            for x in 0..width {
+                let index = y * width + x;
+                full.set(index);
+                if x != 0 {
+                    not_first_column.set(index);
+                }
+                if x != width - 1 {
+                    not_last_column.set(index);
+                }
+            }
+        }
+
+        Some(Self {
+            width,
+            height,
+            full,
+            not_first_column,
+            not_last_column,
+        })
+    }
+
+    pub fn index(&self, pos: &Position) -> usize {
+        pos.y as usize * self.width + pos.x as usize
+    }
+
+    pub fn position(&self, index: usize) -> Position {
+        Position {
+            x: (index % self.width) as i32,
+            y: (index / self.width) as i32,
+        }
+    }
+
+    pub fn mask<'a>(&self, cells: impl IntoIterator<Item = &'a Position>) -> BitBoard<WORDS> {
+        let mut mask = BitBoard::empty();
+        for cell in cells {
+            mask.set(self.index(cell));
+        }
+
+        mask
+    }
+
+    /// Every cell next to one of `cells`, all four directions at once
+    pub fn spread(&self, cells: &BitBoard<WORDS>) -> BitBoard<WORDS> {
+        let right = cells.shift_up(1) & self.not_first_column;
+        let left = cells.shift_down(1) & self.not_last_column;
+        let up = cells.shift_up(self.width);
+        let down = cells.shift_down(self.width);
+
+        (right | left | up | down) & self.full
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BitSnake<S> {
+    pub id: S,
+    pub head: usize,
+    pub length: i64,
+}
+
+/// A bitboard view of a game, built once and then flood filled as often as we like
+#[derive(Debug, Clone)]
+pub struct BitGame<S, const WORDS: usize> {
+    pub geometry: BitGeometry<WORDS>,
+    /// `occupied[t]` is every body segment that will still be on the board `t` turns from now,
+    /// anything past the end of the `Vec` has moved out of the way
+    occupied: Vec<BitBoard<WORDS>>,
+    /// Only the snakes that are still alive
+    pub snakes: Vec<BitSnake<S>>,
+}
+
+impl<S: PartialEq, const WORDS: usize> BitGame<S, WORDS> {
+    /// `None` when the board doesn't fit in `WORDS`
+    pub fn from_game<T>(game: &T) -> Option<Self>
+    where
+        T: SnakeIDGettableGame<SnakeIDType = S>
+            + SnakeBodyGettableGame
+            + HeadGettableGame
+            + LengthGettableGame
+            + HealthGettableGame
+            + PositionGettableGame
+            + SizeDeterminableGame,
+    {
+        let geometry = BitGeometry::new(game.get_width() as usize, game.get_height() as usize)?;
+
+        let mut occupied: Vec<BitBoard<WORDS>> = vec![];
+        for (segment, turns) in turns_until_vacated(game) {
+            let index = geometry.index(&game.position_from_native(segment));
+            if occupied.len() < turns {
+                occupied.resize(turns, BitBoard::empty());
+            }
+            for mask in occupied.iter_mut().take(turns) {
+                mask.set(index);
+            }
+        }
+
+        let snakes = game
+            .get_snake_ids()
+            .into_iter()
+            .filter(|s| game.is_alive(s))
+            .map(|id| BitSnake {
+                head: geometry
+                    .index(&game.position_from_native(game.get_head_as_native_position(&id))),
+                length: game.get_length_i64(&id),
+                id,
+            })
+            .collect();
+
+        Some(Self {
+            geometry,
+            occupied,
+            snakes,
+        })
+    }
+
+    pub fn snake(&self, snake_id: &S) -> Option<&BitSnake<S>> {
+        self.snakes.iter().find(|s| &s.id == snake_id)
+    }
+
+    /// The body segments still on the board `turn` turns from now
+    pub fn occupied_at(&self, turn: usize) -> BitBoard<WORDS> {
+        self.occupied.get(turn).copied().unwrap_or_default()
+    }
+
+    /// A breadth first search from all of the `sources` at once, one whole layer per step
+    ///
+    /// `layers[d]` is every cell first reached on turn `d`. Body segments are walls until the
+    /// turn their snake's tail has moved off of them, the same as in `VoronoiCalculable`
+    pub fn flood_layers(&self, sources: BitBoard<WORDS>) -> Vec<BitBoard<WORDS>> {
+        let mut seen = sources;
+        let mut layers = vec![sources];
+
+        let mut turn = 0;
+        loop {
+            turn += 1;
+
+            let frontier = layers.last().unwrap();
+            let next = self.geometry.spread(frontier) & !seen & !self.occupied_at(turn);
+            if next.is_empty() {
+                break;
+            }
+
+            seen |= next;
+            layers.push(next);
+        }
+
+        layers
+    }
+
+    /// Turns until the search from `sources` first gets to one of the `targets`
+    pub fn distance(&self, sources: BitBoard<WORDS>, targets: BitBoard<WORDS>) -> Option<usize> {
+        self.flood_layers(sources)
+            .iter()
+            .position(|layer| !(*layer & targets).is_empty())
+    }
+
+    /// How many cells this snake could reach if it had the board to itself, including its head
+    pub fn reachable_area(&self, snake_id: &S) -> usize {
+        let snake = match self.snake(snake_id) {
+            Some(s) => s,
+            None => return 0,
+        };
+
+        let mut head = BitBoard::empty();
+        head.set(snake.head);
+
+        self.flood_layers(head).iter().map(BitBoard::count).sum()
+    }
+
+    /// Cells a snake at least as long as this one could move onto next turn, where we would
+    /// lose a head to head
+    pub fn head_danger(&self, snake_id: &S) -> BitBoard<WORDS> {
+        let length = match self.snake(snake_id) {
+            Some(s) => s.length,
+            None => return BitBoard::empty(),
+        };
+
+        let mut heads = BitBoard::empty();
+        for other in self
+            .snakes
+            .iter()
+            .filter(|s| &s.id != snake_id && s.length >= length)
+        {
+            heads.set(other.head);
+        }
+
+        self.geometry.spread(&heads)
+    }
+}
+
+/// `reachable_area` with the fewest words that fit the board, `None` when it's bigger than we
+/// have a bitboard for
+pub fn bit_reachable_area<T>(game: &T, snake_id: &T::SnakeIDType) -> Option<usize>
+where
+    T: SnakeIDGettableGame
+        + SnakeBodyGettableGame
+        + HeadGettableGame
+        + LengthGettableGame
+        + HealthGettableGame
+        + PositionGettableGame
+        + SizeDeterminableGame,
+{
+    if let Some(bits) = BitGame::<_, 1>::from_game(game) {
+        return Some(bits.reachable_area(snake_id));
+    }
+    if let Some(bits) = BitGame::<_, 3>::from_game(game) {
+        return Some(bits.reachable_area(snake_id));
+    }
+    BitGame::<_, 5>::from_game(game).map(|bits| bits.reachable_area(snake_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flood_fill::VoronoiCalculable;
+    use battlesnake_game_types::wire_representation::Game;
+
+    #[test]
+    fn test_spread_stays_on_the_board() {
+        let geometry = BitGeometry::<1>::new(11, 11).unwrap();
+
+        let corner = geometry.mask(&[Position { x: 10, y: 0 }]);
+        assert_eq!(
+            geometry.spread(&corner),
+            geometry.mask(&[Position { x: 9, y: 0 }, Position { x: 10, y: 1 }])
+        );
+
+        let edge = geometry.mask(&[Position { x: 0, y: 5 }]);
+        assert_eq!(
+            geometry.spread(&edge),
+            geometry.mask(&[
+                Position { x: 1, y: 5 },
+                Position { x: 0, y: 4 },
+                Position { x: 0, y: 6 }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_spread_carries_between_words() {
+        let geometry = BitGeometry::<3>::new(19, 19).unwrap();
+
+        // Index 127 is the last bit of the first word, and 146 is the row above it
+        let cell = geometry.position(127);
+        let spread = geometry.spread(&geometry.mask(&[cell]));
+
+        assert!(spread.get(128));
+        assert!(spread.get(146));
+        assert!(spread.get(126));
+        assert!(spread.get(108));
+        assert_eq!(spread.count(), 4);
+    }
+
+    #[test]
+    fn test_reachable_area_matches_the_flood_fill() {
+        let games: Vec<Game> = vec![
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap(),
+            serde_json::from_str(include_str!("../fixtures/a-prime-food-maze.json")).unwrap(),
+            serde_json::from_str(r#"{"game":{"id":"","ruleset":{"name":"standard","version":"v1.0.17"},"timeout":500},"turn":10,"board":{"height":11,"width":11,"food":[],"hazards":[],"snakes":[{"id":"you","name":"","health":90,"body":[{"x":0,"y":0},{"x":1,"y":0},{"x":1,"y":1},{"x":1,"y":2},{"x":0,"y":2}],"latency":0,"head":{"x":0,"y":0},"length":5,"shout":""}]},"you":{"id":"you","name":"","health":90,"body":[{"x":0,"y":0},{"x":1,"y":0},{"x":1,"y":1},{"x":1,"y":2},{"x":0,"y":2}],"latency":0,"head":{"x":0,"y":0},"length":5,"shout":""}}"#).unwrap(),
+        ];
+
+        for game in games {
+            let bits = BitGame::<_, 1>::from_game(&game).unwrap();
+
+            for snake_id in game.get_snake_ids() {
+                assert_eq!(
+                    bits.reachable_area(&snake_id),
+                    game.reachable_area(&snake_id)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_bigger_boards_use_more_words() {
+        let snake = r#"{"id":"you","name":"","health":86,"body":[{"x":9,"y":9},{"x":9,"y":8},{"x":9,"y":7}],"latency":0,"head":{"x":9,"y":9},"length":3,"shout":""}"#;
+        let game: Game = serde_json::from_str(&format!(
+            r#"{{"game":{{"id":"","ruleset":{{"name":"standard","version":"v1.0.17"}},"timeout":500}},"turn":10,"board":{{"height":19,"width":19,"food":[],"hazards":[],"snakes":[{}]}},"you":{}}}"#,
+            snake, snake
+        ))
+        .unwrap();
+
+        assert!(BitGame::<_, 1>::from_game(&game).is_none());
+        assert_eq!(
+            bit_reachable_area(&game, &"you".to_owned()),
+            Some(game.reachable_area(&"you".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_head_danger_only_counts_longer_snakes() {
+        let board_json = r#"{"game":{"id":"","ruleset":{"name":"standard","version":"v1.0.17"},"timeout":500},"turn":10,"board":{"height":11,"width":11,"food":[],"hazards":[],"snakes":[{"id":"you","name":"","health":90,"body":[{"x":5,"y":5},{"x":5,"y":4},{"x":5,"y":3}],"latency":0,"head":{"x":5,"y":5},"length":3,"shout":""},{"id":"big","name":"","health":90,"body":[{"x":7,"y":5},{"x":8,"y":5},{"x":9,"y":5},{"x":10,"y":5}],"latency":0,"head":{"x":7,"y":5},"length":4,"shout":""},{"id":"small","name":"","health":90,"body":[{"x":3,"y":5},{"x":2,"y":5}],"latency":0,"head":{"x":3,"y":5},"length":2,"shout":""}]},"you":{"id":"you","name":"","health":90,"body":[{"x":5,"y":5},{"x":5,"y":4},{"x":5,"y":3}],"latency":0,"head":{"x":5,"y":5},"length":3,"shout":""}}"#;
+        let game: Game = serde_json::from_str(board_json).unwrap();
+        let bits = BitGame::<_, 1>::from_game(&game).unwrap();
+
+        let danger = bits.head_danger(&"you".to_owned());
+        assert!(danger.get(bits.geometry.index(&Position { x: 6, y: 5 })));
+        assert!(!danger.get(bits.geometry.index(&Position { x: 4, y: 5 })));
+
+        let food = bits.geometry.mask(&[Position { x: 5, y: 8 }]);
+        let mut head = BitBoard::empty();
+        head.set(bits.geometry.index(&Position { x: 5, y: 5 }));
+        assert_eq!(bits.distance(head, food), Some(3));
+    }
+}
@@ -1,15 +1,27 @@
-use battlesnake_game_types::types::{
-    RandomReasonableMovesGame, SnakeIDGettableGame, YouDeterminableGame,
-};
+use battlesnake_game_types::types::*;
+
+use crate::safety::{HazardQueryable, SafetyAnalysis};
 
 use super::*;
 
 pub struct BombasticBob<T> {
     game: T,
+    rules: Rules,
 }
 
-impl<T: RandomReasonableMovesGame + SnakeIDGettableGame + YouDeterminableGame> BattlesnakeAI
-    for BombasticBob<T>
+impl<T> BattlesnakeAI for BombasticBob<T>
+where
+    T: RandomReasonableMovesGame
+        + SnakeIDGettableGame
+        + YouDeterminableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + SnakeBodyGettableGame
+        + FoodGettableGame
+        + NeighborDeterminableGame
+        + SizeDeterminableGame
+        + HazardQueryable,
 {
     fn make_move(&self) -> Result<MoveOutput, Box<dyn std::error::Error + Send + Sync>> {
         let chosen = self
@@ -20,6 +32,9 @@ impl<T: RandomReasonableMovesGame + SnakeIDGettableGame + YouDeterminableGame> B
             .map(|x| x.1);
         let dir = chosen.unwrap_or(Move::Right);
 
+        // Random is fine, as long as it isn't random into a head to head we lose
+        let dir = SafetyAnalysis::for_you(&self.game, &self.rules).choose(dir);
+
         Ok(MoveOutput {
             r#move: format!("{}", dir),
             shout: None,
@@ -35,7 +50,14 @@ impl BattlesnakeFactory for BombasticBobFactory {
     }
 
     fn from_wire_game(&self, game: Game) -> BoxedSnake {
-        Box::new(BombasticBob { game })
+        self.from_wire_game_with_context(game, GameContext::default())
+    }
+
+    fn from_wire_game_with_context(&self, game: Game, context: GameContext) -> BoxedSnake {
+        Box::new(BombasticBob {
+            game,
+            rules: context.rules,
+        })
     }
 
     fn about(&self) -> AboutMe {
@@ -0,0 +1,380 @@
+use battlesnake_game_types::types::*;
+
+use rustc_hash::FxHashMap;
+use std::collections::VecDeque;
+use std::hash::Hash;
+
+/// The free cells around some starting cells, split up by their chokepoints
+///
+/// An articulation point is a cell that splits the free space it is in when something moves
+/// onto it. Taking every articulation point out leaves the chambers, pockets of space that are
+/// only connected to each other through articulation points. Snake bodies are treated as walls
+/// for good, so this is a worst case that doesn't count on any tails moving out of the way
+pub struct Chambers<P> {
+    cells: Vec<P>,
+    index_of: FxHashMap<P, usize>,
+    adjacency: Vec<Vec<usize>>,
+    articulation: Vec<bool>,
+    /// `None` for articulation points, which sit between chambers rather than in one
+    chamber: Vec<Option<usize>>,
+    chamber_sizes: Vec<usize>,
+    /// Which connected piece of free space each cell is in
+    region: Vec<usize>,
+    region_sizes: Vec<usize>,
+}
+
+impl<P: Hash + Eq + Clone> Chambers<P> {
+    /// Every free cell we looked at
+    pub fn cells(&self) -> &[P] {
+        &self.cells
+    }
+
+    pub fn is_articulation_point(&self, cell: &P) -> bool {
+        self.index_of
+            .get(cell)
+            .map_or(false, |i| self.articulation[*i])
+    }
+
+    pub fn articulation_points(&self) -> impl Iterator<Item = &P> + '_ {
+        self.cells
+            .iter()
+            .zip(self.articulation.iter())
+            .filter(|(_, is_articulation)| **is_articulation)
+            .map(|(cell, _)| cell)
+    }
+
+    /// Which chamber this cell is in, `None` for articulation points and cells we didn't look
+    /// at
+    pub fn chamber_of(&self, cell: &P) -> Option<usize> {
+        self.index_of.get(cell).and_then(|i| self.chamber[*i])
+    }
+
+    pub fn chamber_sizes(&self) -> &[usize] {
+        &self.chamber_sizes
+    }
+
+    /// The most cells a snake could fill after moving onto `cell`, counting `cell` itself
+    ///
+    /// Moving onto an articulation point seals off all but one side of it, so then we only
+    /// get the biggest side. Anywhere else we keep all of the free space we are in
+    pub fn space_after_entering(&self, cell: &P) -> usize {
+        let index = match self.index_of.get(cell) {
+            Some(i) => *i,
+            None => return 0,
+        };
+
+        if !self.articulation[index] {
+            return self.region_sizes[self.region[index]];
+        }
+
+        let mut seen = vec![false; self.cells.len()];
+        seen[index] = true;
+
+        let mut best_side = 0;
+        for start in self.adjacency[index].iter() {
+            if seen[*start] {
+                continue;
+            }
+
+            seen[*start] = true;
+            let mut queue = VecDeque::from(vec![*start]);
+            let mut side = 0;
+            while let Some(current) = queue.pop_front() {
+                side += 1;
+                for next in self.adjacency[current].iter() {
+                    if !seen[*next] {
+                        seen[*next] = true;
+                        queue.push_back(*next);
+                    }
+                }
+            }
+
+            best_side = best_side.max(side);
+        }
+
+        best_side + 1
+    }
+}
+
+/// Hopcroft and Tarjan's depth first search. Low links are the earliest discovery time each
+/// subtree can get back to without going through its parent
+fn find_articulation_points(adjacency: &[Vec<usize>]) -> Vec<bool> {
+    let count = adjacency.len();
+    let mut discovered: Vec<Option<usize>> = vec![None; count];
+    let mut low = vec![0; count];
+    let mut articulation = vec![false; count];
+    let mut time = 0;
+
+    for root in 0..count {
+        if discovered[root].is_some() {
+            continue;
+        }
+
+        discovered[root] = Some(time);
+        low[root] = time;
+        time += 1;
+
+        let mut root_children = 0;
+        // Each frame is a cell, its parent and how many of its neighbors we've gone through.
+        // Done by hand, instead of recursing, so a big open board can't blow the stack
+        let mut stack: Vec<(usize, Option<usize>, usize)> = vec![(root, None, 0)];
+
+        while let Some((cell, parent, next_neighbor)) = stack.pop() {
+            if let Some(&neighbor) = adjacency[cell].get(next_neighbor) {
+                stack.push((cell, parent, next_neighbor + 1));
+
+                if Some(neighbor) == parent {
+                    continue;
+                }
+
+                match discovered[neighbor] {
+                    Some(neighbor_time) => low[cell] = low[cell].min(neighbor_time),
+                    None => {
+                        discovered[neighbor] = Some(time);
+                        low[neighbor] = time;
+                        time += 1;
+
+                        if cell == root {
+                            root_children += 1;
+                        }
+                        stack.push((neighbor, Some(cell), 0));
+                    }
+                }
+            } else if let Some(parent) = parent {
+                low[parent] = low[parent].min(low[cell]);
+                if parent != root && low[cell] >= discovered[parent].unwrap() {
+                    articulation[parent] = true;
+                }
+            }
+        }
+
+        articulation[root] = root_children > 1;
+    }
+
+    articulation
+}
+
+/// Labels each cell with the connected piece it is in, skipping the ones `skip` says to.
+/// Returns the label of every cell along with the size of each piece
+fn label_components(
+    adjacency: &[Vec<usize>],
+    skip: impl Fn(usize) -> bool,
+) -> (Vec<Option<usize>>, Vec<usize>) {
+    let mut labels: Vec<Option<usize>> = vec![None; adjacency.len()];
+    let mut sizes = vec![];
+
+    for start in 0..adjacency.len() {
+        if labels[start].is_some() || skip(start) {
+            continue;
+        }
+
+        let label = sizes.len();
+        labels[start] = Some(label);
+        let mut size = 0;
+        let mut queue = VecDeque::from(vec![start]);
+        while let Some(current) = queue.pop_front() {
+            size += 1;
+            for next in adjacency[current].iter() {
+                if labels[*next].is_none() && !skip(*next) {
+                    labels[*next] = Some(label);
+                    queue.push_back(*next);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+
+    (labels, sizes)
+}
+
+pub trait ChamberCalculable: NeighborDeterminableGame + PositionGettableGame {
+    /// Finds the chambers in all the free space reachable from the free cells in `from`
+    fn chambers(&self, from: &[Self::NativePositionType]) -> Chambers<Self::NativePositionType>;
+
+    /// The `space_after_entering` for each move from `head` that doesn't run straight into a
+    /// snake
+    fn space_by_move(&self, head: &Self::NativePositionType) -> Vec<(Move, usize)>;
+}
+
+impl<T> ChamberCalculable for T
+where
+    T: NeighborDeterminableGame + PositionGettableGame,
+{
+    fn chambers(&self, from: &[Self::NativePositionType]) -> Chambers<Self::NativePositionType> {
+        let mut cells: Vec<T::NativePositionType> = vec![];
+        let mut index_of: FxHashMap<T::NativePositionType, usize> = FxHashMap::default();
+
+        for start in from {
+            if !index_of.contains_key(start) && !self.position_is_snake_body(start.clone()) {
+                index_of.insert(start.clone(), cells.len());
+                cells.push(start.clone());
+            }
+        }
+
+        // Cells are numbered in the order we find them, so the adjacency can be filled in as
+        // we go
+        let mut adjacency: Vec<Vec<usize>> = vec![];
+        let mut next = 0;
+        while next < cells.len() {
+            let mut neighbors = vec![];
+            for neighbor in self.neighbors(&cells[next]) {
+                if self.position_is_snake_body(neighbor.clone()) {
+                    continue;
+                }
+
+                let index = *index_of.entry(neighbor.clone()).or_insert_with(|| {
+                    cells.push(neighbor);
+                    cells.len() - 1
+                });
+                neighbors.push(index);
+            }
+
+            adjacency.push(neighbors);
+            next += 1;
+        }
+
+        let articulation = find_articulation_points(&adjacency);
+        let (chamber, chamber_sizes) = label_components(&adjacency, |i| articulation[i]);
+        let (region, region_sizes) = label_components(&adjacency, |_| false);
+
+        Chambers {
+            cells,
+            index_of,
+            adjacency,
+            articulation,
+            chamber,
+            chamber_sizes,
+            region: region.into_iter().map(Option::unwrap).collect(),
+            region_sizes,
+        }
+    }
+
+    fn space_by_move(&self, head: &Self::NativePositionType) -> Vec<(Move, usize)> {
+        let moves: Vec<_> = self
+            .possible_moves(head)
+            .into_iter()
+            .filter(|(_, cell)| !self.position_is_snake_body(cell.clone()))
+            .collect();
+        let cells: Vec<_> = moves.iter().map(|(_, cell)| cell.clone()).collect();
+        let chambers = self.chambers(&cells);
+
+        moves
+            .into_iter()
+            .map(|(m, cell)| (m, chambers.space_after_entering(&cell)))
+            .collect()
+    }
+}
+
+/// Keeps `preferred` unless it leads somewhere without room for all `needed` cells of us and
+/// some other move does. Then we take whichever move leaves us the most space
+pub fn avoid_traps<T: ChamberCalculable>(
+    game: &T,
+    head: &T::NativePositionType,
+    preferred: Move,
+    needed: usize,
+) -> Move {
+    let space_by_move = game.space_by_move(head);
+
+    let preferred_space = space_by_move
+        .iter()
+        .find(|(m, _)| *m == preferred)
+        .map_or(0, |(_, space)| *space);
+    if preferred_space >= needed {
+        return preferred;
+    }
+
+    space_by_move
+        .into_iter()
+        .max_by_key(|(_, space)| *space)
+        .filter(|(_, space)| *space > preferred_space)
+        .map_or(preferred, |(m, _)| m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battlesnake_game_types::wire_representation::{Game, Position};
+
+    /// A wall of `other` across row 2 except for a one cell gap at x = 5, and we are out of
+    /// the way in the top right corner
+    fn walled_off_game() -> Game {
+        let wall: Vec<String> = (0..11)
+            .rev()
+            .filter(|x| *x != 5)
+            .map(|x| format!(r#"{{"x":{},"y":2}}"#, x))
+            .collect();
+        let you = r#"{"id":"you","name":"","health":90,"body":[{"x":10,"y":10}],"latency":0,"head":{"x":10,"y":10},"length":1,"shout":""}"#;
+        let board_json = format!(
+            r#"{{"game":{{"id":"","ruleset":{{"name":"standard","version":"v1.0.17"}},"timeout":500}},"turn":10,"board":{{"height":11,"width":11,"food":[],"hazards":[],"snakes":[{},{{"id":"other","name":"","health":90,"body":[{}],"latency":0,"head":{{"x":10,"y":2}},"length":10,"shout":""}}]}},"you":{}}}"#,
+            you,
+            wall.join(","),
+            you
+        );
+
+        serde_json::from_str(&board_json).unwrap()
+    }
+
+    #[test]
+    fn test_gap_in_a_wall_is_an_articulation_point() {
+        let game = walled_off_game();
+        let chambers = game.chambers(&[Position { x: 1, y: 0 }]);
+
+        // The gap, and the cells on either side of it that lead into it
+        let mut articulation_points: Vec<_> = chambers.articulation_points().cloned().collect();
+        articulation_points.sort_by_key(|p| p.y);
+        assert_eq!(
+            articulation_points,
+            vec![
+                Position { x: 5, y: 1 },
+                Position { x: 5, y: 2 },
+                Position { x: 5, y: 3 }
+            ]
+        );
+        assert_eq!(chambers.chamber_of(&Position { x: 5, y: 2 }), None);
+
+        let below = chambers.chamber_of(&Position { x: 1, y: 0 }).unwrap();
+        let above = chambers.chamber_of(&Position { x: 0, y: 10 }).unwrap();
+        assert_ne!(below, above);
+        assert_eq!(chambers.chamber_sizes()[below], 21);
+        // Everything above the wall except our head and the cell above the gap
+        assert_eq!(chambers.chamber_sizes()[above], 86);
+
+        assert_eq!(chambers.space_after_entering(&Position { x: 5, y: 2 }), 88);
+        assert_eq!(chambers.space_after_entering(&Position { x: 1, y: 0 }), 110);
+    }
+
+    #[test]
+    fn test_open_board_has_no_articulation_points() {
+        let json = r#"{"game":{"id":"","ruleset":{"name":"standard","version":"v1.0.17"},"timeout":500},"turn":10,"board":{"height":11,"width":11,"food":[],"hazards":[],"snakes":[{"id":"you","name":"","health":90,"body":[{"x":5,"y":5}],"latency":0,"head":{"x":5,"y":5},"length":1,"shout":""}]},"you":{"id":"you","name":"","health":90,"body":[{"x":5,"y":5}],"latency":0,"head":{"x":5,"y":5},"length":1,"shout":""}}"#;
+        let game: Game = serde_json::from_str(json).unwrap();
+
+        let space = game.space_by_move(&Position { x: 5, y: 5 });
+        assert_eq!(space.len(), 4);
+        assert!(space.iter().all(|(_, s)| *s == 120));
+        assert_eq!(
+            game.chambers(&[Position { x: 5, y: 6 }])
+                .articulation_points()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_avoid_traps_stays_out_of_small_pockets() {
+        // Our head is at the mouth of a two cell pocket on the left edge
+        let you = r#"{"id":"you","name":"","health":90,"body":[{"x":1,"y":5},{"x":2,"y":5},{"x":3,"y":5},{"x":4,"y":5},{"x":5,"y":5},{"x":6,"y":5},{"x":7,"y":5}],"latency":0,"head":{"x":1,"y":5},"length":7,"shout":""}"#;
+        let json = format!(
+            r#"{{"game":{{"id":"","ruleset":{{"name":"standard","version":"v1.0.17"}},"timeout":500}},"turn":10,"board":{{"height":11,"width":11,"food":[],"hazards":[],"snakes":[{},{{"id":"above","name":"","health":90,"body":[{{"x":1,"y":6}},{{"x":1,"y":7}},{{"x":0,"y":7}}],"latency":0,"head":{{"x":1,"y":6}},"length":3,"shout":""}},{{"id":"below","name":"","health":90,"body":[{{"x":0,"y":4}},{{"x":0,"y":3}},{{"x":0,"y":2}}],"latency":0,"head":{{"x":0,"y":4}},"length":3,"shout":""}}]}},"you":{}}}"#,
+            you, you
+        );
+        let game: Game = serde_json::from_str(&json).unwrap();
+        let head = Position { x: 1, y: 5 };
+
+        let space: FxHashMap<_, _> = game.space_by_move(&head).into_iter().collect();
+        assert_eq!(space.get(&Move::Left), Some(&2));
+        assert_eq!(space.get(&Move::Right), None);
+
+        assert_eq!(avoid_traps(&game, &head, Move::Left, 7), Move::Down);
+        assert_eq!(avoid_traps(&game, &head, Move::Left, 2), Move::Left);
+    }
+}
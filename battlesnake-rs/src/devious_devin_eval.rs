@@ -1,8 +1,17 @@
 use crate::a_prime::APrimeCalculable;
-use crate::devious_devin_mutable::{
-    score, Instruments, MinMaxReturn, ScoreEndState, BEST_POSSIBLE_SCORE_STATE,
-    WORT_POSSIBLE_SCORE_STATE,
+use crate::devious_devin_mutable::{Instruments, MinMaxReturn, ScoreEndState};
+use crate::evaluator::{AreaControlEvaluator, Evaluator, StandardEvaluator};
+use crate::fingerprint::Fingerprints;
+use crate::game_state::GameState;
+use crate::matrix_game::{solve_with_regret_matching, REGRET_MATCHING_ITERATIONS};
+use crate::opening_book::OpeningBook;
+use crate::opponent_model::{OpponentModel, Tendencies};
+use crate::search_deadline::SearchDeadline;
+use crate::tablebase::{
+    free_cells, trusted_distance, Outcome, Tablebase, TablebaseEntry, SOLVE_NODE_BUDGET,
+    TABLEBASE_FREE_CELLS,
 };
+use crate::transposition_table::{update_zobrist_hash, zobrist_hash, Bound, TranspositionTable};
 use crate::*;
 
 use battlesnake_game_types::compact_representation::{
@@ -11,18 +20,32 @@ use battlesnake_game_types::compact_representation::{
 use battlesnake_game_types::types::*;
 use battlesnake_game_types::wire_representation::NestedGame;
 
-use std::sync::mpsc;
+use itertools::Itertools;
+use rand::prelude::*;
+use std::sync::{mpsc, Arc};
 use std::thread;
 use std::time::{Duration, Instant};
 use tracing::{info, info_span};
 
-pub struct DeviousDevin<T> {
+pub struct DeviousDevin<T: SnakeIDGettableGame, E = StandardEvaluator> {
     game: T,
     game_info: NestedGame,
     turn: i32,
+    evaluator: E,
+    algorithm: SearchAlgorithm,
+    state: GameState,
+    /// Which of the tablebase's outcomes we can trust depends on how food spawns
+    rules: Rules,
+    tablebase: Option<Arc<Tablebase>>,
+    /// What we have seen each opponent do so far this game
+    opponent_tendencies: Vec<(T::SnakeIDType, Tendencies)>,
+    /// The moves we are confident some opponents are about to make this turn
+    root_predictions: Vec<(T::SnakeIDType, Move)>,
+    /// When the request came in, so the time spent setting up counts against our time limit
+    started: Instant,
 }
 
-impl<T> BattlesnakeAI for DeviousDevin<T>
+impl<T, E> BattlesnakeAI for DeviousDevin<T, E>
 where
     T: SnakeIDGettableGame
         + YouDeterminableGame
@@ -38,31 +61,63 @@ where
         + Copy
         + APrimeCalculable
         + FoodGettableGame
+        + SnakeBodyGettableGame
         + MoveEvaluatableGame
+        + SizeDeterminableGame
         + Send
         + 'static,
+    E: Evaluator<T> + Clone + Send + 'static,
 {
     fn make_move(&self) -> Result<MoveOutput, Box<dyn std::error::Error + Send + Sync>> {
         let my_id = self.game.you_id();
         let mut sorted_ids = self.game.get_snake_ids();
         sorted_ids.sort_by_key(|snake_id| if snake_id == my_id { -1 } else { 1 });
 
-        let best_option =
-            info_span!("deepened_minmax", game_id = %&self.game_info.id, turn = self.turn, ruleset_name = %self.game_info.ruleset.name, ruleset_version = %self.game_info.ruleset.version).in_scope(|| self.deepened_minimax(sorted_ids));
+        if let Some(m) = self.tablebase_move(&sorted_ids, self.max_duration() / 4) {
+            info!(game_id = %&self.game_info.id, turn = self.turn, direction = ?m, "Playing from the tablebase");
+            return Ok(MoveOutput {
+                r#move: format!("{}", m),
+                shout: None,
+            });
+        }
+        let time_left = self.max_duration().saturating_sub(self.started.elapsed());
+
+        let previous = self
+            .state
+            .take::<PreviousSearch<T, E::Score>>()
+            .filter(|previous| previous.turn + 1 == self.turn);
+
+        let (depth, best_option) =
+            info_span!("deepened_minmax", game_id = %&self.game_info.id, turn = self.turn, ruleset_name = %self.game_info.ruleset.name, ruleset_version = %self.game_info.ruleset.version).in_scope(|| self.deepened_minimax(sorted_ids.clone(), previous, time_left));
+
+        let direction = best_option
+            .strategy()
+            .and_then(sample_strategy)
+            .or_else(|| best_option.direction_for(&my_id))
+            .expect("TODO: this needs to be handled");
+
+        self.state.set(PreviousSearch {
+            board: self.game,
+            turn: self.turn,
+            players: sorted_ids,
+            depth,
+            result: best_option,
+        });
 
         Ok(MoveOutput {
-            r#move: format!(
-                "{}",
-                best_option
-                    .direction_for(&my_id)
-                    .expect("TODO: this needs to be handled")
-            ),
+            r#move: format!("{}", direction),
             shout: None,
         })
     }
 }
 
-fn wrapped_score<T>(node: &T, depth: i64, max_depth: i64, num_players: i64) -> Option<ScoreEndState>
+fn wrapped_score<T, E>(
+    node: &T,
+    depth: i64,
+    max_depth: i64,
+    num_players: i64,
+    evaluator: &E,
+) -> Option<ScoreEndState<E::Score>>
 where
     T: SnakeIDGettableGame
         + YouDeterminableGame
@@ -76,6 +131,7 @@ where
         + Clone
         + APrimeCalculable
         + FoodGettableGame,
+    E: Evaluator<T>,
 {
     if depth % num_players != 0 {
         return None;
@@ -98,24 +154,148 @@ where
         return Some(score);
     }
 
+    // With more than two snakes the game goes on without us, but it's over as far as we care
+    if !node.is_alive(you_id) {
+        return Some(ScoreEndState::Lose(depth as i64));
+    }
+
     if depth >= max_depth {
-        let score = score(node);
+        let score = ScoreEndState::Evaluated(evaluator.score(node));
         return Some(score);
     }
 
     None
 }
 
-fn minimax<T>(
+/// Which flavour of minimax Devin uses to handle the opponents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchAlgorithm {
+    /// Every opponent works together to minimize our score. Exact in a duel, but far too
+    /// pessimistic once there are a few snakes on the board
+    Paranoid,
+    /// Each snake maximizes its own score from a vector of scores, one per snake. There is no
+    /// alpha-beta pruning, so this doesn't get very deep
+    MaxN,
+    /// Only the opponent with the most damaging reply gets to pick its move each turn, the rest
+    /// play a default move. This keeps the search as deep as a duel even with lots of snakes
+    BestReplySearch,
+}
+
+impl SearchAlgorithm {
+    pub const ENV_VAR: &'static str = "DEVIN_SEARCH_ALGORITHM";
+
+    pub fn for_snake_count(snake_count: usize) -> Self {
+        if snake_count <= 2 {
+            SearchAlgorithm::Paranoid
+        } else {
+            SearchAlgorithm::BestReplySearch
+        }
+    }
+
+    /// Uses the algorithm from the `DEVIN_SEARCH_ALGORITHM` env var if it is set to one we know
+    /// about, otherwise picks one based on the number of snakes
+    pub fn from_env_or_snake_count(snake_count: usize) -> Self {
+        std::env::var(Self::ENV_VAR)
+            .ok()
+            .and_then(|name| Self::from_name(&name))
+            .unwrap_or_else(|| Self::for_snake_count(snake_count))
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "paranoid" => Some(SearchAlgorithm::Paranoid),
+            "max-n" => Some(SearchAlgorithm::MaxN),
+            "best-reply" => Some(SearchAlgorithm::BestReplySearch),
+            _ => None,
+        }
+    }
+}
+
+/// Which evaluator Devin scores the leaves of its search with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvaluatorChoice {
+    /// `StandardEvaluator`, what Devin has always played with
+    Standard,
+    /// `AreaControlEvaluator`, which runs a couple of flood fills on every leaf. That makes
+    /// each leaf a lot slower, so it has to be asked for
+    AreaControl,
+}
+
+impl EvaluatorChoice {
+    pub const ENV_VAR: &'static str = "DEVIN_EVALUATOR";
+
+    /// Uses the evaluator from the `DEVIN_EVALUATOR` env var if it is set to one we know
+    /// about, otherwise the standard one
+    pub fn from_env() -> Self {
+        std::env::var(Self::ENV_VAR)
+            .ok()
+            .and_then(|name| Self::from_name(&name))
+            .unwrap_or(EvaluatorChoice::Standard)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "standard" => Some(EvaluatorChoice::Standard),
+            "area-control" => Some(EvaluatorChoice::AreaControl),
+            _ => None,
+        }
+    }
+}
+
+/// The parts of a search that stay the same as we go down the tree
+struct SearchSettings<'a, E> {
+    algorithm: SearchAlgorithm,
+    deadline: &'a SearchDeadline,
+    evaluator: &'a E,
+    /// Lines up with `players`, `None` for us and for opponents we don't know anything about
+    opponent_tendencies: &'a [Option<Tendencies>],
+    /// Also lines up with `players`. Opponents with a prediction only get to make that move on
+    /// the first turn of the search
+    root_predictions: &'a [Option<Move>],
+}
+
+fn order_by_previous_return<T, S>(
+    possible_moves: Vec<(Move, T::NativePositionType)>,
+    previous_return: Option<MinMaxReturn<T, S>>,
+) -> Vec<((Move, T::NativePositionType), Option<MinMaxReturn<T, S>>)>
+where
+    T: SnakeIDGettableGame + PositionGettableGame + Clone + Debug,
+    S: Ord + Copy,
+{
+    if let Some(MinMaxReturn::Node { mut options, .. }) = previous_return {
+        let mut v: Vec<_> = possible_moves
+            .into_iter()
+            .map(|m| {
+                (
+                    m.clone(),
+                    options
+                        .iter()
+                        .position(|x| x.0 == m.0)
+                        .map(|x| options.remove(x).1),
+                )
+            })
+            .collect();
+        v.sort_by_cached_key(|(_, r)| r.as_ref().map(|x| *x.score()));
+        v.reverse();
+        v
+    } else {
+        possible_moves.into_iter().map(|m| (m, None)).collect()
+    }
+}
+
+fn minimax<T, E>(
     mut node: T,
     players: &[T::SnakeIDType],
     depth: usize,
-    alpha: ScoreEndState,
-    beta: ScoreEndState,
+    alpha: ScoreEndState<E::Score>,
+    beta: ScoreEndState<E::Score>,
     max_depth: usize,
-    previous_return: Option<MinMaxReturn<T>>,
+    previous_return: Option<MinMaxReturn<T, E::Score>>,
     mut pending_moves: Vec<(T::SnakeIDType, Move)>,
-) -> MinMaxReturn<T>
+    transposition_table: &mut TranspositionTable<E::Score>,
+    mut board_hash: u64,
+    settings: &SearchSettings<E>,
+) -> MinMaxReturn<T, E::Score>
 where
     T: SnakeIDGettableGame
         + YouDeterminableGame
@@ -130,23 +310,59 @@ where
         + Copy
         + MoveEvaluatableGame
         + APrimeCalculable
-        + FoodGettableGame,
+        + FoodGettableGame
+        + SnakeBodyGettableGame,
+    E: Evaluator<T>,
 {
     let mut alpha = alpha;
     let mut beta = beta;
 
+    // The caller throws away any depth that didn't finish before the deadline, so it doesn't
+    // matter what we return here
+    if settings.deadline.is_expired() {
+        return MinMaxReturn::Leaf {
+            score: ScoreEndState::WORST_POSSIBLE,
+        };
+    }
+
     if pending_moves.len() == node.get_snake_ids().len() {
-        node = node.evaluate_moves(&pending_moves);
+        let next = node.evaluate_moves(&pending_moves);
+        board_hash = update_zobrist_hash(board_hash, &node, &next, players);
+        node = next;
         pending_moves = vec![];
     };
 
     let new_depth = depth.try_into().unwrap();
-    if let Some(s) = wrapped_score(&node, new_depth, max_depth as i64, players.len() as i64) {
+    if let Some(s) = wrapped_score(
+        &node,
+        new_depth,
+        max_depth as i64,
+        players.len() as i64,
+        settings.evaluator,
+    ) {
         return MinMaxReturn::Leaf { score: s };
     }
 
+    // The first opponent layer of each turn is where Best-Reply Search picks its replier, and
+    // it covers every other opponent layer of the turn
+    if settings.algorithm == SearchAlgorithm::BestReplySearch && depth % players.len() == 1 {
+        return best_reply(
+            node,
+            players,
+            depth,
+            alpha,
+            beta,
+            max_depth,
+            previous_return,
+            pending_moves,
+            transposition_table,
+            board_hash,
+            settings,
+        );
+    }
+
     let snake_id = &players[depth % players.len()];
-    let mut options: Vec<(Move, MinMaxReturn<T>)> = vec![];
+    let mut options: Vec<(Move, MinMaxReturn<T, E::Score>)> = vec![];
 
     if !node.is_alive(snake_id) {
         return minimax(
@@ -158,33 +374,48 @@ where
             max_depth,
             previous_return,
             pending_moves,
+            transposition_table,
+            board_hash,
+            settings,
         );
     }
 
     let is_maximizing = snake_id == node.you_id();
 
-    let possible_moves = node.possible_moves(&node.get_head_as_native_position(&snake_id));
+    // Only positions at the start of a turn are complete boards, the other layers
+    // depend on the pending moves so we don't store them
+    let hash = if is_maximizing && pending_moves.is_empty() {
+        Some(board_hash)
+    } else {
+        None
+    };
+    let original_alpha = alpha;
+    let remaining_depth = max_depth.saturating_sub(depth);
+    let mut table_move = None;
 
-    let possible_zipped: Vec<((Move, T::NativePositionType), Option<MinMaxReturn<T>>)> =
-        if let Some(MinMaxReturn::Node { mut options, .. }) = previous_return {
-            let mut v: Vec<_> = possible_moves
-                .into_iter()
-                .map(|m| {
-                    (
-                        m.clone(),
-                        options
-                            .iter()
-                            .position(|x| x.0 == m.0)
-                            .map(|x| options.remove(x).1),
-                    )
-                })
-                .collect();
-            v.sort_by_cached_key(|(_, r)| r.as_ref().map(|x| *x.score()));
-            v.reverse();
-            v
-        } else {
-            possible_moves.into_iter().map(|m| (m, None)).collect()
-        };
+    if let Some(entry) = hash.and_then(|h| transposition_table.get(h)) {
+        // We can't cutoff at the root, since we still need options to pick a direction from
+        if depth > 0 {
+            if let Some(score) = entry.cutoff(remaining_depth, alpha, beta) {
+                return MinMaxReturn::Leaf { score };
+            }
+        }
+
+        table_move = entry.best_move;
+    }
+
+    let mut possible_moves = modeled_moves(&node, players, snake_id, settings.opponent_tendencies);
+    // Only reached at the root when nobody is contested, otherwise the matrix already used them
+    if depth < players.len() {
+        if let Some(Some(predicted)) = settings.root_predictions.get(depth) {
+            possible_moves.retain(|(dir, _)| dir == predicted);
+        }
+    }
+    if let Some(table_move) = table_move {
+        possible_moves.sort_by_key(|(dir, _)| *dir != table_move);
+    }
+
+    let possible_zipped = order_by_previous_return(possible_moves, previous_return);
 
     for ((dir, _coor), previous_return) in possible_zipped.into_iter() {
         // let last_move = node.move_to(&coor, &snake_id);
@@ -199,6 +430,9 @@ where
             max_depth,
             previous_return,
             new_pending_moves,
+            transposition_table,
+            board_hash,
+            settings,
         );
         let value = *next_move_return.score();
         // node.reverse_move(last_move);
@@ -221,6 +455,25 @@ where
     }
     let chosen_score = *options[0].1.score();
 
+    // A partial result from an expired search would poison the table for the next depth
+    if let (Some(hash), false) = (hash, settings.deadline.is_expired()) {
+        let bound = if chosen_score <= original_alpha {
+            Bound::Upper
+        } else if chosen_score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+
+        transposition_table.insert(
+            hash,
+            remaining_depth,
+            bound,
+            chosen_score,
+            Some(options[0].0),
+        );
+    }
+
     MinMaxReturn::Node {
         options,
         is_maximizing,
@@ -229,103 +482,148 @@ where
     }
 }
 
-impl<T> DeviousDevin<T>
+/// The moves worth searching for `snake_id`. Opponents we have a model of get theirs ordered
+/// most likely first, with the ones the model doesn't expect left out
+fn modeled_moves<T>(
+    node: &T,
+    players: &[T::SnakeIDType],
+    snake_id: &T::SnakeIDType,
+    opponent_tendencies: &[Option<Tendencies>],
+) -> Vec<(Move, T::NativePositionType)>
 where
     T: SnakeIDGettableGame
         + YouDeterminableGame
-        + VictorDeterminableGame
-        + Send
-        + 'static
         + PositionGettableGame
         + HeadGettableGame
-        + LengthGettableGame
         + HealthGettableGame
-        + Clone
-        + Copy
-        + APrimeCalculable
-        + MoveEvaluatableGame
-        + SimulableGame<Instruments>
-        + FoodGettableGame,
+        + FoodGettableGame
+        + SnakeBodyGettableGame
+        + APrimeCalculable,
 {
-    fn time_limit_ms(&self) -> i64 {
-        const NETWORK_LATENCY_PADDING: i64 = 100;
-        self.game_info.timeout - NETWORK_LATENCY_PADDING
+    let possible_moves = node.possible_moves(&node.get_head_as_native_position(snake_id));
+    if snake_id == node.you_id() {
+        return possible_moves;
     }
 
-    fn max_duration(&self) -> Duration {
-        Duration::new(0, (self.time_limit_ms() * 1_000_000).try_into().unwrap())
+    match players
+        .iter()
+        .position(|s| s == snake_id)
+        .and_then(|i| opponent_tendencies.get(i))
+    {
+        Some(Some(tendencies)) => tendencies.order_moves(node, snake_id, possible_moves),
+        _ => possible_moves,
     }
+}
 
-    fn deepened_minimax(&self, players: Vec<T::SnakeIDType>) -> MinMaxReturn<T> {
-        let node = self.game.clone();
-        let you_id = node.you_id();
-
-        const RUNAWAY_DEPTH_LIMIT: usize = 100;
-
-        let started_at = Instant::now();
-
-        let (tx, rx) = mpsc::channel();
-        thread::spawn(move || {
-            let mut current_depth = 2;
-            let mut current_return = None;
-            loop {
-                let next = minimax(
-                    node,
-                    &players,
-                    0,
-                    WORT_POSSIBLE_SCORE_STATE,
-                    BEST_POSSIBLE_SCORE_STATE,
-                    current_depth,
-                    current_return,
-                    vec![],
-                );
-
-                if tx.send((current_depth, next.clone())).is_err() {
-                    return;
-                }
-
-                current_return = Some(next);
-
-                current_depth += players.len();
-            }
-        });
+/// The move an opponent makes in Best-Reply Search when it isn't the one replying
+///
+/// We don't spend any search on these, so we only check that it isn't running straight into a
+/// body. For modeled opponents that is the most likely move that doesn't
+fn default_move<T>(
+    node: &T,
+    players: &[T::SnakeIDType],
+    snake_id: &T::SnakeIDType,
+    opponent_tendencies: &[Option<Tendencies>],
+) -> Move
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + FoodGettableGame
+        + SnakeBodyGettableGame
+        + APrimeCalculable,
+{
+    let possible_moves = modeled_moves(node, players, snake_id, opponent_tendencies);
 
-        let mut current = None;
+    possible_moves
+        .iter()
+        .find(|(_, coor)| !node.position_is_snake_body(coor.clone()))
+        .or_else(|| possible_moves.first())
+        .map(|(dir, _)| *dir)
+        .unwrap_or(Move::Up)
+}
 
-        let max_duration = self.max_duration();
+type JointMove<T> = Vec<(<T as SnakeIDGettableGame>::SnakeIDType, Move)>;
 
-        while started_at.elapsed() < max_duration {
-            if let Ok((depth, result)) = rx.try_recv() {
-                let current_score = result.score();
-                let terminal_depth = current_score.terminal_depth();
-                info!(depth, current_score = ?&current_score, current_direction = ?result.direction_for(you_id), "Just finished depth");
+/// Every joint move of the opponents where at most one of them deviates from its default
+/// move, along with the opponent that deviated and the move it made. The replies of modeled
+/// opponents come most likely first, without the ones we don't expect
+fn best_replies<T>(
+    node: &T,
+    players: &[T::SnakeIDType],
+    opponent_tendencies: &[Option<Tendencies>],
+) -> Vec<(T::SnakeIDType, Move, JointMove<T>)>
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + FoodGettableGame
+        + SnakeBodyGettableGame
+        + APrimeCalculable,
+{
+    let opponents: Vec<_> = players
+        .iter()
+        .filter(|s| *s != node.you_id() && node.is_alive(s))
+        .cloned()
+        .collect();
+    let default_moves: Vec<_> = opponents
+        .iter()
+        .map(|s| {
+            (
+                s.clone(),
+                default_move(node, players, s, opponent_tendencies),
+            )
+        })
+        .collect();
 
-                current = Some((depth, result));
+    let mut replies: Vec<(T::SnakeIDType, Move, JointMove<T>)> = vec![];
 
-                if let Some(terminal_depth) = terminal_depth {
-                    if depth > (terminal_depth as usize) {
-                        info!(depth, "This game is over, no need to keep going");
-                        break;
+    for replier in opponents.iter() {
+        for (dir, _) in modeled_moves(node, players, replier, opponent_tendencies) {
+            let joint_move: JointMove<T> = default_moves
+                .iter()
+                .map(|(s, default)| {
+                    if s == replier {
+                        (s.clone(), dir)
+                    } else {
+                        (s.clone(), *default)
                     }
-                }
+                })
+                .collect();
 
-                if depth > RUNAWAY_DEPTH_LIMIT {
-                    break;
-                };
+            // Every opponent 'replying' with its default move is the same board
+            if replies.iter().any(|(_, _, seen)| *seen == joint_move) {
+                continue;
             }
-        }
 
-        if let Some((depth, result)) = &current {
-            info!(depth, score = ?result.score(), direction = ?result.direction_for(you_id), "Finished deepened_minimax");
+            replies.push((replier.clone(), dir, joint_move));
         }
-
-        current
-            .map(|(_depth, result)| result)
-            .expect("We weren't able to do even a single layer of minmax")
     }
+
+    replies
 }
 
-pub fn minmax_bench_entry<T>(game_state: T, max_turns: usize) -> MinMaxReturn<T>
+/// A single minimizing layer that stands in for every opponent layer of a turn
+///
+/// Each option is one opponent deviating from its default move, so the branching factor grows
+/// with the total number of opponent moves instead of their product
+fn best_reply<T, E>(
+    node: T,
+    players: &[T::SnakeIDType],
+    depth: usize,
+    alpha: ScoreEndState<E::Score>,
+    beta: ScoreEndState<E::Score>,
+    max_depth: usize,
+    previous_return: Option<MinMaxReturn<T, E::Score>>,
+    pending_moves: Vec<(T::SnakeIDType, Move)>,
+    transposition_table: &mut TranspositionTable<E::Score>,
+    board_hash: u64,
+    settings: &SearchSettings<E>,
+) -> MinMaxReturn<T, E::Score>
 where
     T: SnakeIDGettableGame
         + YouDeterminableGame
@@ -338,71 +636,1202 @@ where
         + SimulableGame<Instruments>
         + Clone
         + Copy
-        + APrimeCalculable
         + MoveEvaluatableGame
-        + FoodGettableGame,
+        + APrimeCalculable
+        + FoodGettableGame
+        + SnakeBodyGettableGame,
+    E: Evaluator<T>,
 {
-    let my_id = game_state.you_id();
-    let mut sorted_ids = game_state.get_snake_ids();
-    sorted_ids.sort_by_key(|snake_id| if snake_id == my_id { -1 } else { 1 });
+    let mut beta = beta;
+    let next_turn_depth = depth - 1 + players.len();
 
-    minimax(
-        game_state,
-        &sorted_ids,
-        0,
-        WORT_POSSIBLE_SCORE_STATE,
-        BEST_POSSIBLE_SCORE_STATE,
-        max_turns * sorted_ids.len(),
-        None,
-        vec![],
-    )
+    let replies = best_replies(&node, players, settings.opponent_tendencies);
+
+    if replies.is_empty() {
+        return minimax(
+            node,
+            players,
+            next_turn_depth,
+            alpha,
+            beta,
+            max_depth,
+            None,
+            pending_moves,
+            transposition_table,
+            board_hash,
+            settings,
+        );
+    }
+
+    let mut previous_options = match previous_return {
+        Some(MinMaxReturn::BestReply { options, .. }) => options,
+        _ => vec![],
+    };
+    let mut replies: Vec<_> = replies
+        .into_iter()
+        .map(|(replier, dir, joint_move)| {
+            let previous = previous_options
+                .iter()
+                .position(|(s, d, _)| *s == replier && *d == dir)
+                .map(|i| previous_options.remove(i).2);
+            (replier, dir, joint_move, previous)
+        })
+        .collect();
+    replies.sort_by_cached_key(|(_, _, _, r)| r.as_ref().map(|x| *x.score()));
+
+    let mut options: Vec<(T::SnakeIDType, Move, MinMaxReturn<T, E::Score>)> = vec![];
+
+    for (replier, dir, joint_move, previous_return) in replies.into_iter() {
+        let mut new_pending_moves = pending_moves.clone();
+        new_pending_moves.extend(joint_move);
+
+        let next_move_return = minimax(
+            node,
+            players,
+            next_turn_depth,
+            alpha,
+            beta,
+            max_depth,
+            previous_return,
+            new_pending_moves,
+            transposition_table,
+            board_hash,
+            settings,
+        );
+        let value = *next_move_return.score();
+        options.push((replier, dir, next_move_return));
+
+        beta = std::cmp::min(beta, value);
+        if beta <= alpha {
+            break;
+        }
+    }
+
+    options.sort_by_cached_key(|(_, _, value)| *value.score());
+    let chosen_score = *options[0].2.score();
+
+    MinMaxReturn::BestReply {
+        options,
+        score: chosen_score,
+    }
+}
+
+/// Scores the board from every player's point of view, in the same order as `players`
+fn max_n_scores<T, E>(
+    node: &T,
+    players: &[T::SnakeIDType],
+    depth: usize,
+    max_depth: usize,
+    evaluator: &E,
+) -> Option<Vec<ScoreEndState<E::Score>>>
+where
+    T: SnakeIDGettableGame + HealthGettableGame + VictorDeterminableGame,
+    E: Evaluator<T>,
+{
+    if depth % players.len() != 0 {
+        return None;
+    }
+
+    let depth = depth as i64;
+
+    if node.is_over() {
+        let winner = node.get_winner();
+        let scores = players
+            .iter()
+            .map(|s| match &winner {
+                Some(w) if w == s => ScoreEndState::Win(-depth),
+                Some(_) => ScoreEndState::Lose(depth),
+                None => ScoreEndState::Tie(depth),
+            })
+            .collect();
+
+        return Some(scores);
+    }
+
+    if depth >= max_depth as i64 {
+        let scores = players
+            .iter()
+            .map(|s| {
+                if node.is_alive(s) {
+                    ScoreEndState::Evaluated(evaluator.score_for(node, s))
+                } else {
+                    ScoreEndState::Lose(depth)
+                }
+            })
+            .collect();
+
+        return Some(scores);
+    }
+
+    None
 }
 
-pub fn minmax_deepened_bench_entry<T>(game_state: T, max_turns: usize) -> MinMaxReturn<T>
+/// Returns the search tree along with the score vector of the line it picked
+///
+/// The score in the returned tree is always ours, so the iterative deepening and move picking
+/// don't need to know which algorithm produced it
+fn max_n<T, E>(
+    mut node: T,
+    players: &[T::SnakeIDType],
+    depth: usize,
+    max_depth: usize,
+    previous_return: Option<MinMaxReturn<T, E::Score>>,
+    mut pending_moves: Vec<(T::SnakeIDType, Move)>,
+    settings: &SearchSettings<E>,
+) -> (MinMaxReturn<T, E::Score>, Vec<ScoreEndState<E::Score>>)
 where
     T: SnakeIDGettableGame
         + YouDeterminableGame
         + PositionGettableGame
         + HeadGettableGame
-        + LengthGettableGame
         + HealthGettableGame
         + VictorDeterminableGame
-        + HeadGettableGame
         + SimulableGame<Instruments>
         + Clone
-        + APrimeCalculable
-        + MoveEvaluatableGame
         + Copy
-        + FoodGettableGame,
+        + MoveEvaluatableGame
+        + APrimeCalculable,
+    E: Evaluator<T>,
 {
-    let my_id = game_state.you_id();
-    let mut sorted_ids = game_state.get_snake_ids();
-    sorted_ids.sort_by_key(|snake_id| if snake_id == my_id { -1 } else { 1 });
-
-    let players = sorted_ids;
+    if settings.deadline.is_expired() {
+        return (
+            MinMaxReturn::Leaf {
+                score: ScoreEndState::WORST_POSSIBLE,
+            },
+            vec![ScoreEndState::WORST_POSSIBLE; players.len()],
+        );
+    }
 
-    let max_depth = max_turns * players.len();
-    let mut current_depth = players.len();
-    let mut current_return = None;
-    while current_depth <= max_depth {
-        current_return = Some(minimax(
-            game_state,
-            &players,
-            0,
-            WORT_POSSIBLE_SCORE_STATE,
-            BEST_POSSIBLE_SCORE_STATE,
-            current_depth,
-            current_return,
-            vec![],
-        ));
+    if pending_moves.len() == node.get_snake_ids().len() {
+        node = node.evaluate_moves(&pending_moves);
+        pending_moves = vec![];
+    };
 
-        current_depth += players.len();
+    if let Some(scores) = max_n_scores(&node, players, depth, max_depth, settings.evaluator) {
+        return (MinMaxReturn::Leaf { score: scores[0] }, scores);
+    }
+
+    let player_index = depth % players.len();
+    let snake_id = &players[player_index];
+
+    if !node.is_alive(snake_id) {
+        return max_n(
+            node,
+            players,
+            depth + 1,
+            max_depth,
+            previous_return,
+            pending_moves,
+            settings,
+        );
+    }
+
+    let mut possible_moves = node.possible_moves(&node.get_head_as_native_position(snake_id));
+    if depth < players.len() {
+        if let Some(Some(predicted)) = settings.root_predictions.get(player_index) {
+            possible_moves.retain(|(dir, _)| dir == predicted);
+        }
+    }
+    let mut options: Vec<_> = order_by_previous_return(possible_moves, previous_return)
+        .into_iter()
+        .map(|((dir, _coor), previous_return)| {
+            let mut new_pending_moves = pending_moves.clone();
+            new_pending_moves.push((snake_id.clone(), dir));
+
+            let (next_move_return, scores) = max_n(
+                node,
+                players,
+                depth + 1,
+                max_depth,
+                previous_return,
+                new_pending_moves,
+                settings,
+            );
+
+            (dir, next_move_return, scores)
+        })
+        .collect();
+
+    options.sort_by(|(_, _, a), (_, _, b)| b[player_index].cmp(&a[player_index]));
+    let chosen_scores = options[0].2.clone();
+
+    (
+        MinMaxReturn::Node {
+            options: options
+                .into_iter()
+                .map(|(dir, next_move_return, _)| (dir, next_move_return))
+                .collect(),
+            is_maximizing: player_index == 0,
+            moving_snake_id: snake_id.clone(),
+            score: chosen_scores[0],
+        },
+        chosen_scores,
+    )
+}
+
+/// Moves we would pick less often than this in the mixed strategy are dropped before picking,
+/// they are almost always leftovers from the early rounds of regret matching
+const MIN_STRATEGY_PROBABILITY: f64 = 0.05;
+
+/// The opponents that could end up on the same cell as us this turn. These are the only ones
+/// for which it matters that we all move at the same time
+fn contested_opponents<T>(node: &T, players: &[T::SnakeIDType]) -> Vec<T::SnakeIDType>
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + APrimeCalculable,
+{
+    let ours: Vec<_> = node
+        .possible_moves(&node.get_head_as_native_position(node.you_id()))
+        .into_iter()
+        .map(|(_, c)| c)
+        .collect();
+
+    players
+        .iter()
+        .filter(|s| *s != node.you_id() && node.is_alive(s))
+        .filter(|s| {
+            node.possible_moves(&node.get_head_as_native_position(s))
+                .iter()
+                .any(|(_, c)| ours.contains(c))
+        })
+        .cloned()
+        .collect()
+}
+
+/// The opponents' joint moves that make up the columns of the root matrix
+///
+/// Only the contested opponents branch here, the rest play their default move for this one
+/// turn and get searched properly from the next turn on. That keeps a crowded board from
+/// turning into a matrix with a column for every joint move of every opponent. Modeled
+/// opponents only get columns for the moves we expect from them. Empty when no opponent is
+/// contested, since then moving at the same time doesn't change anything
+fn root_columns<T>(
+    node: &T,
+    players: &[T::SnakeIDType],
+    algorithm: SearchAlgorithm,
+    opponent_tendencies: &[Option<Tendencies>],
+) -> Vec<JointMove<T>>
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + FoodGettableGame
+        + SnakeBodyGettableGame
+        + APrimeCalculable,
+{
+    let contested = contested_opponents(node, players);
+    if contested.is_empty() {
+        return vec![];
+    }
+
+    let default_moves: JointMove<T> = players
+        .iter()
+        .filter(|s| *s != node.you_id() && node.is_alive(s))
+        .map(|s| {
+            (
+                s.clone(),
+                default_move(node, players, s, opponent_tendencies),
+            )
+        })
+        .collect();
+
+    match algorithm {
+        SearchAlgorithm::BestReplySearch => best_replies(node, players, opponent_tendencies)
+            .into_iter()
+            .filter(|(replier, _, joint_move)| {
+                contested.contains(replier) || *joint_move == default_moves
+            })
+            .map(|(_, _, joint_move)| joint_move)
+            .collect(),
+        _ => default_moves
+            .iter()
+            .map(|(s, default)| {
+                if contested.contains(s) {
+                    modeled_moves(node, players, s, opponent_tendencies)
+                        .into_iter()
+                        .map(|(dir, _)| (s.clone(), dir))
+                        .collect()
+                } else {
+                    vec![(s.clone(), *default)]
+                }
+            })
+            .multi_cartesian_product()
+            .collect(),
+    }
+}
+
+/// Picks our move from the mixed strategy of a simultaneous root
+///
+/// This happens once, on the deepest search that finished. Sampling at every depth would
+/// mean the move we play doesn't line up with the tree we keep for next turn
+fn sample_strategy(strategy: &[(Move, f64)]) -> Option<Move> {
+    let weighted: Vec<_> = strategy
+        .iter()
+        .filter(|(_, p)| *p >= MIN_STRATEGY_PROBABILITY)
+        .collect();
+
+    weighted
+        .choose_weighted(&mut thread_rng(), |(_, p)| *p)
+        .ok()
+        .map(|(dir, _)| *dir)
+}
+
+/// Swaps in the predicted move for every opponent we have one for. Joint moves that only
+/// differed in those opponents' moves end up the same, so they get merged
+fn with_root_predictions<T>(
+    columns: Vec<JointMove<T>>,
+    players: &[T::SnakeIDType],
+    predictions: &[Option<Move>],
+) -> Vec<JointMove<T>>
+where
+    T: SnakeIDGettableGame,
+{
+    let mut forced: Vec<JointMove<T>> = vec![];
+
+    for joint_move in columns {
+        let joint_move: JointMove<T> = joint_move
+            .into_iter()
+            .map(|(s, dir)| {
+                let predicted = players
+                    .iter()
+                    .position(|p| *p == s)
+                    .and_then(|i| predictions.get(i).copied().flatten());
+
+                (s, predicted.unwrap_or(dir))
+            })
+            .collect();
+
+        if !forced.contains(&joint_move) {
+            forced.push(joint_move);
+        }
+    }
+
+    forced
+}
+
+/// Paranoid and Best-Reply Search both let the opponents see our move before they pick theirs,
+/// but in a real game everyone moves at the same time
+///
+/// At the root we search every pairing of our moves with the opponents' joint moves, and treat
+/// the results as the payoff matrix of a zero sum game. Our move is later picked from the
+/// mixed strategy for that game, so we stop being predictable in head to head coinflips
+fn simultaneous_root<T, E>(
+    node: T,
+    players: &[T::SnakeIDType],
+    max_depth: usize,
+    previous_return: Option<MinMaxReturn<T, E::Score>>,
+    transposition_table: &mut TranspositionTable<E::Score>,
+    settings: &SearchSettings<E>,
+) -> MinMaxReturn<T, E::Score>
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + LengthGettableGame
+        + HealthGettableGame
+        + VictorDeterminableGame
+        + HeadGettableGame
+        + SimulableGame<Instruments>
+        + Clone
+        + Copy
+        + MoveEvaluatableGame
+        + APrimeCalculable
+        + FoodGettableGame
+        + SnakeBodyGettableGame,
+    E: Evaluator<T>,
+{
+    let you_id = node.you_id().clone();
+    let columns = root_columns(
+        &node,
+        players,
+        settings.algorithm,
+        settings.opponent_tendencies,
+    );
+    let columns = with_root_predictions(columns, players, settings.root_predictions);
+    let board_hash = zobrist_hash(&node, players);
+
+    if node.is_over() || columns.is_empty() {
+        return minimax(
+            node,
+            players,
+            0,
+            ScoreEndState::WORST_POSSIBLE,
+            ScoreEndState::BEST_POSSIBLE,
+            max_depth,
+            previous_return,
+            vec![],
+            transposition_table,
+            board_hash,
+            settings,
+        );
+    }
+
+    let mut previous_rows = match previous_return {
+        Some(MinMaxReturn::Simultaneous { options, .. }) => options,
+        _ => vec![],
+    };
+
+    let mut rows = vec![];
+    for (dir, _) in node.possible_moves(&node.get_head_as_native_position(&you_id)) {
+        let mut previous_columns = previous_rows
+            .iter()
+            .position(|(d, _)| *d == dir)
+            .map(|i| previous_rows.remove(i).1)
+            .unwrap_or_default();
+
+        let mut row = vec![];
+        for joint_move in columns.iter() {
+            let previous_return = previous_columns
+                .iter()
+                .position(|(j, _)| j == joint_move)
+                .map(|i| previous_columns.remove(i).1);
+
+            let mut pending_moves = vec![(you_id.clone(), dir)];
+            pending_moves.extend(joint_move.iter().cloned());
+
+            let next_move_return = minimax(
+                node,
+                players,
+                players.len(),
+                ScoreEndState::WORST_POSSIBLE,
+                ScoreEndState::BEST_POSSIBLE,
+                max_depth,
+                previous_return,
+                pending_moves,
+                transposition_table,
+                board_hash,
+                settings,
+            );
+            row.push((joint_move.clone(), next_move_return));
+        }
+
+        row.sort_by_cached_key(|(_, value)| *value.score());
+        rows.push((dir, row));
+    }
+
+    // The scores aren't numbers, so the payoffs are each score's rank among all the scores in
+    // the matrix, scaled to be between 0 and 1
+    let mut ranked: Vec<ScoreEndState<E::Score>> = rows
+        .iter()
+        .flat_map(|(_, row)| row.iter().map(|(_, value)| *value.score()))
+        .collect();
+    ranked.sort();
+    ranked.dedup();
+    let scale = (ranked.len().max(2) - 1) as f64;
+
+    let payoffs: Vec<Vec<f64>> = rows
+        .iter()
+        .map(|(_, row)| {
+            columns
+                .iter()
+                .map(|joint_move| {
+                    let (_, value) = row
+                        .iter()
+                        .find(|(j, _)| j == joint_move)
+                        .expect("Every row has a column for each joint move");
+                    let rank = ranked.binary_search(value.score()).unwrap();
+
+                    rank as f64 / scale
+                })
+                .collect()
+        })
+        .collect();
+
+    let solved = solve_with_regret_matching(&payoffs, REGRET_MATCHING_ITERATIONS);
+    let strategy: Vec<(Move, f64)> = rows
+        .iter()
+        .zip(solved.rows.iter())
+        .map(|((dir, _), p)| (*dir, *p))
+        .collect();
+
+    // Nothing is sampled here, the most likely move goes first so that deepening has a stable
+    // line to order the next depth by
+    let mut rows: Vec<_> = rows.into_iter().zip(solved.rows.iter()).collect();
+    rows.sort_by(|((_, a_row), a), ((_, b_row), b)| {
+        b.partial_cmp(a)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| b_row[0].1.score().cmp(a_row[0].1.score()))
+    });
+    let rows: Vec<_> = rows.into_iter().map(|(row, _)| row).collect();
+
+    let score = *rows[0].1[0].1.score();
+
+    MinMaxReturn::Simultaneous {
+        moving_snake_id: you_id,
+        options: rows,
+        strategy,
+        score,
+    }
+}
+
+/// Runs a search to `max_depth` from the start of our turn, with whichever algorithm the
+/// settings ask for
+fn search<T, E>(
+    node: T,
+    players: &[T::SnakeIDType],
+    max_depth: usize,
+    previous_return: Option<MinMaxReturn<T, E::Score>>,
+    transposition_table: &mut TranspositionTable<E::Score>,
+    settings: &SearchSettings<E>,
+) -> MinMaxReturn<T, E::Score>
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + LengthGettableGame
+        + HealthGettableGame
+        + VictorDeterminableGame
+        + HeadGettableGame
+        + SimulableGame<Instruments>
+        + Clone
+        + Copy
+        + MoveEvaluatableGame
+        + APrimeCalculable
+        + FoodGettableGame
+        + SnakeBodyGettableGame,
+    E: Evaluator<T>,
+{
+    match settings.algorithm {
+        SearchAlgorithm::MaxN => {
+            max_n(
+                node,
+                players,
+                0,
+                max_depth,
+                previous_return,
+                vec![],
+                settings,
+            )
+            .0
+        }
+        SearchAlgorithm::Paranoid | SearchAlgorithm::BestReplySearch => simultaneous_root(
+            node,
+            players,
+            max_depth,
+            previous_return,
+            transposition_table,
+            settings,
+        ),
+    }
+}
+
+/// The free cells in the last duel we ran out of budget or time solving
+#[derive(Debug, Clone, Copy)]
+struct UnsolvedDuel {
+    free_cells: usize,
+}
+
+/// The search we did last turn, kept around to seed this turn's search
+struct PreviousSearch<T: SnakeIDGettableGame + Clone + Debug, S> {
+    board: T,
+    turn: i32,
+    players: Vec<T::SnakeIDType>,
+    /// The deepest search that finished, and the tree it returned
+    depth: usize,
+    result: MinMaxReturn<T, S>,
+}
+
+/// The move every snake that was alive on `before` made to get to `after`
+///
+/// `None` when we can't tell, like when a snake died and the ids were handed out again
+fn played_moves<T>(before: &T, after: &T, players: &[T::SnakeIDType]) -> Option<JointMove<T>>
+where
+    T: SnakeIDGettableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + APrimeCalculable,
+{
+    if before.get_snake_ids() != after.get_snake_ids() {
+        return None;
+    }
+
+    players
+        .iter()
+        .filter(|s| before.is_alive(s))
+        .map(|s| {
+            if !after.is_alive(s) {
+                return None;
+            }
+
+            let new_head = after.get_head_as_native_position(s);
+            before
+                .possible_moves(&before.get_head_as_native_position(s))
+                .into_iter()
+                .find(|(_, c)| *c == new_head)
+                .map(|(dir, _)| (s.clone(), dir))
+        })
+        .collect()
+}
+
+/// Walks down the tree along the moves that were actually played, and returns the subtree
+/// for the start of the next turn
+fn follow_played_moves<T, S>(
+    tree: MinMaxReturn<T, S>,
+    played: &[(T::SnakeIDType, Move)],
+    moved: usize,
+) -> Option<MinMaxReturn<T, S>>
+where
+    T: SnakeIDGettableGame + Clone + Debug,
+{
+    if moved == played.len() {
+        return Some(tree);
+    }
+
+    let move_for = |snake_id: &T::SnakeIDType| {
+        played
+            .iter()
+            .find(|(s, _)| s == snake_id)
+            .map(|(_, dir)| *dir)
+    };
+
+    match tree {
+        MinMaxReturn::Node {
+            options,
+            moving_snake_id,
+            ..
+        } => {
+            let played_dir = move_for(&moving_snake_id)?;
+            let (_, next) = options.into_iter().find(|(dir, _)| *dir == played_dir)?;
+
+            follow_played_moves(next, played, moved + 1)
+        }
+        MinMaxReturn::Simultaneous {
+            options,
+            moving_snake_id,
+            ..
+        } => {
+            let played_dir = move_for(&moving_snake_id)?;
+            let (_, columns) = options.into_iter().find(|(dir, _)| *dir == played_dir)?;
+            let (_, next) = columns.into_iter().find(|(joint_move, _)| {
+                joint_move.len() + 1 == played.len()
+                    && joint_move.iter().all(|m| played.contains(m))
+            })?;
+
+            Some(next)
+        }
+        _ => None,
+    }
+}
+
+/// Splits the opponent layers under one of our moves back into the joint moves that
+/// `simultaneous_root` uses as columns
+fn joint_move_columns<T, S>(
+    tree: MinMaxReturn<T, S>,
+    node: &T,
+    players: &[T::SnakeIDType],
+    opponent_tendencies: &[Option<Tendencies>],
+    prefix: JointMove<T>,
+) -> Vec<(JointMove<T>, MinMaxReturn<T, S>)>
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + FoodGettableGame
+        + SnakeBodyGettableGame
+        + APrimeCalculable
+        + Clone
+        + Debug,
+{
+    match tree {
+        MinMaxReturn::Node {
+            options,
+            moving_snake_id,
+            is_maximizing: false,
+            ..
+        } => options
+            .into_iter()
+            .flat_map(|(dir, next)| {
+                let mut joint_move = prefix.clone();
+                joint_move.push((moving_snake_id.clone(), dir));
+                joint_move_columns(next, node, players, opponent_tendencies, joint_move)
+            })
+            .collect(),
+        MinMaxReturn::BestReply { options, .. } => {
+            let replies = best_replies(node, players, opponent_tendencies);
+
+            options
+                .into_iter()
+                .filter_map(|(replier, dir, next)| {
+                    replies
+                        .iter()
+                        .find(|(r, d, _)| *r == replier && *d == dir)
+                        .map(|(_, _, joint_move)| (joint_move.clone(), next))
+                })
+                .collect()
+        }
+        other => vec![(prefix, other)],
+    }
+}
+
+/// The part of last turn's search that starts from `node`, in the shape this turn's search
+/// wants it, along with how deep it was searched
+fn reuse_previous_search<T, S>(
+    previous: PreviousSearch<T, S>,
+    node: &T,
+    players: &[T::SnakeIDType],
+    algorithm: SearchAlgorithm,
+    opponent_tendencies: &[Option<Tendencies>],
+) -> Option<(usize, MinMaxReturn<T, S>)>
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + FoodGettableGame
+        + SnakeBodyGettableGame
+        + APrimeCalculable
+        + Clone
+        + Debug,
+{
+    if previous.players != players {
+        return None;
+    }
+
+    let depth = previous
+        .depth
+        .checked_sub(players.len())
+        .filter(|d| *d > 0)?;
+    let played = played_moves(&previous.board, node, players)?;
+    let subtree = follow_played_moves(previous.result, &played, 0)?;
+
+    if algorithm == SearchAlgorithm::MaxN {
+        return Some((depth, subtree));
+    }
+
+    match subtree {
+        MinMaxReturn::Node {
+            options,
+            moving_snake_id,
+            score,
+            is_maximizing: true,
+        } => {
+            let options = options
+                .into_iter()
+                .map(|(dir, next)| {
+                    (
+                        dir,
+                        joint_move_columns(next, node, players, opponent_tendencies, vec![]),
+                    )
+                })
+                .collect();
+
+            Some((
+                depth,
+                MinMaxReturn::Simultaneous {
+                    moving_snake_id,
+                    options,
+                    strategy: vec![],
+                    score,
+                },
+            ))
+        }
+        _ => None,
+    }
+}
+
+impl<T, E> DeviousDevin<T, E>
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + VictorDeterminableGame
+        + Send
+        + 'static
+        + PositionGettableGame
+        + HeadGettableGame
+        + LengthGettableGame
+        + HealthGettableGame
+        + Clone
+        + Copy
+        + APrimeCalculable
+        + MoveEvaluatableGame
+        + SimulableGame<Instruments>
+        + FoodGettableGame
+        + SnakeBodyGettableGame
+        + SizeDeterminableGame,
+    E: Evaluator<T> + Clone + Send + 'static,
+{
+    fn time_limit_ms(&self) -> i64 {
+        const NETWORK_LATENCY_PADDING: i64 = 100;
+        self.game_info.timeout - NETWORK_LATENCY_PADDING
+    }
+
+    fn max_duration(&self) -> Duration {
+        Duration::new(0, (self.time_limit_ms() * 1_000_000).try_into().unwrap())
+    }
+
+    /// The tablebase move once a duel is closed out enough to solve exactly
+    ///
+    /// We only trust wins and draws. When the tablebase says we lose against perfect play, the
+    /// search still looks for the line that gives the opponent the most chances to go wrong.
+    /// Outcomes further out than food could spawn and be eaten aren't trusted either
+    fn tablebase_move(&self, players: &[T::SnakeIDType], time: Duration) -> Option<Move> {
+        let alive: Vec<_> = players
+            .iter()
+            .filter(|s| self.game.is_alive(s))
+            .cloned()
+            .collect();
+        let free = free_cells(&self.game, &alive);
+        if alive.len() != 2 || &alive[0] != self.game.you_id() || free > TABLEBASE_FREE_CELLS {
+            return None;
+        }
+
+        // The search already sees a turn ahead by itself
+        let trusted = trusted_distance(&self.game, &alive, &self.rules);
+        if trusted <= 1 {
+            return None;
+        }
+        // It would only run out again, at least until someone eats and the duel gets smaller
+        if self
+            .state
+            .get::<UnsolvedDuel>()
+            .map_or(false, |unsolved| free >= unsolved.free_cells)
+        {
+            return None;
+        }
+
+        // Whatever we solved on earlier turns of this game is likely to come up again
+        let mut table = self.state.take::<Tablebase>().unwrap_or_default();
+        let entry = table.solve(
+            &self.game,
+            &alive,
+            self.tablebase.as_deref(),
+            &SearchDeadline::new(time),
+            SOLVE_NODE_BUDGET,
+        );
+        self.state.set(table);
+
+        let entry = match entry {
+            Some(entry) => entry,
+            None => {
+                info!(free_cells = free, "Couldn't solve the duel in time");
+                self.state.set(UnsolvedDuel { free_cells: free });
+                return None;
+            }
+        };
+
+        match entry {
+            TablebaseEntry {
+                outcome: Outcome::Loss(_),
+                ..
+            } => None,
+            entry if entry.outcome.distance() > trusted => None,
+            entry => entry.best_move,
+        }
+    }
+
+    /// Returns the deepest search that finished before the deadline, and how deep it went
+    ///
+    /// When last turn's search covered the moves that were actually played, we start from its
+    /// subtree instead of from scratch. It is already ordered, so we get back to the depth it
+    /// reached much quicker
+    fn deepened_minimax(
+        &self,
+        players: Vec<T::SnakeIDType>,
+        previous: Option<PreviousSearch<T, E::Score>>,
+        time_left: Duration,
+    ) -> (usize, MinMaxReturn<T, E::Score>) {
+        let node = self.game;
+        let you_id = node.you_id();
+        let evaluator = self.evaluator.clone();
+        let algorithm = self.algorithm;
+        let opponent_tendencies: Vec<_> = players
+            .iter()
+            .map(|id| {
+                self.opponent_tendencies
+                    .iter()
+                    .find(|(s, _)| s == id)
+                    .map(|(_, t)| t.clone())
+            })
+            .collect();
+        let root_predictions: Vec<_> = players
+            .iter()
+            .map(|id| {
+                let (_, predicted) = self.root_predictions.iter().find(|(s, _)| s == id)?;
+                let possible = node.possible_moves(&node.get_head_as_native_position(id));
+
+                possible
+                    .iter()
+                    .any(|(dir, _)| dir == predicted)
+                    .then(|| *predicted)
+            })
+            .collect();
+
+        const RUNAWAY_DEPTH_LIMIT: usize = 100;
+
+        let previous_depth = previous.as_ref().map(|p| p.depth);
+        let reused = previous.and_then(|previous| {
+            reuse_previous_search(previous, &node, &players, algorithm, &opponent_tendencies)
+        });
+        let reused_depth = reused.as_ref().map(|(depth, _)| *depth);
+        // The first depth we search after reusing a tree is already a deep one, so if it
+        // misses the deadline we fall back to what last turn's search said about this board
+        let fallback = reused.clone();
+
+        let deadline = SearchDeadline::new(time_left);
+        let worker_deadline = deadline.clone();
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut current_depth, mut current_return) = match reused {
+                Some((depth, subtree)) => (depth, Some(subtree)),
+                None => (2, None),
+            };
+            let mut transposition_table = TranspositionTable::default();
+            let settings = SearchSettings {
+                algorithm,
+                deadline: &worker_deadline,
+                evaluator: &evaluator,
+                opponent_tendencies: &opponent_tendencies,
+                root_predictions: &root_predictions,
+            };
+            loop {
+                let next = search(
+                    node,
+                    &players,
+                    current_depth,
+                    current_return,
+                    &mut transposition_table,
+                    &settings,
+                );
+
+                if worker_deadline.is_expired() {
+                    return;
+                }
+
+                if tx.send((current_depth, next.clone())).is_err() {
+                    return;
+                }
+
+                current_return = Some(next);
+
+                current_depth += players.len();
+            }
+        });
+
+        info!(algorithm = ?self.algorithm, reused_depth, "Starting deepened_minimax");
+
+        let mut current = None;
+
+        while let Ok((depth, result)) = rx.recv_timeout(deadline.remaining().unwrap_or_default()) {
+            let current_score = result.score();
+            let terminal_depth = current_score.terminal_depth();
+            info!(depth, current_score = ?&current_score, current_direction = ?result.direction_for(you_id), "Just finished depth");
+
+            current = Some((depth, result));
+
+            if let Some(terminal_depth) = terminal_depth {
+                if depth > (terminal_depth as usize) {
+                    info!(depth, "This game is over, no need to keep going");
+                    break;
+                }
+            }
+
+            if depth > RUNAWAY_DEPTH_LIMIT {
+                break;
+            };
+        }
+
+        deadline.abort();
+
+        if let Some((depth, result)) = &current {
+            // How much deeper we got than last turn, which is where reusing its tree pays off
+            let depth_gained = previous_depth.map(|previous| *depth as i64 - previous as i64);
+            info!(depth, depth_gained, reused_depth, score = ?result.score(), direction = ?result.direction_for(you_id), strategy = ?result.strategy(), "Finished deepened_minimax");
+        }
+
+        if current.is_none() {
+            info!(
+                reused_depth,
+                "Missed the deadline, falling back to last turn's search"
+            );
+        }
+
+        current
+            .or(fallback)
+            .expect("We weren't able to do even a single layer of minmax")
+    }
+}
+
+impl<T> DeviousDevin<T, StandardEvaluator>
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + LengthGettableGame
+        + HealthGettableGame
+        + VictorDeterminableGame
+        + SimulableGame<Instruments>
+        + Clone
+        + Sync
+        + Copy
+        + APrimeCalculable
+        + FoodGettableGame
+        + SnakeBodyGettableGame
+        + MoveEvaluatableGame
+        + SizeDeterminableGame
+        + Send
+        + 'static,
+{
+    /// Swaps in the evaluator that was asked for
+    fn into_boxed(self, choice: EvaluatorChoice) -> BoxedSnake {
+        match choice {
+            EvaluatorChoice::Standard => Box::new(self),
+            EvaluatorChoice::AreaControl => Box::new(DeviousDevin {
+                game: self.game,
+                game_info: self.game_info,
+                turn: self.turn,
+                evaluator: AreaControlEvaluator {
+                    rules: self.evaluator.rules,
+                },
+                algorithm: self.algorithm,
+                state: self.state,
+                rules: self.rules,
+                tablebase: self.tablebase,
+                opponent_tendencies: self.opponent_tendencies,
+                root_predictions: self.root_predictions,
+                started: self.started,
+            }),
+        }
+    }
+}
+
+pub fn minmax_bench_entry<T, E>(
+    game_state: T,
+    max_turns: usize,
+    evaluator: &E,
+) -> MinMaxReturn<T, E::Score>
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + LengthGettableGame
+        + HealthGettableGame
+        + VictorDeterminableGame
+        + HeadGettableGame
+        + SimulableGame<Instruments>
+        + Clone
+        + Copy
+        + APrimeCalculable
+        + MoveEvaluatableGame
+        + FoodGettableGame
+        + SnakeBodyGettableGame,
+    E: Evaluator<T>,
+{
+    let my_id = game_state.you_id();
+    let mut sorted_ids = game_state.get_snake_ids();
+    sorted_ids.sort_by_key(|snake_id| if snake_id == my_id { -1 } else { 1 });
+
+    let settings = SearchSettings {
+        algorithm: SearchAlgorithm::for_snake_count(sorted_ids.len()),
+        deadline: &SearchDeadline::unbounded(),
+        evaluator,
+        opponent_tendencies: &[],
+        root_predictions: &[],
+    };
+
+    search(
+        game_state,
+        &sorted_ids,
+        max_turns * sorted_ids.len(),
+        None,
+        &mut TranspositionTable::default(),
+        &settings,
+    )
+}
+
+pub fn minmax_deepened_bench_entry<T, E>(
+    game_state: T,
+    max_turns: usize,
+    evaluator: &E,
+) -> MinMaxReturn<T, E::Score>
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + LengthGettableGame
+        + HealthGettableGame
+        + VictorDeterminableGame
+        + HeadGettableGame
+        + SimulableGame<Instruments>
+        + Clone
+        + APrimeCalculable
+        + MoveEvaluatableGame
+        + Copy
+        + FoodGettableGame
+        + SnakeBodyGettableGame,
+    E: Evaluator<T>,
+{
+    let my_id = game_state.you_id();
+    let mut sorted_ids = game_state.get_snake_ids();
+    sorted_ids.sort_by_key(|snake_id| if snake_id == my_id { -1 } else { 1 });
+
+    let players = sorted_ids;
+
+    let max_depth = max_turns * players.len();
+    let mut current_depth = players.len();
+    let mut current_return = None;
+    let mut transposition_table = TranspositionTable::default();
+    let deadline = SearchDeadline::unbounded();
+    let settings = SearchSettings {
+        algorithm: SearchAlgorithm::for_snake_count(players.len()),
+        deadline: &deadline,
+        evaluator,
+        opponent_tendencies: &[],
+        root_predictions: &[],
+    };
+    while current_depth <= max_depth {
+        current_return = Some(search(
+            game_state,
+            &players,
+            current_depth,
+            current_return,
+            &mut transposition_table,
+            &settings,
+        ));
+
+        current_depth += players.len();
     }
 
     current_return.unwrap()
 }
 
-pub struct DeviousDevinFactory;
+#[derive(Default)]
+pub struct DeviousDevinFactory {
+    /// Checked before we search, so well known openings don't use up any time
+    pub opening_book: Option<Arc<OpeningBook>>,
+    /// Solved duel endings to start from, on top of whatever each game solves for itself
+    pub tablebase: Option<Arc<Tablebase>>,
+    /// Our snakes to compare the opponents against. Once one of these predicts an opponent
+    /// well enough, we search its prediction instead of every move that opponent could make
+    pub fingerprint_candidates: Arc<Vec<BoxedFactory>>,
+}
+
+/// Plays the move the opening book has for this turn
+struct BookMove(Move);
+
+impl BattlesnakeAI for BookMove {
+    fn make_move(&self) -> Result<MoveOutput, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(MoveOutput {
+            r#move: format!("{}", self.0),
+            shout: None,
+        })
+    }
+}
 
 impl BattlesnakeFactory for DeviousDevinFactory {
     fn name(&self) -> String {
@@ -410,31 +1839,123 @@ impl BattlesnakeFactory for DeviousDevinFactory {
     }
 
     fn from_wire_game(&self, game: Game) -> BoxedSnake {
+        self.from_wire_game_with_context(game, GameContext::default())
+    }
+
+    fn from_wire_game_with_context(&self, game: Game, context: GameContext) -> BoxedSnake {
+        let started = Instant::now();
+        let state = context.state;
+        let id_map = build_snake_id_map(&game);
+
+        // Book moves are still turns the opponents moved on, so we learn from every board
+        let mut opponent_model = state.take::<OpponentModel>().unwrap_or_default();
+        opponent_model.observe(&game);
+        let opponent_tendencies: Vec<_> = id_map
+            .iter()
+            .filter_map(|(name, id)| Some((*id, opponent_model.tendencies(name)?.clone())))
+            .collect();
+        state.set(opponent_model);
+
+        let mut fingerprints = state.take::<Fingerprints>().unwrap_or_default();
+        fingerprints.observe(&game, &self.fingerprint_candidates, context.rules);
+        let root_predictions: Vec<_> = id_map
+            .iter()
+            .filter_map(|(name, id)| Some((*id, fingerprints.predicted_move(name)?)))
+            .collect();
+        state.set(fingerprints);
+        if !root_predictions.is_empty() {
+            info!(game_id = %game.game.id, turn = game.turn, predicted = root_predictions.len(), "Using fingerprinted opponents' moves");
+        }
+
+        if let Some(m) = self
+            .opening_book
+            .as_ref()
+            .and_then(|book| book.lookup(&game, &context.rules))
+        {
+            info!(game_id = %game.game.id, turn = game.turn, direction = ?m, "Playing from the opening book");
+            return Box::new(BookMove(m));
+        }
+
+        // The wire game search has no tablebase or tree reuse, but it gets whatever time the
+        // opponent models and fingerprints left us
+        if !game.board.hazards.is_empty() && !context.rules.simulated_by_compact_boards() {
+            info!(game_id = %game.game.id, turn = game.turn, hazard_damage = context.rules.hazard_damage_per_turn, "Searching the wire game to get the hazard damage right");
+            let time_limit = crate::devious_devin_mutable::search_time(game.game.timeout)
+                .saturating_sub(started.elapsed());
+            return Box::new(crate::devious_devin_mutable::DeviousDevin {
+                game,
+                rules: context.rules,
+                time_limit,
+            });
+        }
+
+        let evaluator = StandardEvaluator {
+            rules: context.rules,
+        };
+        let evaluator_choice = EvaluatorChoice::from_env();
+        let tablebase = self.tablebase.clone();
         let game_info = game.game.clone();
         let turn = game.turn;
+        let algorithm = SearchAlgorithm::from_env_or_snake_count(game.board.snakes.len());
 
         let best_board = game.to_best_cell_board().unwrap();
         let inner: BoxedSnake = match best_board {
-            BestCellBoard::Standard(b) => Box::new(DeviousDevin {
+            BestCellBoard::Standard(b) => DeviousDevin {
                 game_info,
                 turn,
                 game: *b,
-            }),
-            BestCellBoard::LargestU8(b) => Box::new(DeviousDevin {
+                evaluator,
+                algorithm,
+                state,
+                rules: context.rules,
+                tablebase,
+                opponent_tendencies,
+                root_predictions,
+                started,
+            }
+            .into_boxed(evaluator_choice),
+            BestCellBoard::LargestU8(b) => DeviousDevin {
                 game_info,
                 turn,
                 game: *b,
-            }),
-            BestCellBoard::Large(b) => Box::new(DeviousDevin {
+                evaluator,
+                algorithm,
+                state,
+                rules: context.rules,
+                tablebase,
+                opponent_tendencies,
+                root_predictions,
+                started,
+            }
+            .into_boxed(evaluator_choice),
+            BestCellBoard::Large(b) => DeviousDevin {
                 game_info,
                 turn,
                 game: *b,
-            }),
-            BestCellBoard::Silly(b) => Box::new(DeviousDevin {
+                evaluator,
+                algorithm,
+                state,
+                rules: context.rules,
+                tablebase,
+                opponent_tendencies,
+                root_predictions,
+                started,
+            }
+            .into_boxed(evaluator_choice),
+            BestCellBoard::Silly(b) => DeviousDevin {
                 game_info,
                 turn,
                 game: *b,
-            }),
+                evaluator,
+                algorithm,
+                state,
+                rules: context.rules,
+                tablebase,
+                opponent_tendencies,
+                root_predictions,
+                started,
+            }
+            .into_boxed(evaluator_choice),
         };
 
         inner
@@ -453,4 +1974,396 @@ impl BattlesnakeFactory for DeviousDevinFactory {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::evaluator::StandardScore;
+    use crate::opponent_model::Tendency;
+    use battlesnake_game_types::compact_representation::CellBoard;
+
+    #[test]
+    fn test_search_algorithm_depends_on_snake_count() {
+        assert_eq!(
+            SearchAlgorithm::for_snake_count(2),
+            SearchAlgorithm::Paranoid
+        );
+        assert_eq!(
+            SearchAlgorithm::for_snake_count(4),
+            SearchAlgorithm::BestReplySearch
+        );
+    }
+
+    #[test]
+    fn test_search_algorithm_from_name() {
+        assert_eq!(
+            SearchAlgorithm::from_name("max-n"),
+            Some(SearchAlgorithm::MaxN)
+        );
+        assert_eq!(
+            SearchAlgorithm::from_name("best-reply"),
+            Some(SearchAlgorithm::BestReplySearch)
+        );
+        assert_eq!(SearchAlgorithm::from_name("expectimax"), None);
+    }
+
+    #[test]
+    fn test_evaluator_choice_from_name() {
+        assert_eq!(
+            EvaluatorChoice::from_name("area-control"),
+            Some(EvaluatorChoice::AreaControl)
+        );
+        assert_eq!(
+            EvaluatorChoice::from_name("standard"),
+            Some(EvaluatorChoice::Standard)
+        );
+        assert_eq!(EvaluatorChoice::from_name("voronoi"), None);
+    }
+
+    /// We are at (5, 5) with a longer snake at (7, 5), so we both moving to (6, 5) is a head to
+    /// head we lose. A third, hungry snake is in the corner next to some food
+    fn three_snakes_game() -> Game {
+        serde_json::from_str(include_str!("../fixtures/three_snakes.json")).unwrap()
+    }
+
+    fn three_snakes() -> CellBoard4Snakes11x11 {
+        let game = three_snakes_game();
+        let id_map = build_snake_id_map(&game);
+
+        CellBoard::convert_from_game(game, &id_map).unwrap()
+    }
+
+    fn id_of(name: &str) -> SnakeId {
+        build_snake_id_map(&three_snakes_game())[name]
+    }
+
+    fn sorted_players(node: &CellBoard4Snakes11x11) -> Vec<SnakeId> {
+        let mut players = node.get_snake_ids();
+        players.sort_by_key(|s| if s == node.you_id() { -1 } else { 1 });
+
+        players
+    }
+
+    #[test]
+    fn test_best_reply_picks_the_most_dangerous_replier() {
+        let node = three_snakes();
+        let players = sorted_players(&node);
+        let deadline = SearchDeadline::unbounded();
+        let evaluator = StandardEvaluator::default();
+        let settings = SearchSettings {
+            algorithm: SearchAlgorithm::BestReplySearch,
+            deadline: &deadline,
+            evaluator: &evaluator,
+            opponent_tendencies: &[],
+            root_predictions: &[],
+        };
+
+        let result = best_reply(
+            node,
+            &players,
+            1,
+            ScoreEndState::WORST_POSSIBLE,
+            ScoreEndState::BEST_POSSIBLE,
+            players.len(),
+            None,
+            vec![(*node.you_id(), Move::Right)],
+            &mut TranspositionTable::default(),
+            zobrist_hash(&node, &players),
+            &settings,
+        );
+
+        match result {
+            MinMaxReturn::BestReply { options, score } => {
+                let (replier, dir, _) = &options[0];
+
+                assert_eq!(*replier, id_of("long"));
+                assert_eq!(*dir, Move::Left);
+                assert_eq!(score, ScoreEndState::Lose(players.len() as i64));
+            }
+            other => panic!("Expected a Best-Reply layer, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_opponent_tendencies_prune_replies_and_root_columns() {
+        let node = three_snakes();
+        let players = sorted_players(&node);
+        let long = id_of("long");
+        // Long has always gone for the closest head, which is ours just to its left
+        let opponent_tendencies: Vec<_> = players
+            .iter()
+            .map(|s| (*s == long).then(|| Tendencies::always(Tendency::ChasesHeads, 20)))
+            .collect();
+        let deadline = SearchDeadline::unbounded();
+        let evaluator = StandardEvaluator::default();
+        let settings = SearchSettings {
+            algorithm: SearchAlgorithm::BestReplySearch,
+            deadline: &deadline,
+            evaluator: &evaluator,
+            opponent_tendencies: &opponent_tendencies,
+            root_predictions: &[],
+        };
+
+        let result = best_reply(
+            node,
+            &players,
+            1,
+            ScoreEndState::WORST_POSSIBLE,
+            ScoreEndState::BEST_POSSIBLE,
+            players.len(),
+            None,
+            vec![(*node.you_id(), Move::Up)],
+            &mut TranspositionTable::default(),
+            zobrist_hash(&node, &players),
+            &settings,
+        );
+        let long_replies: Vec<_> = match result {
+            MinMaxReturn::BestReply { options, .. } => options
+                .into_iter()
+                .filter(|(replier, _, _)| *replier == long)
+                .map(|(_, dir, _)| dir)
+                .collect(),
+            other => panic!("Expected a Best-Reply layer, got {:?}", other),
+        };
+        assert!(long_replies.contains(&Move::Left));
+        assert!(!long_replies.contains(&Move::Up));
+        assert!(!long_replies.contains(&Move::Down));
+
+        let long_columns = |opponent_tendencies: &[Option<Tendencies>]| -> Vec<Move> {
+            root_columns(
+                &node,
+                &players,
+                SearchAlgorithm::Paranoid,
+                opponent_tendencies,
+            )
+            .into_iter()
+            .filter_map(|joint_move| joint_move.into_iter().find(|(s, _)| *s == long))
+            .map(|(_, dir)| dir)
+            .collect()
+        };
+        assert_eq!(long_columns(&[]).len(), 4);
+        assert_eq!(
+            long_columns(&opponent_tendencies),
+            vec![Move::Left, Move::Right]
+        );
+    }
+
+    #[test]
+    fn test_max_n_lets_each_snake_maximize_its_own_score() {
+        let node = three_snakes();
+        let players = sorted_players(&node);
+        let hungry = id_of("hungry");
+        let deadline = SearchDeadline::unbounded();
+        let evaluator = StandardEvaluator::default();
+        let settings = SearchSettings {
+            algorithm: SearchAlgorithm::MaxN,
+            deadline: &deadline,
+            evaluator: &evaluator,
+            opponent_tendencies: &[],
+            root_predictions: &[],
+        };
+
+        let (mut tree, scores) = max_n(node, &players, 0, players.len(), None, vec![], &settings);
+        assert_eq!(scores.len(), players.len());
+
+        // Follow the line max-n picked down to the hungry snake's layer. Going down is its own
+        // body, so eating the food to the right is what's best for it, whatever it does to us
+        loop {
+            match tree {
+                MinMaxReturn::Node {
+                    moving_snake_id,
+                    options,
+                    ..
+                } if moving_snake_id == hungry => {
+                    assert_eq!(options[0].0, Move::Right);
+                    break;
+                }
+                MinMaxReturn::Node { mut options, .. } => tree = options.remove(0).1,
+                other => panic!("Never got to the hungry snake's layer, got {:?}", other),
+            }
+        }
+
+        let hungry_index = players.iter().position(|s| *s == hungry).unwrap();
+        assert!(matches!(scores[hungry_index], ScoreEndState::Evaluated(_)));
+    }
+
+    #[test]
+    fn test_simultaneous_root_only_branches_on_contested_opponents() {
+        let node = three_snakes();
+        let players = sorted_players(&node);
+        let long = id_of("long");
+        let deadline = SearchDeadline::unbounded();
+        let evaluator = StandardEvaluator::default();
+        let settings = SearchSettings {
+            algorithm: SearchAlgorithm::Paranoid,
+            deadline: &deadline,
+            evaluator: &evaluator,
+            opponent_tendencies: &[],
+            root_predictions: &[],
+        };
+        let root = |tt: &mut TranspositionTable<StandardScore>| {
+            simultaneous_root(node, &players, players.len(), None, tt, &settings)
+        };
+
+        let result = root(&mut TranspositionTable::default());
+        let (options, strategy) = match &result {
+            MinMaxReturn::Simultaneous {
+                options, strategy, ..
+            } => (options.clone(), strategy.clone()),
+            other => panic!("Expected a simultaneous root, got {:?}", other),
+        };
+
+        // The hungry snake can't reach any of our cells, so only the long snake branches
+        let long_moves = node
+            .possible_moves(&node.get_head_as_native_position(&long))
+            .len();
+        for (_, row) in options.iter() {
+            assert_eq!(row.len(), long_moves);
+        }
+
+        // Right runs into the long snake's head if it goes left
+        let (_, right) = options.iter().find(|(dir, _)| *dir == Move::Right).unwrap();
+        let (joint_move, worst) = &right[0];
+        assert!(joint_move.contains(&(long, Move::Left)));
+        assert!(matches!(worst.score(), ScoreEndState::Lose(_)));
+
+        let total: f64 = strategy.iter().map(|(_, p)| p).sum();
+        assert_eq!(strategy.len(), options.len());
+        assert!((total - 1.0).abs() < 1e-6);
+
+        // Our move is only sampled once the search is done, so searching again gives us the
+        // same line to deepen from
+        let again = root(&mut TranspositionTable::default());
+        assert_eq!(
+            again.direction_for(node.you_id()),
+            result.direction_for(node.you_id())
+        );
+    }
+
+    #[test]
+    fn test_reuses_the_subtree_for_the_moves_that_were_played() {
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let before: CellBoard4Snakes11x11 = CellBoard::convert_from_game(game, &id_map).unwrap();
+
+        let mut players = before.get_snake_ids();
+        players.sort_by_key(|s| if s == before.you_id() { -1 } else { 1 });
+        let algorithm = SearchAlgorithm::for_snake_count(players.len());
+        let deadline = SearchDeadline::unbounded();
+        let evaluator = AreaControlEvaluator {
+            rules: Rules::default(),
+        };
+        let settings = SearchSettings {
+            algorithm,
+            deadline: &deadline,
+            evaluator: &evaluator,
+            opponent_tendencies: &[],
+            root_predictions: &[],
+        };
+
+        let depth = 2 * players.len();
+        let result = search(
+            before,
+            &players,
+            depth,
+            None,
+            &mut TranspositionTable::default(),
+            &settings,
+        );
+
+        let joint_move: JointMove<CellBoard4Snakes11x11> = players
+            .iter()
+            .map(|s| (s.clone(), result.direction_for(s).unwrap()))
+            .collect();
+        let after = before.evaluate_moves(&joint_move);
+        assert_eq!(
+            played_moves(&before, &after, &players),
+            Some(joint_move.clone())
+        );
+
+        let previous = PreviousSearch {
+            board: before,
+            turn: 200,
+            players: players.clone(),
+            depth,
+            result,
+        };
+        let (reused_depth, subtree) =
+            reuse_previous_search(previous, &after, &players, algorithm, &[]).unwrap();
+        assert_eq!(reused_depth, players.len());
+        assert!(matches!(subtree, MinMaxReturn::Simultaneous { .. }));
+
+        let next = search(
+            after,
+            &players,
+            reused_depth + players.len(),
+            Some(subtree),
+            &mut TranspositionTable::default(),
+            &settings,
+        );
+        assert!(next.direction_for(after.you_id()).is_some());
+    }
+
+    #[test]
+    fn test_falls_back_to_the_reused_subtree_when_out_of_time() {
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game_info = game.game.clone();
+        let before: CellBoard4Snakes11x11 = CellBoard::convert_from_game(game, &id_map).unwrap();
+
+        let mut players = before.get_snake_ids();
+        players.sort_by_key(|s| if s == before.you_id() { -1 } else { 1 });
+        let algorithm = SearchAlgorithm::for_snake_count(players.len());
+        let deadline = SearchDeadline::unbounded();
+        let evaluator = StandardEvaluator::default();
+        let settings = SearchSettings {
+            algorithm,
+            deadline: &deadline,
+            evaluator: &evaluator,
+            opponent_tendencies: &[],
+            root_predictions: &[],
+        };
+
+        let depth = 2 * players.len();
+        let result = search(
+            before,
+            &players,
+            depth,
+            None,
+            &mut TranspositionTable::default(),
+            &settings,
+        );
+        let joint_move: JointMove<CellBoard4Snakes11x11> = players
+            .iter()
+            .map(|s| (s.clone(), result.direction_for(s).unwrap()))
+            .collect();
+        let after = before.evaluate_moves(&joint_move);
+
+        let devin = DeviousDevin {
+            game: after,
+            game_info,
+            turn: 1,
+            evaluator,
+            algorithm,
+            state: GameState::default(),
+            rules: Rules::default(),
+            tablebase: None,
+            opponent_tendencies: vec![],
+            root_predictions: vec![],
+            started: Instant::now(),
+        };
+        let previous = PreviousSearch {
+            board: before,
+            turn: 0,
+            players: players.clone(),
+            depth,
+            result,
+        };
+
+        // Not even the first layer can finish without any time, so all we have is last turn's
+        let (reused_depth, subtree) =
+            devin.deepened_minimax(players.clone(), Some(previous), Duration::from_secs(0));
+        assert_eq!(reused_depth, players.len());
+        assert!(subtree.direction_for(after.you_id()).is_some());
+    }
+}
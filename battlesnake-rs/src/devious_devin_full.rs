@@ -1,7 +1,9 @@
 use crate::a_prime::APrimeCalculable;
 use crate::devious_devin_mutable::{
-    score, Instruments, ScoreEndState, BEST_POSSIBLE_SCORE_STATE, WORT_POSSIBLE_SCORE_STATE,
+    Instruments, ScoreEndState, BEST_POSSIBLE_SCORE_STATE, WORT_POSSIBLE_SCORE_STATE,
 };
+use crate::evaluator::{Evaluator, StandardEvaluator};
+use crate::search_deadline::SearchDeadline;
 use crate::*;
 
 use battlesnake_game_types::compact_representation::{BestCellBoard, ToBestCellBoard};
@@ -14,7 +16,7 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::mpsc;
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tracing::{info, info_span};
 
 pub struct FullDeviousDevin<T> {
@@ -205,6 +207,7 @@ fn minimax_min<
     mut beta: ScoreEndState,
     max_depth: usize,
     previous_return: Option<MinMaxReturn<T>>,
+    deadline: &SearchDeadline,
 ) -> MinMaxReturn<T> {
     let mut prev_options = match previous_return {
         Some(MinMaxReturn::MinLayer { options, .. }) => options,
@@ -228,7 +231,7 @@ fn minimax_min<
     other_moves_and_baords.sort_by_key(|(i, _prev, _other_moves, _board)| i.unwrap_or(usize::MAX));
 
     for (_, prev, moves, board) in other_moves_and_baords.into_iter() {
-        let next_move_return = minimax(&board, depth + 1, alpha, beta, max_depth, prev);
+        let next_move_return = minimax(&board, depth + 1, alpha, beta, max_depth, prev, deadline);
 
         let value = next_move_return.score();
         options.push((moves, next_move_return));
@@ -283,6 +286,7 @@ where
         BEST_POSSIBLE_SCORE_STATE,
         max_depth,
         None,
+        &SearchDeadline::unbounded(),
     )
 }
 
@@ -302,6 +306,7 @@ where
     let max_depth = max_turns * 2;
     let mut current_depth = 2;
     let mut current_return = None;
+    let deadline = SearchDeadline::unbounded();
     while current_depth <= max_depth {
         current_return = Some(minimax(
             &game_state,
@@ -310,6 +315,7 @@ where
             BEST_POSSIBLE_SCORE_STATE,
             current_depth,
             current_return,
+            &deadline,
         ));
 
         current_depth += 2;
@@ -325,6 +331,7 @@ fn minimax<T>(
     mut beta: ScoreEndState,
     max_depth: usize,
     previous_return: Option<MinMaxReturn<T>>,
+    deadline: &SearchDeadline,
 ) -> MinMaxReturn<T>
 where
     T: YouDeterminableGame
@@ -345,6 +352,12 @@ where
         );
     };
 
+    if deadline.is_expired() {
+        return MinMaxReturn::Leaf {
+            score: WORT_POSSIBLE_SCORE_STATE,
+        };
+    }
+
     let you_id = node.you_id();
 
     if node.is_over() {
@@ -363,7 +376,7 @@ where
     }
 
     if depth >= max_depth {
-        let score = score(node);
+        let score = ScoreEndState::Evaluated(StandardEvaluator::default().score(node));
         return MinMaxReturn::Leaf { score };
     }
 
@@ -439,6 +452,7 @@ where
             beta,
             max_depth,
             previous_return,
+            deadline,
         );
         let value = next_move_return.score();
         options.push((you_move.1, next_move_return));
@@ -500,7 +514,8 @@ where
 
         const RUNAWAY_DEPTH_LIMIT: usize = 100;
 
-        let started_at = Instant::now();
+        let deadline = SearchDeadline::new(self.max_duration());
+        let worker_deadline = deadline.clone();
 
         let (tx, rx) = mpsc::channel();
         thread::spawn(move || {
@@ -514,8 +529,13 @@ where
                     BEST_POSSIBLE_SCORE_STATE,
                     current_depth,
                     current_return,
+                    &worker_deadline,
                 );
 
+                if worker_deadline.is_expired() {
+                    return;
+                }
+
                 if tx.send((current_depth, next.clone())).is_err() {
                     return;
                 }
@@ -528,28 +548,26 @@ where
 
         let mut current = None;
 
-        let max_duration = self.max_duration();
-
-        while started_at.elapsed() < max_duration {
-            if let Ok((depth, result)) = rx.try_recv() {
-                let current_score = result.score();
-                info!(depth, current_score = ?&current_score, current_direction = ?result.my_best_move(), "Just finished depth");
+        while let Ok((depth, result)) = rx.recv_timeout(deadline.remaining().unwrap_or_default()) {
+            let current_score = result.score();
+            info!(depth, current_score = ?&current_score, current_direction = ?result.my_best_move(), "Just finished depth");
 
-                current = Some((depth, result));
+            current = Some((depth, result));
 
-                if let Some(terminal_depth) = current_score.terminal_depth() {
-                    if depth > (terminal_depth as usize) {
-                        info!(depth, "This game is over, no need to keep going");
-                        break;
-                    }
-                }
-
-                if depth > RUNAWAY_DEPTH_LIMIT {
+            if let Some(terminal_depth) = current_score.terminal_depth() {
+                if depth > (terminal_depth as usize) {
+                    info!(depth, "This game is over, no need to keep going");
                     break;
-                };
+                }
             }
+
+            if depth > RUNAWAY_DEPTH_LIMIT {
+                break;
+            };
         }
 
+        deadline.abort();
+
         if let Some((depth, result)) = &current {
             info!(depth, score = ?result.score(), direction = ?result.my_best_move(), "Finished deepened_minimax");
         }
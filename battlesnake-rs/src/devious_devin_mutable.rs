@@ -1,15 +1,27 @@
 use crate::a_prime::APrimeCalculable;
+use crate::evaluator::{Evaluator, StandardEvaluator, StandardScore};
+use crate::rules::Rules;
+use crate::search_deadline::SearchDeadline;
 use crate::*;
 
 use battlesnake_game_types::types::*;
 
 use std::sync::mpsc;
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tracing::{info, info_span};
 
 pub struct DeviousDevin<T> {
-    game: T,
+    pub(crate) game: T,
+    pub(crate) rules: Rules,
+    /// How long the search gets, once the time it takes to send our move back is taken off
+    pub(crate) time_limit: Duration,
+}
+
+/// How long we can search for when the game gives us `timeout_ms` to answer
+pub(crate) fn search_time(timeout_ms: i64) -> Duration {
+    const NETWORK_LATENCY_PADDING: i64 = 100;
+    Duration::from_millis((timeout_ms - NETWORK_LATENCY_PADDING).max(0) as u64)
 }
 
 #[derive(Debug)]
@@ -44,8 +56,8 @@ where
         let mut players: Vec<_> = sorted_ids.into_iter().map(Player::Snake).collect();
         players.push(Player::Nature);
 
-        let best_option =
-            info_span!("deepened_minmax").in_scope(|| deepened_minimax(self.game.clone(), players));
+        let best_option = info_span!("deepened_minmax")
+            .in_scope(|| deepened_minimax(self.game.clone(), players, self.rules, self.time_limit));
 
         Ok(MoveOutput {
             r#move: format!(
@@ -59,21 +71,23 @@ where
     }
 }
 
+/// The score of a node in the search tree, `S` is the score of the `Evaluator` used for boards
+/// where the game isn't over yet
 #[derive(Serialize, PartialEq, PartialOrd, Ord, Eq, Debug, Copy, Clone)]
-pub enum ScoreEndState {
+pub enum ScoreEndState<S = StandardScore> {
     /// depth: i64
     Lose(i64),
     /// depth: i64
     Tie(i64),
-    /// difference_in_snake_length: u16, negative_distance_to_nearest_food: Option<i32>, health: u8
-    ShorterThanOpponent(i64, Option<i32>, i64),
-    /// negative_distance_to_opponent: Option<i64>, difference_in_snake_length: i64, health: u8
-    LongerThanOpponent(Option<i32>, i64, i64),
+    Evaluated(S),
     /// depth: i64
     Win(i64),
 }
 
-impl ScoreEndState {
+impl<S> ScoreEndState<S> {
+    pub const BEST_POSSIBLE: Self = ScoreEndState::Win(i64::MAX);
+    pub const WORST_POSSIBLE: Self = ScoreEndState::Lose(i64::MIN);
+
     pub fn terminal_depth(&self) -> Option<i64> {
         match &self {
             ScoreEndState::Win(d) => Some(-d),
@@ -83,67 +97,8 @@ impl ScoreEndState {
     }
 }
 
-pub const BEST_POSSIBLE_SCORE_STATE: ScoreEndState = ScoreEndState::Win(i64::MAX);
-pub const WORT_POSSIBLE_SCORE_STATE: ScoreEndState = ScoreEndState::Lose(i64::MIN);
-
-pub fn score<
-    T: SnakeIDGettableGame
-        + YouDeterminableGame
-        + PositionGettableGame
-        + HeadGettableGame
-        + LengthGettableGame
-        + HealthGettableGame
-        + HeadGettableGame
-        + APrimeCalculable
-        + FoodGettableGame,
->(
-    node: &T,
-) -> ScoreEndState {
-    let me_id = node.you_id();
-    let opponents: Vec<T::SnakeIDType> = node
-        .get_snake_ids()
-        .into_iter()
-        .filter(|x| x != me_id)
-        .collect();
-
-    let opponent_heads: Vec<_> = opponents
-        .iter()
-        .map(|s| node.get_head_as_native_position(s))
-        .collect();
-    let my_head = node.get_head_as_native_position(me_id);
-
-    let my_length = node.get_length_i64(me_id);
-
-    let max_opponent_length = opponents
-        .iter()
-        .map(|o| node.get_length_i64(o))
-        .max()
-        .unwrap();
-    let length_difference = (my_length as i64) - (max_opponent_length as i64);
-    let my_health = node.get_health_i64(me_id);
-
-    let foods: Vec<_> = node.get_all_food_as_native_positions();
-    if max_opponent_length >= my_length || my_health < 20 {
-        let negative_closest_food_distance =
-            node.shortest_distance(&my_head, &foods, None).map(|x| -x);
-
-        return ScoreEndState::ShorterThanOpponent(
-            length_difference,
-            negative_closest_food_distance,
-            my_health.max(50),
-        );
-    }
-
-    let negative_distance_to_opponent = node
-        .shortest_distance(&my_head, &opponent_heads, None)
-        .map(|dist| -dist);
-
-    ScoreEndState::LongerThanOpponent(
-        negative_distance_to_opponent,
-        length_difference.max(4),
-        my_health.max(50),
-    )
-}
+pub const BEST_POSSIBLE_SCORE_STATE: ScoreEndState = ScoreEndState::BEST_POSSIBLE;
+pub const WORT_POSSIBLE_SCORE_STATE: ScoreEndState = ScoreEndState::WORST_POSSIBLE;
 
 #[derive(Clone, Debug, Serialize)]
 struct SnakeMove<T: PositionGettableGame> {
@@ -154,35 +109,86 @@ struct SnakeMove<T: PositionGettableGame> {
 }
 
 #[derive(Debug, Clone)]
-pub enum MinMaxReturn<T: SnakeIDGettableGame + Clone + Debug> {
+pub enum MinMaxReturn<T: SnakeIDGettableGame + Clone + Debug, S = StandardScore> {
     Node {
         is_maximizing: bool,
-        options: Vec<(Move, MinMaxReturn<T>)>,
+        options: Vec<(Move, MinMaxReturn<T, S>)>,
         moving_snake_id: T::SnakeIDType,
-        score: ScoreEndState,
+        score: ScoreEndState<S>,
     },
     Nature {
-        score: ScoreEndState,
-        next: Box<MinMaxReturn<T>>,
+        score: ScoreEndState<S>,
+        next: Box<MinMaxReturn<T, S>>,
+    },
+    /// A Best-Reply Search layer, where a single opponent picks a move while every other
+    /// opponent plays its default move
+    BestReply {
+        options: Vec<(T::SnakeIDType, Move, MinMaxReturn<T, S>)>,
+        score: ScoreEndState<S>,
+    },
+    /// Our move and the opponents' moves made at the same time, and solved as a matrix game
+    Simultaneous {
+        moving_snake_id: T::SnakeIDType,
+        /// One row for each of our moves, with a column for each joint move of the opponents.
+        /// The row we picked comes first and each row's worst column comes first in that row
+        options: Vec<(Move, Vec<(Vec<(T::SnakeIDType, Move)>, MinMaxReturn<T, S>)>)>,
+        /// The mixed strategy we picked our move from
+        strategy: Vec<(Move, f64)>,
+        score: ScoreEndState<S>,
     },
     Leaf {
-        score: ScoreEndState,
+        score: ScoreEndState<S>,
     },
 }
 
-impl<T: SnakeIDGettableGame + Clone + Debug> MinMaxReturn<T> {
-    pub fn score(&self) -> &ScoreEndState {
+impl<T: SnakeIDGettableGame + Clone + Debug, S> MinMaxReturn<T, S> {
+    pub fn score(&self) -> &ScoreEndState<S> {
         match self {
             MinMaxReturn::Node { score, .. } => score,
             MinMaxReturn::Nature { score, .. } => score,
+            MinMaxReturn::BestReply { score, .. } => score,
+            MinMaxReturn::Simultaneous { score, .. } => score,
             MinMaxReturn::Leaf { score } => score,
         }
     }
 
+    /// The mixed strategy we picked our move from, if this is a simultaneous move root
+    pub fn strategy(&self) -> Option<&[(Move, f64)]> {
+        match self {
+            MinMaxReturn::Simultaneous { strategy, .. } => Some(strategy),
+            _ => None,
+        }
+    }
+
     pub fn direction_for(&self, snake_id: &T::SnakeIDType) -> Option<Move> {
         match self {
             MinMaxReturn::Leaf { .. } => None,
             MinMaxReturn::Nature { next, .. } => next.direction_for(snake_id),
+            MinMaxReturn::BestReply { options, .. } => {
+                let (replying_snake_id, dir, next) = options.first()?;
+                if replying_snake_id == snake_id {
+                    Some(*dir)
+                } else {
+                    next.direction_for(snake_id)
+                }
+            }
+            MinMaxReturn::Simultaneous {
+                moving_snake_id,
+                options,
+                ..
+            } => {
+                let (dir, columns) = options.first()?;
+                if moving_snake_id == snake_id {
+                    return Some(*dir);
+                }
+
+                let (joint_move, next) = columns.first()?;
+                joint_move
+                    .iter()
+                    .find(|(s, _)| s == snake_id)
+                    .map(|(_, m)| *m)
+                    .or_else(|| next.direction_for(snake_id))
+            }
             MinMaxReturn::Node {
                 moving_snake_id,
                 options,
@@ -204,7 +210,13 @@ enum Player<Game: SnakeIDGettableGame> {
     Nature,
 }
 
-fn wrapped_score<T>(node: &T, depth: i64, max_depth: i64, num_players: i64) -> Option<ScoreEndState>
+fn wrapped_score<T>(
+    node: &T,
+    depth: i64,
+    max_depth: i64,
+    num_players: i64,
+    rules: &Rules,
+) -> Option<ScoreEndState>
 where
     T: SnakeIDGettableGame
         + YouDeterminableGame
@@ -242,7 +254,7 @@ where
     }
 
     if depth >= max_depth {
-        let score = score(node);
+        let score = ScoreEndState::Evaluated(StandardEvaluator { rules: *rules }.score(node));
         return Some(score);
     }
 
@@ -257,6 +269,8 @@ fn minimax<T>(
     beta: ScoreEndState,
     max_depth: usize,
     previous_return: Option<MinMaxReturn<T>>,
+    deadline: &SearchDeadline,
+    rules: &Rules,
 ) -> MinMaxReturn<T>
 where
     T: SnakeIDGettableGame
@@ -276,8 +290,20 @@ where
     let mut alpha = alpha;
     let mut beta = beta;
 
+    if deadline.is_expired() {
+        return MinMaxReturn::Leaf {
+            score: WORT_POSSIBLE_SCORE_STATE,
+        };
+    }
+
     let new_depth = depth.try_into().unwrap();
-    if let Some(s) = wrapped_score(node, new_depth, max_depth as i64, players.len() as i64) {
+    if let Some(s) = wrapped_score(
+        node,
+        new_depth,
+        max_depth as i64,
+        players.len() as i64,
+        rules,
+    ) {
         return MinMaxReturn::Leaf { score: s };
     }
 
@@ -321,7 +347,7 @@ where
                 };
 
             for ((dir, coor), previous_return) in possible_zipped.into_iter() {
-                let last_move = node.move_to(&coor, snake_id);
+                let last_move = node.move_to(&coor, snake_id, rules);
                 let next_move_return = minimax(
                     node,
                     players,
@@ -330,6 +356,8 @@ where
                     beta,
                     max_depth,
                     previous_return,
+                    deadline,
+                    rules,
                 );
                 let value = *next_move_return.score();
                 node.reverse_move(last_move);
@@ -369,6 +397,8 @@ where
                 beta,
                 max_depth,
                 previous_return,
+                deadline,
+                rules,
             );
             for m in nature_moves.into_iter() {
                 node.reverse_nature(m)
@@ -382,7 +412,12 @@ where
     }
 }
 
-fn deepened_minimax<T>(node: T, players: Vec<Player<T>>) -> MinMaxReturn<T>
+fn deepened_minimax<T>(
+    node: T,
+    players: Vec<Player<T>>,
+    rules: Rules,
+    time_limit: Duration,
+) -> MinMaxReturn<T>
 where
     T: SnakeIDGettableGame
         + YouDeterminableGame
@@ -402,7 +437,8 @@ where
 {
     const RUNAWAY_DEPTH_LIMIT: usize = 2_000;
 
-    let started_at = Instant::now();
+    let deadline = SearchDeadline::new(time_limit);
+    let worker_deadline = deadline.clone();
     let me_id = node.you_id().clone();
 
     let (tx, rx) = mpsc::channel();
@@ -420,8 +456,14 @@ where
                 BEST_POSSIBLE_SCORE_STATE,
                 current_depth,
                 current_return,
+                &worker_deadline,
+                &rules,
             );
 
+            if worker_deadline.is_expired() {
+                return;
+            }
+
             if tx.send((current_depth, next.clone())).is_err() {
                 return;
             }
@@ -434,18 +476,18 @@ where
 
     let mut current = None;
 
-    while started_at.elapsed() < Duration::new(0, 400_000_000) {
-        if let Ok((depth, result)) = rx.try_recv() {
-            info!(depth, current_score = ?result.score(), current_direction = ?result.direction_for(&me_id), "Just finished depth");
+    while let Ok((depth, result)) = rx.recv_timeout(deadline.remaining().unwrap_or_default()) {
+        info!(depth, current_score = ?result.score(), current_direction = ?result.direction_for(&me_id), "Just finished depth");
 
-            current = Some((depth, result));
+        current = Some((depth, result));
 
-            if depth > RUNAWAY_DEPTH_LIMIT {
-                break;
-            };
-        }
+        if depth > RUNAWAY_DEPTH_LIMIT {
+            break;
+        };
     }
 
+    deadline.abort();
+
     info!(score = ?current.as_ref().map(|x| x.1.score()), depth = ?current.as_ref().map(|(d, _)| d), "Finished deepened_minimax");
     current
         .map(|(_depth, result)| result)
@@ -485,6 +527,8 @@ where
         BEST_POSSIBLE_SCORE_STATE,
         max_turns * players.len(),
         None,
+        &SearchDeadline::unbounded(),
+        &Rules::default(),
     )
 }
 
@@ -515,6 +559,8 @@ where
     let max_depth = max_turns * players.len();
     let mut current_depth = players.len();
     let mut current_return = None;
+    let deadline = SearchDeadline::unbounded();
+    let rules = Rules::default();
     while current_depth <= max_depth {
         current_return = Some(minimax(
             &mut game_state,
@@ -524,6 +570,8 @@ where
             BEST_POSSIBLE_SCORE_STATE,
             current_depth,
             current_return,
+            &deadline,
+            &rules,
         ));
 
         current_depth += players.len();
@@ -542,7 +590,15 @@ impl BattlesnakeFactory for DeviousDevinFactory {
     fn from_wire_game(&self, game: Game) -> BoxedSnake {
         // let id_map = build_snake_id_map(&game);
         // let game = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
-        Box::new(DeviousDevin { game })
+        self.from_wire_game_with_context(game, GameContext::default())
+    }
+
+    fn from_wire_game_with_context(&self, game: Game, context: GameContext) -> BoxedSnake {
+        Box::new(DeviousDevin {
+            time_limit: search_time(game.game.timeout),
+            game,
+            rules: context.rules,
+        })
     }
 
     fn about(&self) -> AboutMe {
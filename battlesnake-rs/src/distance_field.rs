@@ -0,0 +1,225 @@
+use crate::a_prime::{APrimeCalculable, APrimeOptions};
+
+use rustc_hash::FxHashMap;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::hash::Hash;
+
+/// The cheapest cost from a set of sources to every cell they can reach
+///
+/// Costs are the same ones A* uses, so hazards and food are charged per the `APrimeOptions`
+/// the field was built with
+pub struct DistanceField<P> {
+    distances: FxHashMap<P, i32>,
+    predecessors: FxHashMap<P, Option<P>>,
+}
+
+impl<P: Hash + Eq + Clone> DistanceField<P> {
+    /// `None` when no source can reach this cell
+    pub fn distance_to(&self, cell: &P) -> Option<i32> {
+        self.distances.get(cell).copied()
+    }
+
+    /// The reachable target with the smallest distance, along with that distance
+    pub fn nearest(&self, targets: &[P]) -> Option<(P, i32)> {
+        targets
+            .iter()
+            .filter_map(|t| self.distance_to(t).map(|d| (t.clone(), d)))
+            .min_by_key(|(_, d)| *d)
+    }
+
+    /// The cheapest path to `cell`, starting from the source it is closest to and ending on
+    /// `cell` itself. Empty when `cell` can't be reached
+    pub fn path_to(&self, cell: &P) -> Vec<P> {
+        let mut path = vec![];
+        let mut current = self.predecessors.get(cell).map(|_| cell.clone());
+
+        while let Some(c) = current {
+            current = self.predecessors.get(&c).cloned().flatten();
+            path.push(c);
+        }
+
+        path.reverse();
+
+        path
+    }
+
+    /// The source that `cell` is closest to
+    pub fn closest_source(&self, cell: &P) -> Option<P> {
+        self.path_to(cell).into_iter().next()
+    }
+
+    /// Every cell the field reached, and its distance
+    pub fn iter(&self) -> impl Iterator<Item = (&P, &i32)> {
+        self.distances.iter()
+    }
+}
+
+/// A cell waiting to be expanded. Unlike the A* `Node` these only compare by cost, so the
+/// position type doesn't need to be `Ord`
+struct Frontier<P> {
+    cost: i32,
+    cell: P,
+}
+
+impl<P> PartialEq for Frontier<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl<P> Eq for Frontier<P> {}
+
+// Flipped so the `BinaryHeap` pops the cheapest cell first
+impl<P> Ord for Frontier<P> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.cmp(&self.cost)
+    }
+}
+
+impl<P> PartialOrd for Frontier<P> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+pub trait DistanceFieldCalculable: APrimeCalculable {
+    /// Dijkstra's from all of the `sources` at once
+    ///
+    /// Snake bodies get a distance, so heads and tails can be looked up, but the search never
+    /// goes through them. This is the same as A* treating them as reachable only when they are
+    /// a target. The `passable` and `extra_cost` callbacks apply the same way they do to A*
+    fn distance_field(
+        &self,
+        sources: &[Self::NativePositionType],
+        options: Option<APrimeOptions>,
+    ) -> DistanceField<Self::NativePositionType>;
+}
+
+impl<T: APrimeCalculable> DistanceFieldCalculable for T {
+    fn distance_field(
+        &self,
+        sources: &[Self::NativePositionType],
+        options: Option<APrimeOptions>,
+    ) -> DistanceField<Self::NativePositionType> {
+        let options = options.unwrap_or_default();
+
+        let mut distances: FxHashMap<T::NativePositionType, i32> = FxHashMap::default();
+        let mut predecessors: FxHashMap<T::NativePositionType, Option<T::NativePositionType>> =
+            FxHashMap::default();
+        let mut to_search: BinaryHeap<Frontier<T::NativePositionType>> = BinaryHeap::new();
+
+        for source in sources {
+            distances.insert(source.clone(), 0);
+            predecessors.insert(source.clone(), None);
+            to_search.push(Frontier {
+                cost: 0,
+                cell: source.clone(),
+            });
+        }
+
+        while let Some(Frontier { cost, cell }) = to_search.pop() {
+            if distances.get(&cell).map_or(false, |known| *known < cost) {
+                continue;
+            }
+            if cost > 0 && self.is_obstacle(&cell) {
+                continue;
+            }
+
+            let tentative = cost + self.step_cost(&cell, &options);
+            for neighbor in self.search_neighbors(&cell, &options) {
+                if !self.is_passable(&neighbor, &options) {
+                    continue;
+                }
+
+                let tentative = tentative + self.extra_cost(&neighbor, &options);
+                if tentative < *distances.get(&neighbor).unwrap_or(&i32::MAX) {
+                    distances.insert(neighbor.clone(), tentative);
+                    predecessors.insert(neighbor.clone(), Some(cell.clone()));
+                    to_search.push(Frontier {
+                        cost: tentative,
+                        cell: neighbor,
+                    });
+                }
+            }
+        }
+
+        DistanceField {
+            distances,
+            predecessors,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battlesnake_game_types::wire_representation::{Game, Position};
+
+    fn game_with_hazards(hazards: &str) -> Game {
+        let json = format!(
+            r#"{{"game":{{"id":"","ruleset":{{"name":"standard","version":"v1.0.17"}},"timeout":500}},"turn":0,"board":{{"height":11,"width":11,"food":[],"hazards":[{}],"snakes":[{{"id":"you","name":"","health":90,"body":[{{"x":0,"y":0}},{{"x":1,"y":0}}],"latency":0,"head":{{"x":0,"y":0}},"length":2,"shout":""}}]}},"you":{{"id":"you","name":"","health":90,"body":[{{"x":0,"y":0}},{{"x":1,"y":0}}],"latency":0,"head":{{"x":0,"y":0}},"length":2,"shout":""}}}}"#,
+            hazards
+        );
+
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_single_source_matches_a_prime() {
+        let game = game_with_hazards("");
+        let head = Position { x: 0, y: 0 };
+        let field = game.distance_field(&[head], None);
+
+        for target in [Position { x: 10, y: 10 }, Position { x: 5, y: 0 }] {
+            assert_eq!(
+                field.distance_to(&target),
+                game.shortest_distance(&head, &[target], None)
+            );
+
+            let path = field.path_to(&target);
+            assert_eq!(path.len() as i32, field.distance_to(&target).unwrap() + 1);
+            assert_eq!(path.first(), Some(&head));
+            assert_eq!(path.last(), Some(&target));
+        }
+        // Our own neck gets a distance, but it's a wall so we have to go around it
+        assert_eq!(field.distance_to(&Position { x: 1, y: 0 }), Some(1));
+        assert_eq!(field.distance_to(&Position { x: 2, y: 0 }), Some(4));
+    }
+
+    #[test]
+    fn test_closest_source_splits_the_board() {
+        let game = game_with_hazards("");
+        let sources = [Position { x: 0, y: 10 }, Position { x: 10, y: 10 }];
+        let field = game.distance_field(&sources, None);
+
+        assert_eq!(
+            field.closest_source(&Position { x: 2, y: 8 }),
+            Some(sources[0])
+        );
+        assert_eq!(
+            field.closest_source(&Position { x: 9, y: 5 }),
+            Some(sources[1])
+        );
+        assert_eq!(
+            field.nearest(&[Position { x: 5, y: 5 }, Position { x: 9, y: 9 }]),
+            Some((Position { x: 9, y: 9 }, 2))
+        );
+    }
+
+    #[test]
+    fn test_hazards_cost_extra_to_leave() {
+        let game = game_with_hazards(r#"{"x":5,"y":6}"#);
+        let field = game.distance_field(
+            &[Position { x: 5, y: 5 }],
+            Some(APrimeOptions {
+                hazard_penalty: 14,
+                ..Default::default()
+            }),
+        );
+
+        assert_eq!(field.distance_to(&Position { x: 5, y: 6 }), Some(1));
+        // Going through the hazard would cost 16, so we go around it instead
+        assert_eq!(field.distance_to(&Position { x: 5, y: 7 }), Some(4));
+    }
+}
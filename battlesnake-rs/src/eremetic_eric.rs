@@ -4,11 +4,15 @@ use battlesnake_game_types::types::*;
 use itertools::Itertools;
 
 use crate::a_prime::{dist_between_new, APrimeCalculable, APrimeNextDirection, APrimeOptions};
+use crate::chambers::avoid_traps;
+use crate::distance_field::DistanceFieldCalculable;
+use crate::safety::{HazardQueryable, SafetyAnalysis};
 
 use super::*;
 
 pub struct EremeticEric<T> {
     pub game: T,
+    pub rules: Rules,
 }
 
 impl<T> BattlesnakeAI for EremeticEric<T>
@@ -24,7 +28,13 @@ where
         + HealthGettableGame
         + APrimeNextDirection
         + HeadGettableGame
-        + FoodGettableGame,
+        + FoodGettableGame
+        + SnakeIDGettableGame
+        + PositionGettableGame
+        + LengthGettableGame
+        + NeighborDeterminableGame
+        + SizeDeterminableGame
+        + HazardQueryable,
 {
     fn end(&self) {
         println!("Died at turn: {}", self.game.turn());
@@ -38,13 +48,16 @@ where
     fn make_move(&self) -> Result<MoveOutput, Box<dyn std::error::Error + Send + Sync>> {
         let you_id = self.game.you_id();
         let body = self.game.get_snake_body_vec(self.game.you_id());
+        // Looping back to our tail can still run into another snake, so every move we pick
+        // gets swapped for a safer one when there is one
+        let safety = SafetyAnalysis::for_you(&self.game, &self.rules);
         let modified_board = {
             let mut b = self.game.clone();
             let mut path_to_complete_circle =
                 self.game
                     .shortest_path(&body[0], &[body.last().unwrap().clone()], None);
             path_to_complete_circle.reverse();
-            for c in path_to_complete_circle.into_iter() {
+            for (c, _) in path_to_complete_circle.into_iter() {
                 if !body.contains(&c) {
                     b.push_tail(you_id, c);
                 }
@@ -57,9 +70,17 @@ where
         let food_options: Vec<_> = all_food
             .iter()
             .map(|food| {
+                // Walked distances from the food, so a body part on the other side of a wall
+                // isn't counted as close. Parts the food can't reach fall back to manhattan
+                let from_food = self.game.distance_field(&[food.clone()], None);
                 let body_options = body
                     .iter()
-                    .map(|body_part| (body_part, dist_between_new(&self.game, food, body_part)))
+                    .map(|body_part| {
+                        let cost = from_food
+                            .distance_to(body_part)
+                            .unwrap_or_else(|| dist_between_new(&self.game, food, body_part));
+                        (body_part, cost)
+                    })
                     .collect_vec();
                 let best = body_options.iter().cloned().min_by_key(|x| x.1).unwrap();
                 body_options
@@ -141,27 +162,36 @@ where
         if &you_head == closest_body_part && cant_survive_another_loop {
             let d = self
                 .game
-                .shortest_path_next_direction(&you_head, &[best_food.clone()], None)
+                .shortest_path_next_direction(
+                    &you_head,
+                    &[best_food.clone()],
+                    Some(APrimeOptions {
+                        receding_tails: true,
+                        ..Default::default()
+                    }),
+                )
                 .unwrap();
+            // Leaving the loop for food is only worth it if we aren't sealing ourselves in
+            let d = avoid_traps(&self.game, &you_head, d, body.len());
 
             return Ok(MoveOutput {
-                r#move: format!("{}", d),
+                r#move: format!("{}", safety.choose(d)),
                 shout: None,
             });
         }
 
         if self.game.turn() < 3 {
+            let d = self
+                .game
+                .shortest_path_next_direction(
+                    &you_head,
+                    &self.game.get_all_food_as_native_positions(),
+                    None,
+                )
+                .unwrap();
+
             return Ok(MoveOutput {
-                r#move: format!(
-                    "{}",
-                    self.game
-                        .shortest_path_next_direction(
-                            &you_head,
-                            &self.game.get_all_food_as_native_positions(),
-                            None
-                        )
-                        .unwrap()
-                ),
+                r#move: format!("{}", safety.choose(d)),
                 shout: None,
             });
         }
@@ -171,12 +201,15 @@ where
             .shortest_path_next_direction(
                 &you_head,
                 &[self.game.get_snake_body_vec(you_id).last().unwrap().clone()],
-                Some(APrimeOptions { food_penalty: 1 }),
+                Some(APrimeOptions {
+                    food_penalty: 1,
+                    ..Default::default()
+                }),
             )
             .unwrap();
 
         Ok(MoveOutput {
-            r#move: format!("{}", dir),
+            r#move: format!("{}", safety.choose(dir)),
             shout: None,
         })
     }
@@ -190,7 +223,14 @@ impl BattlesnakeFactory for EremeticEricFactory {
     }
 
     fn from_wire_game(&self, game: Game) -> BoxedSnake {
-        Box::new(EremeticEric { game })
+        self.from_wire_game_with_context(game, GameContext::default())
+    }
+
+    fn from_wire_game_with_context(&self, game: Game, context: GameContext) -> BoxedSnake {
+        Box::new(EremeticEric {
+            game,
+            rules: context.rules,
+        })
     }
     fn about(&self) -> AboutMe {
         AboutMe {
@@ -0,0 +1,176 @@
+use crate::a_prime::{APrimeCalculable, APrimeOptions};
+use crate::bitboard::bit_reachable_area;
+use crate::flood_fill::VoronoiCalculable;
+use crate::rules::Rules;
+
+use battlesnake_game_types::types::*;
+
+use std::fmt::Debug;
+
+/// Scores a board that isn't over yet from the perspective of a single snake
+///
+/// Won, lost and tied boards are handled by the search itself, so evaluators only ever need to
+/// compare boards where the game is still going. Bigger scores are better for the snake whose
+/// perspective we are scoring from
+pub trait Evaluator<T: SnakeIDGettableGame> {
+    type Score: Ord + Copy + Debug + Send + 'static;
+
+    fn score_for(&self, node: &T, snake_id: &T::SnakeIDType) -> Self::Score;
+
+    fn score(&self, node: &T) -> Self::Score
+    where
+        T: YouDeterminableGame,
+    {
+        self.score_for(node, node.you_id())
+    }
+}
+
+#[derive(Serialize, PartialEq, PartialOrd, Ord, Eq, Debug, Copy, Clone)]
+pub enum StandardScore {
+    /// difference_in_snake_length: u16, negative_distance_to_nearest_food: Option<i32>, health: u8
+    ShorterThanOpponent(i64, Option<i32>, i64),
+    /// negative_distance_to_opponent: Option<i64>, difference_in_snake_length: i64, health: u8
+    LongerThanOpponent(Option<i32>, i64, i64),
+}
+
+/// The original Devin evaluation
+///
+/// While we aren't the longest snake we head towards food, once we are we go after the
+/// closest opponent. Distances are measured with the hazard damage from the `rules`, so food
+/// behind the hazard looks as far away as it really is. Only one of them is needed per board,
+/// so it is an A* that stops at the first target instead of a distance field over the board
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StandardEvaluator {
+    pub rules: Rules,
+}
+
+impl<T> Evaluator<T> for StandardEvaluator
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + LengthGettableGame
+        + HealthGettableGame
+        + APrimeCalculable
+        + FoodGettableGame,
+{
+    type Score = StandardScore;
+
+    fn score_for(&self, node: &T, me_id: &T::SnakeIDType) -> StandardScore {
+        let opponents: Vec<T::SnakeIDType> = node
+            .get_snake_ids()
+            .into_iter()
+            .filter(|x| x != me_id)
+            .collect();
+
+        let opponent_heads: Vec<_> = opponents
+            .iter()
+            .map(|s| node.get_head_as_native_position(s))
+            .collect();
+        let my_head = node.get_head_as_native_position(me_id);
+
+        let my_length = node.get_length_i64(me_id);
+
+        let max_opponent_length = opponents
+            .iter()
+            .map(|o| node.get_length_i64(o))
+            .max()
+            .unwrap();
+        let length_difference = (my_length as i64) - (max_opponent_length as i64);
+        let my_health = node.get_health_i64(me_id);
+
+        let options = Some(APrimeOptions::for_rules(&self.rules));
+
+        let foods: Vec<_> = node.get_all_food_as_native_positions();
+        if max_opponent_length >= my_length || my_health < 20 {
+            let negative_closest_food_distance = node
+                .shortest_distance(&my_head, &foods, options)
+                .map(|x| -x);
+
+            return StandardScore::ShorterThanOpponent(
+                length_difference,
+                negative_closest_food_distance,
+                my_health.max(50),
+            );
+        }
+
+        let negative_distance_to_opponent = node
+            .shortest_distance(&my_head, &opponent_heads, options)
+            .map(|dist| -dist);
+
+        StandardScore::LongerThanOpponent(
+            negative_distance_to_opponent,
+            length_difference.max(4),
+            my_health.max(50),
+        )
+    }
+}
+
+/// Owned area is compared in buckets, so a cell or two of territory doesn't outweigh the
+/// standard evaluation
+const AREA_BUCKET_SIZE: i64 = 5;
+
+#[derive(Serialize, PartialEq, PartialOrd, Ord, Eq, Debug, Copy, Clone)]
+pub struct AreaControlScore {
+    /// `false` when the space we can reach isn't big enough to fit our whole body
+    pub has_room: bool,
+    /// Our owned area minus the biggest opponent's, in buckets of `AREA_BUCKET_SIZE`
+    pub owned_area_difference: i64,
+    pub standard: StandardScore,
+}
+
+/// Layers territory on top of the `StandardEvaluator`
+///
+/// Boards where we are trapped in a pocket smaller than our length are always worse than ones
+/// where we aren't, and after that we prefer boards where we control more of the board than
+/// our biggest opponent
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AreaControlEvaluator {
+    pub rules: Rules,
+}
+
+impl<T> Evaluator<T> for AreaControlEvaluator
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + LengthGettableGame
+        + HealthGettableGame
+        + APrimeCalculable
+        + FoodGettableGame
+        + NeighborDeterminableGame
+        + SnakeBodyGettableGame
+        + SizeDeterminableGame,
+{
+    type Score = AreaControlScore;
+
+    fn score_for(&self, node: &T, me_id: &T::SnakeIDType) -> AreaControlScore {
+        let territory = node.territory();
+
+        let my_area = territory.owned_area(me_id) as i64;
+        let max_opponent_area = node
+            .get_snake_ids()
+            .iter()
+            .filter(|s| *s != me_id)
+            .map(|s| territory.owned_area(s) as i64)
+            .max()
+            .unwrap_or(0);
+
+        // Everything we own we can also reach, so the flood fill is only needed when our
+        // territory alone doesn't fit us
+        let my_length = node.get_length_i64(me_id);
+        let has_room = my_area >= my_length || {
+            let reachable_area =
+                bit_reachable_area(node, me_id).unwrap_or_else(|| node.reachable_area(me_id));
+            reachable_area as i64 >= my_length
+        };
+
+        AreaControlScore {
+            has_room,
+            owned_area_difference: (my_area - max_opponent_area) / AREA_BUCKET_SIZE,
+            standard: StandardEvaluator { rules: self.rules }.score_for(node, me_id),
+        }
+    }
+}
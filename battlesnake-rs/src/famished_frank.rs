@@ -1,11 +1,14 @@
 use battlesnake_game_types::types::*;
 
-use crate::a_prime::APrimeNextDirection;
+use crate::a_prime::{APrimeNextDirection, APrimeOptions};
+use crate::chambers::avoid_traps;
+use crate::safety::{HazardQueryable, SafetyAnalysis};
 
 use super::*;
 
 pub struct FamishedFrank<T> {
     game: T,
+    rules: Rules,
 }
 
 impl<T> BattlesnakeAI for FamishedFrank<T>
@@ -17,7 +20,11 @@ where
         + APrimeNextDirection
         + RandomReasonableMovesGame
         + SnakeIDGettableGame
-        + YouDeterminableGame,
+        + YouDeterminableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + HazardQueryable,
 {
     fn make_move(&self) -> Result<MoveOutput, Box<dyn std::error::Error + Send + Sync>> {
         let target_length = self.game.get_height() * 2 + self.game.get_width();
@@ -51,7 +58,14 @@ where
             .collect();
 
         let head = you_body.first().unwrap();
-        let dir = self.game.shortest_path_next_direction(head, &targets, None);
+        let dir = self.game.shortest_path_next_direction(
+            head,
+            &targets,
+            Some(APrimeOptions {
+                receding_tails: true,
+                ..Default::default()
+            }),
+        );
 
         let dir = if let Some(s) = dir {
             s
@@ -69,6 +83,11 @@ where
                 })
         };
 
+        // Don't follow the path into a pocket we won't fit in
+        let dir = avoid_traps(&self.game, head, dir, you_body.len());
+        // A* doesn't know about the heads that might move next to our path
+        let dir = SafetyAnalysis::for_you(&self.game, &self.rules).choose(dir);
+
         Ok(MoveOutput {
             r#move: format!("{}", dir),
             shout: None,
@@ -84,7 +103,14 @@ impl BattlesnakeFactory for FamishedFrankFactory {
     }
 
     fn from_wire_game(&self, game: Game) -> BoxedSnake {
-        Box::new(FamishedFrank { game })
+        self.from_wire_game_with_context(game, GameContext::default())
+    }
+
+    fn from_wire_game_with_context(&self, game: Game, context: GameContext) -> BoxedSnake {
+        Box::new(FamishedFrank {
+            game,
+            rules: context.rules,
+        })
     }
     fn about(&self) -> AboutMe {
         AboutMe {
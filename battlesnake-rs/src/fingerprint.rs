@@ -0,0 +1,267 @@
+use battlesnake_game_types::types::Move;
+use battlesnake_game_types::wire_representation::Game;
+
+use crate::game_state::GameState;
+use crate::opponent_model::inferred_moves;
+use crate::rules::Rules;
+use crate::{BoxedFactory, GameContext};
+
+use std::collections::HashMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+/// A candidate has to have predicted this many of an opponent's moves before we trust it
+pub const MIN_PREDICTIONS: u32 = 8;
+
+/// How many of those predictions have to have been right
+pub const CONFIDENT_ACCURACY: f64 = 0.9;
+
+/// The most time a turn spends replaying candidates, out of what Devin has to search with
+pub const REPLAY_TIME_LIMIT: Duration = Duration::from_millis(30);
+
+/// Candidates that got fewer than this many right are clearly a different snake, so we stop
+/// spending time replaying them
+const RULED_OUT_BELOW: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PredictionRecord {
+    pub predicted: u32,
+    pub correct: u32,
+}
+
+impl PredictionRecord {
+    pub fn accuracy(&self) -> f64 {
+        if self.predicted == 0 {
+            0.0
+        } else {
+            self.correct as f64 / self.predicted as f64
+        }
+    }
+
+    pub fn is_confident(&self) -> bool {
+        self.predicted >= MIN_PREDICTIONS && self.accuracy() >= CONFIDENT_ACCURACY
+    }
+
+    fn is_ruled_out(&self) -> bool {
+        self.predicted >= MIN_PREDICTIONS && self.accuracy() < RULED_OUT_BELOW
+    }
+}
+
+/// The board as `snake_id` sees it, `None` once it is out of the game
+pub fn as_seen_by(game: &Game, snake_id: &str) -> Option<Game> {
+    let snake = game.board.snakes.iter().find(|s| s.id == snake_id)?.clone();
+    let mut game = game.clone();
+    game.you = snake;
+
+    Some(game)
+}
+
+/// The move the `factory` snake would make if it were playing `snake_id`
+///
+/// Every replay starts from an empty `GameState`, since we don't see the opponent's `/start`.
+/// Snakes that error or panic on the board don't predict anything
+pub fn predict(factory: &BoxedFactory, game: &Game, snake_id: &str, rules: Rules) -> Option<Move> {
+    let game = as_seen_by(game, snake_id)?;
+    let context = GameContext {
+        rules,
+        state: GameState::default(),
+    };
+
+    catch_unwind(AssertUnwindSafe(|| {
+        factory
+            .from_wire_game_with_context(game, context)
+            .make_move()
+            .ok()
+    }))
+    .ok()
+    .flatten()?
+    .direction()
+}
+
+/// How well each of our own snakes predicts each opponent in one game
+///
+/// Lots of snakes on the ladder are simple bots that play a lot like ours. Once one of ours
+/// has been predicting an opponent well for long enough, we can trust it to tell us what that
+/// opponent is about to do
+#[derive(Debug, Clone, Default)]
+pub struct Fingerprints {
+    /// Keyed by the opponent's id, then by the name of our snake that is predicting it
+    records: HashMap<String, HashMap<String, PredictionRecord>>,
+    /// The last board we saw, and what every candidate said each opponent would do on it
+    pending: Option<(Game, HashMap<String, Vec<(String, Option<Move>)>>)>,
+}
+
+impl Fingerprints {
+    /// Scores last turn's predictions against the moves the opponents actually made, then
+    /// replays this board through every `candidate` that is still in the running. Once the
+    /// replays have taken `REPLAY_TIME_LIMIT` the rest are skipped, and don't count for or
+    /// against anyone next turn
+    pub fn observe(&mut self, game: &Game, candidates: &[BoxedFactory], rules: Rules) {
+        self.observe_within(game, candidates, rules, REPLAY_TIME_LIMIT)
+    }
+
+    fn observe_within(
+        &mut self,
+        game: &Game,
+        candidates: &[BoxedFactory],
+        rules: Rules,
+        time_limit: Duration,
+    ) {
+        let started = Instant::now();
+
+        if let Some((before, predictions)) = self.pending.take() {
+            if before.game.id == game.game.id && before.turn + 1 == game.turn {
+                for (snake_id, actual) in inferred_moves(&before, game) {
+                    let records = self.records.entry(snake_id.clone()).or_default();
+
+                    for (name, predicted) in predictions.get(&snake_id).into_iter().flatten() {
+                        let record = records.entry(name.clone()).or_default();
+                        record.predicted += 1;
+                        if *predicted == Some(actual) {
+                            record.correct += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        let predictions = game
+            .board
+            .snakes
+            .iter()
+            .filter(|snake| snake.id != game.you.id)
+            .map(|snake| {
+                let moves = candidates
+                    .iter()
+                    .filter(|candidate| {
+                        !self
+                            .record(&snake.id, &candidate.name())
+                            .map_or(false, |r| r.is_ruled_out())
+                    })
+                    .take_while(|_| started.elapsed() < time_limit)
+                    .map(|candidate| (candidate.name(), predict(candidate, game, &snake.id, rules)))
+                    .collect();
+
+                (snake.id.clone(), moves)
+            })
+            .collect();
+
+        self.pending = Some((game.clone(), predictions));
+    }
+
+    pub fn record(&self, snake_id: &str, candidate: &str) -> Option<&PredictionRecord> {
+        self.records.get(snake_id)?.get(candidate)
+    }
+
+    /// The name of our snake that predicts `snake_id` best, if it does well enough to trust
+    pub fn confident_match(&self, snake_id: &str) -> Option<&str> {
+        self.records
+            .get(snake_id)?
+            .iter()
+            .filter(|(_, record)| record.is_confident())
+            .max_by(|(_, a), (_, b)| {
+                a.accuracy()
+                    .partial_cmp(&b.accuracy())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// What the confident match for `snake_id` says it will do on the last board we observed
+    pub fn predicted_move(&self, snake_id: &str) -> Option<Move> {
+        let name = self.confident_match(snake_id)?;
+        let (_, predictions) = self.pending.as_ref()?;
+
+        predictions
+            .get(snake_id)?
+            .iter()
+            .find(|(candidate, _)| candidate == name)?
+            .1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constant_carter::ConstantCarterFactory;
+    use crate::test_helpers::{moved, start_of_game};
+    use crate::BattlesnakeFactory;
+    use battlesnake_game_types::types::*;
+
+    #[test]
+    fn test_replays_boards_from_the_opponents_point_of_view() {
+        let game = start_of_game();
+        let seen = as_seen_by(&game, "#FF6444").unwrap();
+
+        assert_eq!(seen.you.id, "#FF6444");
+        assert_eq!(seen.board.food, game.board.food);
+        assert!(as_seen_by(&game, "missing").is_none());
+    }
+
+    #[test]
+    fn test_matches_an_opponent_that_plays_like_one_of_ours() {
+        let candidates: Vec<BoxedFactory> = vec![Box::new(ConstantCarterFactory {})];
+        let mut fingerprints = Fingerprints::default();
+
+        // Carter always goes right, and there is room for it to keep doing that
+        let mut game = start_of_game();
+        for _ in 0..MIN_PREDICTIONS {
+            fingerprints.observe(&game, &candidates, Rules::default());
+            assert_eq!(fingerprints.predicted_move("#FF6444"), None);
+
+            game = moved(&game, "#FF6444", Move::Right);
+        }
+        fingerprints.observe(&game, &candidates, Rules::default());
+
+        let record = fingerprints
+            .record("#FF6444", &ConstantCarterFactory {}.name())
+            .unwrap();
+        assert_eq!(record.predicted, MIN_PREDICTIONS);
+        assert_eq!(record.correct, MIN_PREDICTIONS);
+        assert_eq!(
+            fingerprints.confident_match("#FF6444"),
+            Some("constant-carter")
+        );
+        assert_eq!(fingerprints.predicted_move("#FF6444"), Some(Move::Right));
+
+        // The other opponent never moved, so we have nothing to go on
+        assert_eq!(fingerprints.predicted_move("#FF6c96"), None);
+    }
+
+    #[test]
+    fn test_stops_replaying_once_the_time_is_used_up() {
+        let candidates: Vec<BoxedFactory> = vec![
+            Box::new(ConstantCarterFactory {}),
+            Box::new(ConstantCarterFactory {}),
+        ];
+        let replayed = |fingerprints: &Fingerprints| -> usize {
+            fingerprints.pending.as_ref().map_or(0, |(_, predictions)| {
+                predictions.values().map(Vec::len).sum()
+            })
+        };
+        let game = start_of_game();
+
+        let mut fingerprints = Fingerprints::default();
+        fingerprints.observe_within(&game, &candidates, Rules::default(), Duration::from_secs(0));
+        assert_eq!(replayed(&fingerprints), 0);
+
+        // Skipped candidates don't count against anyone once we see the moves
+        fingerprints.observe_within(
+            &moved(&game, "#FF6444", Move::Right),
+            &candidates,
+            Rules::default(),
+            Duration::from_secs(0),
+        );
+        assert!(fingerprints.record("#FF6444", "constant-carter").is_none());
+
+        // Both opponents get every candidate when there is time for them
+        let mut fingerprints = Fingerprints::default();
+        fingerprints.observe_within(
+            &game,
+            &candidates,
+            Rules::default(),
+            Duration::from_secs(3600),
+        );
+        assert_eq!(replayed(&fingerprints), 4);
+    }
+}
@@ -0,0 +1,256 @@
+use battlesnake_game_types::types::*;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// Which snake gets to each cell of the board first
+pub struct Territory<T: SnakeIDGettableGame + PositionGettableGame> {
+    /// `None` means two snakes of the same length get there on the same turn
+    pub owners: FxHashMap<T::NativePositionType, Option<T::SnakeIDType>>,
+}
+
+impl<T: SnakeIDGettableGame + PositionGettableGame> Territory<T> {
+    /// Number of cells this snake reaches before anyone else, including its own head
+    pub fn owned_area(&self, snake_id: &T::SnakeIDType) -> usize {
+        self.owners
+            .values()
+            .filter(|owner| owner.as_ref() == Some(snake_id))
+            .count()
+    }
+}
+
+pub trait VoronoiCalculable: SnakeIDGettableGame + PositionGettableGame {
+    /// A simultaneous breadth first search from every snakes head
+    ///
+    /// Each cell belongs to the snake that reaches it first. When two snakes get to a cell on
+    /// the same turn the longer one wins it, like it would in a head to head collision, and if
+    /// they are the same length nobody does. Body segments are walls until the turn their
+    /// snake's tail has moved off of them
+    fn territory(&self) -> Territory<Self>
+    where
+        Self: Sized;
+
+    /// How many cells this snake could reach if it had the board to itself, respecting the same
+    /// receding tails as `territory`
+    fn reachable_area(&self, snake_id: &Self::SnakeIDType) -> usize;
+}
+
+/// The number of turns until each body segment on the board has moved out of the way
+pub(crate) fn turns_until_vacated<T>(game: &T) -> FxHashMap<T::NativePositionType, usize>
+where
+    T: SnakeIDGettableGame + SnakeBodyGettableGame + HealthGettableGame,
+{
+    let mut vacated: FxHashMap<T::NativePositionType, usize> = FxHashMap::default();
+
+    for snake_id in game.get_snake_ids().iter().filter(|s| game.is_alive(s)) {
+        let body = game.get_snake_body_vec(snake_id);
+        let length = body.len();
+
+        // A snake that just ate has its tail stacked, so the earlier index of the stacked
+        // segment gives it the extra turn it needs
+        for (i, segment) in body.into_iter().enumerate() {
+            let turns = length - i;
+            let entry = vacated.entry(segment).or_insert(turns);
+            *entry = (*entry).max(turns);
+        }
+    }
+
+    vacated
+}
+
+impl<T> VoronoiCalculable for T
+where
+    T: SnakeIDGettableGame
+        + PositionGettableGame
+        + NeighborDeterminableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + SnakeBodyGettableGame,
+{
+    fn territory(&self) -> Territory<Self> {
+        let vacated = turns_until_vacated(self);
+
+        let snake_ids: Vec<_> = self
+            .get_snake_ids()
+            .into_iter()
+            .filter(|s| self.is_alive(s))
+            .collect();
+        let lengths: Vec<_> = snake_ids.iter().map(|s| self.get_length_i64(s)).collect();
+
+        let mut owners: FxHashMap<T::NativePositionType, Option<usize>> = FxHashMap::default();
+        let mut frontier: Vec<(T::NativePositionType, usize)> = vec![];
+
+        for (i, snake_id) in snake_ids.iter().enumerate() {
+            let head = self.get_head_as_native_position(snake_id);
+            owners.insert(head.clone(), Some(i));
+            frontier.push((head, i));
+        }
+
+        let mut turn = 0;
+        while !frontier.is_empty() {
+            turn += 1;
+
+            // The longest length to reach each cell this turn, and who it belongs to. `None`
+            // once snakes of that length tie, though a longer one can still take the cell
+            let mut claimed: FxHashMap<T::NativePositionType, (i64, Option<usize>)> =
+                FxHashMap::default();
+
+            for (cell, owner) in frontier.iter() {
+                for neighbor in self.neighbors(cell) {
+                    if owners.contains_key(&neighbor) {
+                        continue;
+                    }
+                    if vacated.get(&neighbor).map_or(false, |v| *v > turn) {
+                        continue;
+                    }
+
+                    let (owner, length) = (*owner, lengths[*owner]);
+                    claimed
+                        .entry(neighbor)
+                        .and_modify(|(best, current)| {
+                            if length > *best {
+                                *best = length;
+                                *current = Some(owner);
+                            } else if length == *best && *current != Some(owner) {
+                                *current = None;
+                            }
+                        })
+                        .or_insert((length, Some(owner)));
+                }
+            }
+
+            frontier = claimed
+                .iter()
+                .filter_map(|(cell, (_, owner))| owner.map(|o| (cell.clone(), o)))
+                .collect();
+            owners.extend(claimed.into_iter().map(|(cell, (_, owner))| (cell, owner)));
+        }
+
+        Territory {
+            owners: owners
+                .into_iter()
+                .map(|(cell, owner)| (cell, owner.map(|i| snake_ids[i].clone())))
+                .collect(),
+        }
+    }
+
+    fn reachable_area(&self, snake_id: &Self::SnakeIDType) -> usize {
+        if !self.is_alive(snake_id) {
+            return 0;
+        }
+
+        let vacated = turns_until_vacated(self);
+        let head = self.get_head_as_native_position(snake_id);
+
+        let mut seen: FxHashSet<T::NativePositionType> = FxHashSet::default();
+        seen.insert(head.clone());
+        let mut frontier = vec![head];
+
+        let mut turn = 0;
+        while !frontier.is_empty() {
+            turn += 1;
+
+            let mut next = vec![];
+            for cell in frontier.iter() {
+                for neighbor in self.neighbors(cell) {
+                    if seen.contains(&neighbor) {
+                        continue;
+                    }
+                    if vacated.get(&neighbor).map_or(false, |v| *v > turn) {
+                        continue;
+                    }
+
+                    seen.insert(neighbor.clone());
+                    next.push(neighbor);
+                }
+            }
+
+            frontier = next;
+        }
+
+        seen.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battlesnake_game_types::wire_representation::{Game, Position};
+
+    #[test]
+    fn test_single_snake_owns_the_board() {
+        let json = b"{\"game\":{\"id\":\"\",\"ruleset\":{\"name\":\"standard\",\"version\":\"v1.0.17\"},\"timeout\":500},\"turn\":60,\"board\":{\"height\":11,\"width\":11,\"snakes\":[{\"id\":\"\",\"name\":\"\",\"latency\":\"100\",\"health\":86,\"body\":[{\"x\":10,\"y\":4}],\"head\":{\"x\":10,\"y\":4},\"length\":1,\"shout\":\"\"}],\"food\":[],\"hazards\":[]},\"you\":{\"id\":\"\",\"name\":\"\",\"latency\":\"100\",\"health\":86,\"body\":[{\"x\":10,\"y\":4}],\"head\":{\"x\":10,\"y\":4},\"length\":1,\"shout\":\"\"}}";
+        let game: Game = serde_json::from_slice(json).unwrap();
+
+        assert_eq!(game.territory().owned_area(&"".to_owned()), 121);
+        assert_eq!(game.reachable_area(&"".to_owned()), 121);
+    }
+
+    #[test]
+    fn test_start_of_game_splits_the_board() {
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let territory = game.territory();
+
+        let total: usize = game
+            .get_snake_ids()
+            .iter()
+            .map(|s| territory.owned_area(s))
+            .sum();
+        let contested = territory.owners.values().filter(|o| o.is_none()).count();
+
+        assert_eq!(total + contested, 121);
+        assert!(territory.owned_area(&"you".to_owned()) > 3);
+    }
+
+    #[test]
+    fn test_receding_tail_opens_up_a_pocket() {
+        let board_json = r#"{"game":{"id":"","ruleset":{"name":"standard","version":"v1.0.17"},"timeout":500},"turn":10,"board":{"height":11,"width":11,"food":[],"hazards":[],"snakes":[{"id":"you","name":"","health":90,"body":[{"x":0,"y":0},{"x":1,"y":0},{"x":1,"y":1},{"x":1,"y":2},{"x":0,"y":2}],"latency":0,"head":{"x":0,"y":0},"length":5,"shout":""}]},"you":{"id":"you","name":"","health":90,"body":[{"x":0,"y":0},{"x":1,"y":0},{"x":1,"y":1},{"x":1,"y":2},{"x":0,"y":2}],"latency":0,"head":{"x":0,"y":0},"length":5,"shout":""}}"#;
+        let game: Game = serde_json::from_str(board_json).unwrap();
+
+        // Only (0, 1) is free right away, but the tail at (0, 2) has moved off by the time
+        // we get there which opens up the rest of the board
+        assert_eq!(game.reachable_area(&"you".to_owned()), 121);
+
+        let owners = game.territory().owners;
+        assert_eq!(
+            owners.get(&Position { x: 0, y: 1 }),
+            Some(&Some("you".to_owned()))
+        );
+        assert_eq!(
+            owners.get(&Position { x: 0, y: 3 }),
+            Some(&Some("you".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_longest_snake_wins_a_cell_others_tied_on() {
+        let snake = |id: &str, body: &str| {
+            format!(
+                r#"{{"id":"{}","name":"","health":90,"body":[{}],"latency":0,"head":{},"length":{},"shout":""}}"#,
+                id,
+                body,
+                body.split('}').next().unwrap().to_owned() + "}",
+                body.matches('{').count()
+            )
+        };
+        // The two equal snakes are listed first so they tie on (5, 5) before the longer snake
+        // gets there on the same turn
+        let a = snake("a", r#"{"x":4,"y":5},{"x":3,"y":5},{"x":2,"y":5}"#);
+        let b = snake("b", r#"{"x":6,"y":5},{"x":7,"y":5},{"x":8,"y":5}"#);
+        let long = snake(
+            "long",
+            r#"{"x":5,"y":6},{"x":5,"y":7},{"x":5,"y":8},{"x":5,"y":9}"#,
+        );
+        let game: Game = serde_json::from_str(&format!(
+            r#"{{"game":{{"id":"","ruleset":{{"name":"standard","version":"v1.0.17"}},"timeout":500}},"turn":10,"board":{{"height":11,"width":11,"food":[],"hazards":[],"snakes":[{},{},{}]}},"you":{}}}"#,
+            a, b, long, a
+        ))
+        .unwrap();
+
+        assert_eq!(
+            game.territory().owners.get(&Position { x: 5, y: 5 }),
+            Some(&Some("long".to_owned()))
+        );
+    }
+}
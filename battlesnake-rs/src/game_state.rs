@@ -0,0 +1,173 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Games we haven't heard about in this long are assumed to be over, even if we never got an
+/// `/end` for them
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+type Slot = Arc<Mutex<HashMap<TypeId, Box<dyn Any + Send>>>>;
+
+/// Poisoning only means some other request panicked while it held the lock, the state itself
+/// is still whatever that snake last saved
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Whatever one snake wants to remember about one game between requests
+///
+/// Values are looked up by their type, so a snake can keep one of each type it cares about
+#[derive(Clone, Default)]
+pub struct GameState {
+    slot: Slot,
+}
+
+impl GameState {
+    pub fn get<S: Any + Clone>(&self) -> Option<S> {
+        lock(&self.slot)
+            .get(&TypeId::of::<S>())
+            .and_then(|saved| saved.downcast_ref::<S>())
+            .cloned()
+    }
+
+    pub fn set<S: Any + Send>(&self, value: S) {
+        lock(&self.slot).insert(TypeId::of::<S>(), Box::new(value));
+    }
+
+    /// Removes the saved `S` and hands it back
+    pub fn take<S: Any + Send>(&self) -> Option<S> {
+        lock(&self.slot)
+            .remove(&TypeId::of::<S>())
+            .and_then(|saved| saved.downcast::<S>().ok())
+            .map(|saved| *saved)
+    }
+
+    pub fn clear(&self) {
+        lock(&self.slot).clear();
+    }
+}
+
+impl fmt::Debug for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GameState")
+            .field("saved", &lock(&self.slot).len())
+            .finish()
+    }
+}
+
+struct StoredGame {
+    state: GameState,
+    last_used: Instant,
+}
+
+/// The `GameState` for every game a server is in the middle of, keyed by the game's id and the
+/// name of the snake playing it
+///
+/// Cloning a store gives another handle to the same games
+#[derive(Clone)]
+pub struct GameStateStore {
+    games: Arc<Mutex<HashMap<(String, String), StoredGame>>>,
+    idle_timeout: Duration,
+}
+
+impl Default for GameStateStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_IDLE_TIMEOUT)
+    }
+}
+
+impl GameStateStore {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            games: Default::default(),
+            idle_timeout,
+        }
+    }
+
+    /// The state for this game, starting a fresh one if we haven't seen it before
+    ///
+    /// This is also when games that have sat idle for too long get dropped
+    pub fn state_for(&self, game_id: &str, snake_name: &str) -> GameState {
+        let now = Instant::now();
+        let mut games = lock(&self.games);
+
+        let idle_timeout = self.idle_timeout;
+        games.retain(|_, game| now.duration_since(game.last_used) < idle_timeout);
+
+        let game = games
+            .entry((game_id.to_owned(), snake_name.to_owned()))
+            .or_insert_with(|| StoredGame {
+                state: GameState::default(),
+                last_used: now,
+            });
+        game.last_used = now;
+
+        game.state.clone()
+    }
+
+    /// Forgets everything about a game once it's over
+    pub fn end_game(&self, game_id: &str, snake_name: &str) {
+        lock(&self.games).remove(&(game_id.to_owned(), snake_name.to_owned()));
+    }
+
+    /// How many games we are holding on to
+    pub fn len(&self) -> usize {
+        lock(&self.games).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_is_shared_within_a_game() {
+        let store = GameStateStore::default();
+
+        store.state_for("game", "george").set(vec![1, 2, 3]);
+
+        assert_eq!(
+            store.state_for("game", "george").get::<Vec<i32>>(),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(store.state_for("game", "devin").get::<Vec<i32>>(), None);
+        assert_eq!(store.state_for("other", "george").get::<Vec<i32>>(), None);
+        assert_eq!(store.len(), 3);
+    }
+
+    #[test]
+    fn test_each_type_gets_its_own_value() {
+        let state = GameState::default();
+        state.set(7_u8);
+        state.set("seven".to_owned());
+
+        assert_eq!(state.take::<i32>(), None);
+        assert_eq!(state.take::<u8>(), Some(7));
+        assert_eq!(state.get::<u8>(), None);
+        assert_eq!(state.get::<String>(), Some("seven".to_owned()));
+    }
+
+    #[test]
+    fn test_games_expire_on_end_and_when_idle() {
+        let store = GameStateStore::default();
+        store.state_for("game", "george").set(1);
+        store.end_game("game", "george");
+
+        assert_eq!(store.state_for("game", "george").get::<i32>(), None);
+
+        let store = GameStateStore::new(Duration::from_secs(0));
+        store.state_for("idle", "george").set(1);
+        store.state_for("busy", "george");
+
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.state_for("idle", "george").get::<i32>(), None);
+    }
+}
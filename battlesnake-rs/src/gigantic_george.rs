@@ -1,37 +1,50 @@
 use battlesnake_game_types::types::*;
 
 use crate::eremetic_eric::EremeticEric;
+use crate::game_state::GameState;
+use crate::hamiltonian::{self, HamiltonianCycle, BACKTRACKING_BUDGET};
+use crate::safety::{tail_moves, HazardQueryable, SafetyAnalysis};
 
 use super::*;
 
 pub struct GiganticGeorge<T> {
     game: T,
+    rules: Rules,
+    state: GameState,
 }
 
-fn path_to_full_board<T: PositionGettableGame + SizeDeterminableGame + NeighborDeterminableGame>(
-    reversed_body: &[T::NativePositionType],
-    game: &T,
-) -> Option<Vec<(Move, T::NativePositionType)>> {
-    let max_size = game.get_width() * game.get_height();
-    if reversed_body.len() == max_size as usize {
-        return Some(vec![]);
-    }
+/// What George remembers between turns of a game
+#[derive(Default)]
+struct Memory {
+    /// Built in `/start`, or on our first move if we missed it
+    cycle: Option<HamiltonianCycle>,
+    /// The rest of a full board path, with the next move last
+    planned_path: Vec<Move>,
+}
 
-    for (dir, coor) in game
-        .possible_moves(reversed_body.last().unwrap())
-        .iter()
-        .filter(|(_, c)| !reversed_body.contains(c))
-    {
-        let mut new_body = reversed_body.to_vec();
-        new_body.push(coor.clone());
-
-        if let Some(mut path) = path_to_full_board(&new_body, game) {
-            path.push((*dir, coor.clone()));
-            return Some(path);
-        }
-    }
+/// Once there are this few empty cells left we stop following the cycle and search for a path
+/// that fills the rest of the board
+const FULL_BOARD_SEARCH_CELLS: usize = 20;
+
+fn move_between(from: &Position, to: &Position) -> Move {
+    Move::from_vector(to.sub_vec(from.to_vector()).to_vector())
+}
 
-    None
+/// A path from our head that fills every cell our body isn't in, as the moves to get there
+///
+/// Our tail moves out of the way as we go, so we need to fill its cell too
+fn path_to_full_board(body: &[Position], width: i32, height: i32) -> Option<Vec<Move>> {
+    let head = *body.first()?;
+    let blocked = &body[1..body.len() - 1];
+    let path = hamiltonian::full_board_path(width, height, blocked, head, BACKTRACKING_BUDGET)?;
+
+    Some(
+        std::iter::once(&head)
+            .chain(path.iter())
+            .zip(path.iter())
+            .map(|(from, to)| move_between(from, to))
+            .collect(),
+    )
 }
 
 pub trait FullBoardDeterminable {
@@ -57,10 +70,82 @@ impl FullBoardDeterminable for Game {
     }
 }
 
+impl<T> GiganticGeorge<T>
+where
+    T: YouDeterminableGame
+        + NeighborDeterminableGame
+        + PositionGettableGame
+        + SnakeBodyGettableGame
+        + HeadGettableGame
+        + FoodGettableGame,
+{
+    /// Follows a full board path if we have one, otherwise the cycle. `None` when neither has
+    /// anywhere for us to go, or when we aren't alone on the board
+    fn choose_move(&self, memory: &mut Memory, width: i32, height: i32) -> Option<Move> {
+        // The cycle and our paths only keep us out of our own way, other snakes can be
+        // anywhere on them
+        if self.game.get_snake_ids().len() > 1 {
+            memory.planned_path.clear();
+            return None;
+        }
+
+        if let Some(next) = memory.planned_path.pop() {
+            if self.is_still_free(&next) {
+                return Some(next);
+            }
+            memory.planned_path.clear();
+        }
+
+        let you_id = self.game.you_id();
+        let body: Vec<Position> = self
+            .game
+            .get_snake_body_vec(you_id)
+            .iter()
+            .map(|c| self.game.position_from_native(c.clone()))
+            .collect();
+        let empty_cells = ((width * height) as usize).saturating_sub(body.len());
+
+        if body.len() > 1 && empty_cells <= FULL_BOARD_SEARCH_CELLS {
+            if let Some(mut path) = path_to_full_board(&body, width, height) {
+                path.reverse();
+                if let Some(next) = path.pop() {
+                    memory.planned_path = path;
+                    return Some(next);
+                }
+            }
+        }
+
+        let food: Vec<Position> = self
+            .game
+            .get_all_food_as_native_positions()
+            .into_iter()
+            .map(|c| self.game.position_from_native(c))
+            .collect();
+
+        memory
+            .cycle
+            .as_ref()?
+            .next_step(&body, &food)
+            .map(|next| move_between(&body[0], &next))
+    }
+
+    /// Whether a saved move still lands somewhere we can go. Other snakes can get in the way of
+    /// a path we planned on an earlier turn
+    fn is_still_free(&self, m: &Move) -> bool {
+        let you_id = self.game.you_id();
+        let head = self.game.get_head_as_native_position(you_id);
+        let body = self.game.get_snake_body_vec(you_id);
+        let tail = body.last().filter(|_| tail_moves(&body));
+
+        self.game.possible_moves(&head).into_iter().any(|(dir, c)| {
+            dir == *m && (tail == Some(&c) || !self.game.position_is_snake_body(c.clone()))
+        })
+    }
+}
+
 impl<T> BattlesnakeAI for GiganticGeorge<T>
 where
     T: FullBoardDeterminable
-        + ShoutGettableGame
         + YouDeterminableGame
         + NeighborDeterminableGame
         + SizeDeterminableGame
@@ -72,60 +157,36 @@ where
         + battlesnake_game_types::types::HealthGettableGame
         + a_prime::APrimeNextDirection
         + TurnDeterminableGame
+        + LengthGettableGame
+        + HazardQueryable
         + std::clone::Clone,
 {
     fn make_move(&self) -> Result<MoveOutput, Box<dyn std::error::Error + Send + Sync>> {
-        let you_id = self.game.you_id();
-
-        if let Some(s) = self.game.get_shout(you_id) {
-            if s.starts_with("PATH:") {
-                let path = s.split("PATH:").nth(1).unwrap();
-
-                let next_char = path.to_lowercase().chars().last().unwrap();
-                let dir = match next_char {
-                    'l' => Some(Move::Left),
-                    'r' => Some(Move::Right),
-                    'u' => Some(Move::Up),
-                    'd' => Some(Move::Down),
-                    _ => None,
-                };
-
-                if let Some(d) = dir {
-                    return Ok(MoveOutput {
-                        r#move: format!("{}", d),
-                        shout: Some(format!("PATH:{}", &path[..path.len() - 1])),
-                    });
-                }
-            }
+        let (width, height) = (self.game.get_width() as i32, self.game.get_height() as i32);
+        let mut memory: Memory = self.state.take().unwrap_or_default();
+        if memory.cycle.is_none() {
+            memory.cycle = HamiltonianCycle::for_board(width, height);
         }
 
-        if !self.game.contains_empty_squares() {
-            println!("Ok now can we complete the board?");
-
-            let reversed_body = {
-                let mut x = self.game.get_snake_body_vec(you_id);
-                x.pop(); // Remove my current tail cause I will need to fill that space too
-                x.reverse();
-                x
-            };
-
-            if let Some(mut path) = path_to_full_board(&reversed_body, &self.game) {
-                let new = path.pop();
-                let path_string: String = path
-                    .iter()
-                    .map(|(d, _)| format!("{}", d).chars().next().unwrap())
-                    .collect();
-                return Ok(MoveOutput {
-                    r#move: format!("{}", new.unwrap().0),
-                    shout: Some("PATH:".to_string() + &path_string),
-                });
-            } else {
-                println!("Nah lets keep looping");
+        let next = self.choose_move(&mut memory, width, height).map(|next| {
+            let safe = SafetyAnalysis::for_you(&self.game, &self.rules).choose(next);
+            if safe != next {
+                memory.planned_path.clear();
             }
+            safe
+        });
+        self.state.set(memory);
+
+        if let Some(next) = next {
+            return Ok(MoveOutput {
+                r#move: format!("{}", next),
+                shout: None,
+            });
         }
 
         let eric = EremeticEric {
             game: self.game.clone(),
+            rules: self.rules,
         };
         eric.make_move()
     }
@@ -135,7 +196,22 @@ pub struct GiganticGeorgeFactory {}
 
 impl BattlesnakeFactory for GiganticGeorgeFactory {
     fn from_wire_game(&self, game: Game) -> BoxedSnake {
-        Box::new(GiganticGeorge { game })
+        self.from_wire_game_with_context(game, GameContext::default())
+    }
+
+    fn from_wire_game_with_context(&self, game: Game, context: GameContext) -> BoxedSnake {
+        Box::new(GiganticGeorge {
+            game,
+            rules: context.rules,
+            state: context.state,
+        })
+    }
+
+    fn start(&self, game: &Game, context: &GameContext) {
+        context.state.set(Memory {
+            cycle: HamiltonianCycle::for_board(game.get_width() as i32, game.get_height() as i32),
+            planned_path: vec![],
+        });
     }
 
     fn name(&self) -> String {
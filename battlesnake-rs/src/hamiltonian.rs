@@ -0,0 +1,555 @@
+use battlesnake_game_types::wire_representation::Position;
+
+use crate::safety::tail_moves;
+
+/// How many dead ends, parity failures and disconnected boards the full board search is allowed
+/// to back out of before giving up
+pub const BACKTRACKING_BUDGET: usize = 200_000;
+
+fn neighbors(pos: &Position, width: i32, height: i32) -> impl Iterator<Item = Position> {
+    let pos = *pos;
+
+    [(0, 1), (0, -1), (-1, 0), (1, 0)]
+        .iter()
+        .map(move |(dx, dy)| Position {
+            x: pos.x + dx,
+            y: pos.y + dy,
+        })
+        .filter(move |p| p.x >= 0 && p.y >= 0 && p.x < width && p.y < height)
+}
+
+fn are_adjacent(a: &Position, b: &Position) -> bool {
+    (a.x - b.x).abs() + (a.y - b.y).abs() == 1
+}
+
+/// A cycle that zig zags across the rows and comes back down column zero. Needs an even
+/// number of rows
+fn comb_cycle(width: i32, height: i32) -> Vec<Position> {
+    let mut cycle = vec![Position { x: 0, y: 0 }];
+
+    for y in 0..height {
+        if y % 2 == 0 {
+            cycle.extend((1..width).map(|x| Position { x, y }));
+        } else {
+            cycle.extend((1..width).rev().map(|x| Position { x, y }));
+        }
+    }
+    cycle.extend((1..height).rev().map(|y| Position { x: 0, y }));
+
+    cycle
+}
+
+/// A fixed loop through the board that a snake can follow forever without running into itself
+///
+/// Boards with an even number of cells get a cycle through every cell. Boards where both sides
+/// are odd can't have one, so the cycle skips the top right corner and that last cell is left
+/// for the full board search at the end of the game
+#[derive(Debug, Clone)]
+pub struct HamiltonianCycle {
+    width: i32,
+    height: i32,
+    order: Vec<Position>,
+    index: Vec<Option<usize>>,
+    /// The cell the cycle doesn't go through, if there is one
+    pub skipped: Option<Position>,
+}
+
+impl HamiltonianCycle {
+    /// `None` when the board is too thin to loop around
+    pub fn for_board(width: i32, height: i32) -> Option<Self> {
+        if width < 2 || height < 2 || (width * height % 2 == 1 && (width < 3 || height < 3)) {
+            return None;
+        }
+
+        let (order, skipped) = if height % 2 == 0 {
+            (comb_cycle(width, height), None)
+        } else if width % 2 == 0 {
+            let transposed = comb_cycle(height, width)
+                .into_iter()
+                .map(|p| Position { x: p.y, y: p.x })
+                .collect();
+            (transposed, None)
+        } else {
+            // Loop around everything below the top row, and then pick the top row up two cells
+            // at a time by replacing the step left from `a + 1` to `a` along the row below it
+            // with a detour up and over
+            let top = height - 1;
+            let mut order = vec![];
+            for p in comb_cycle(width, height - 1) {
+                if let Some(previous) = order.last().copied() {
+                    let previous: Position = previous;
+                    if previous.y == top - 1
+                        && p.y == top - 1
+                        && previous.x == p.x + 1
+                        && p.x % 2 == 0
+                    {
+                        order.push(Position {
+                            x: previous.x,
+                            y: top,
+                        });
+                        order.push(Position { x: p.x, y: top });
+                    }
+                }
+                order.push(p);
+            }
+
+            (
+                order,
+                Some(Position {
+                    x: width - 1,
+                    y: top,
+                }),
+            )
+        };
+
+        let mut index = vec![None; (width * height) as usize];
+        for (i, p) in order.iter().enumerate() {
+            index[(p.y * width + p.x) as usize] = Some(i);
+        }
+
+        Some(Self {
+            width,
+            height,
+            order,
+            index,
+            skipped,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// The cells in the order the cycle visits them, the last one leads back to the first
+    pub fn cells(&self) -> &[Position] {
+        &self.order
+    }
+
+    pub fn index_of(&self, pos: &Position) -> Option<usize> {
+        if pos.x < 0 || pos.y < 0 || pos.x >= self.width || pos.y >= self.height {
+            return None;
+        }
+
+        self.index[(pos.y * self.width + pos.x) as usize]
+    }
+
+    /// Steps along the cycle to get from `from` to `to`
+    fn distance(&self, from: usize, to: usize) -> usize {
+        (to + self.len() - from) % self.len()
+    }
+
+    /// Where to go next for a snake that has been following the cycle, `body` is head first
+    ///
+    /// Following the cycle keeps the whole body between the tail and the head, going around
+    /// the cycle in order, which leaves everything from the head up to the tail free. So we can
+    /// also cut across to any cell in that stretch, and we take the biggest shortcut that
+    /// doesn't go past the closest food. `None` when the head or tail isn't on the cycle
+    pub fn next_step(&self, body: &[Position], food: &[Position]) -> Option<Position> {
+        let head_pos = *body.first()?;
+        if Some(head_pos) == self.skipped {
+            // We only come in here on the way to the other side, which is still free
+            return neighbors(&head_pos, self.width, self.height).find(|n| !body.contains(n));
+        }
+
+        let head = self.index_of(&head_pos)?;
+        // When our tail is on the skipped cell the next piece of us is as good a place as any
+        // to measure from
+        let tail = body.iter().rev().find_map(|p| self.index_of(p))?;
+
+        // A freshly spawned snake is stacked on one cell, and has the whole cycle to itself
+        let room = if head == tail {
+            self.len()
+        } else {
+            self.distance(head, tail)
+        };
+
+        if let Some(skipped) = self.skipped_food_step(body, food, head, room) {
+            return Some(skipped);
+        }
+
+        let (in_the_way, reachable) = if tail_moves(body) {
+            (&body[..body.len() - 1], room)
+        } else {
+            (body, room - 1)
+        };
+
+        // Eating stacks our tail for a turn, so we need a spare cell in front of us after
+        let safe_to_eat = |d: usize| d + 2 <= room;
+        let closest_food = food
+            .iter()
+            .filter_map(|f| self.index_of(f))
+            .map(|f| self.distance(head, f))
+            .filter(|d| *d > 0 && *d <= reachable && safe_to_eat(*d))
+            .min();
+        // Without food in front of us we just follow the cycle. Once we fill half the board
+        // the cells a shortcut skips are too hard to get back to, so we stop taking them
+        let furthest_allowed = match closest_food {
+            Some(d) if body.len() * 2 <= self.len() => d,
+            _ => 1,
+        };
+
+        let options: Vec<(Position, usize)> = neighbors(&body[0], self.width, self.height)
+            .filter(|n| !in_the_way.contains(n))
+            .filter_map(|n| self.index_of(&n).map(|i| (n, self.distance(head, i))))
+            .filter(|(_, d)| *d > 0 && *d <= reachable)
+            .collect();
+
+        // When the cycle runs us right into food we can't safely eat, we still have to go
+        options
+            .iter()
+            .filter(|(n, d)| *d <= furthest_allowed && (!food.contains(n) || safe_to_eat(*d)))
+            .max_by_key(|(_, d)| *d)
+            .or_else(|| options.iter().min_by_key(|(_, d)| *d))
+            .map(|(n, _)| *n)
+    }
+
+    /// Steps onto the cell the cycle skips when there is food on it, as long as we can get
+    /// back onto the cycle on the other side of it. Eating there fills the board when it's the
+    /// only cell left, otherwise the tail stays put while we go through so the way out has to
+    /// be in front of us
+    fn skipped_food_step(
+        &self,
+        body: &[Position],
+        food: &[Position],
+        head: usize,
+        room: usize,
+    ) -> Option<Position> {
+        let skipped = self.skipped?;
+        if !food.contains(&skipped) || !are_adjacent(&body[0], &skipped) {
+            return None;
+        }
+
+        let fills_the_board = body.len() + 1 == (self.width * self.height) as usize;
+        let way_out = neighbors(&skipped, self.width, self.height)
+            .filter(|n| !body.contains(n))
+            .filter_map(|n| self.index_of(&n))
+            .any(|i| {
+                let d = self.distance(head, i);
+                d > 0 && d < room
+            });
+
+        if fills_the_board || way_out {
+            Some(skipped)
+        } else {
+            None
+        }
+    }
+}
+
+/// A board for the full board search, with every cell either taken or still to be filled
+struct FillSearch {
+    width: i32,
+    height: i32,
+    taken: Vec<bool>,
+    remaining: usize,
+    budget: usize,
+}
+
+impl FillSearch {
+    fn cell(&self, pos: &Position) -> usize {
+        (pos.y * self.width + pos.x) as usize
+    }
+
+    fn free_neighbors(&self, pos: &Position) -> impl Iterator<Item = Position> + '_ {
+        neighbors(pos, self.width, self.height).filter(move |n| !self.taken[self.cell(n)])
+    }
+
+    /// Whether the remaining cells could still be filled by a path starting next to `head`
+    ///
+    /// The board is a checkerboard and every step changes color, so the path has to start on
+    /// the other color from the head and use up the colors evenly. Every remaining cell must be
+    /// reachable, and at most one of them can be a dead end since only the end of the path can
+    /// be
+    fn could_finish_from(&self, head: &Position) -> bool {
+        let head_color = (head.x + head.y) % 2;
+        let mut counts = [0, 0];
+        let mut dead_ends = 0;
+        let mut start = None;
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let pos = Position { x, y };
+                if self.taken[self.cell(&pos)] {
+                    continue;
+                }
+
+                counts[((x + y) % 2) as usize] += 1;
+
+                let next_to_head = are_adjacent(&pos, head);
+                if next_to_head {
+                    start = Some(pos);
+                }
+
+                let degree = self.free_neighbors(&pos).count() + next_to_head as usize;
+                if degree == 0 {
+                    return false;
+                }
+                if degree == 1 {
+                    dead_ends += 1;
+                }
+            }
+        }
+
+        let first_color = (1 - head_color) as usize;
+        if counts[first_color] != (self.remaining + 1) / 2
+            || counts[1 - first_color] != self.remaining / 2
+            || dead_ends > 1
+        {
+            return false;
+        }
+
+        let start = match start {
+            Some(s) => s,
+            None => return false,
+        };
+        let mut seen = vec![false; self.taken.len()];
+        seen[self.cell(&start)] = true;
+        let mut stack = vec![start];
+        let mut reached = 0;
+        while let Some(current) = stack.pop() {
+            reached += 1;
+            for n in self.free_neighbors(&current).collect::<Vec<_>>() {
+                let i = self.cell(&n);
+                if !seen[i] {
+                    seen[i] = true;
+                    stack.push(n);
+                }
+            }
+        }
+
+        reached == self.remaining
+    }
+
+    fn extend(&mut self, path: &mut Vec<Position>) -> bool {
+        if self.remaining == 0 {
+            return true;
+        }
+        if self.budget == 0 {
+            return false;
+        }
+
+        let head = *path.last().unwrap();
+        if !self.could_finish_from(&head) {
+            self.budget -= 1;
+            return false;
+        }
+
+        // Warnsdorff's rule, squeeze into the tightest spots first while we still can
+        let mut candidates: Vec<(usize, Position)> = self
+            .free_neighbors(&head)
+            .map(|n| (self.free_neighbors(&n).count(), n))
+            .collect();
+        candidates.sort_by_key(|(onward, _)| *onward);
+
+        for (_, next) in candidates {
+            let i = self.cell(&next);
+            self.taken[i] = true;
+            self.remaining -= 1;
+            path.push(next);
+
+            if self.extend(path) {
+                return true;
+            }
+
+            path.pop();
+            self.remaining += 1;
+            self.taken[i] = false;
+        }
+
+        false
+    }
+}
+
+/// A path from `head` through every cell that isn't `blocked`, not including `head` itself
+///
+/// Searches depth first, giving up after `budget` dead ends. `None` when there isn't a path or
+/// we ran out of budget looking for one
+pub fn full_board_path(
+    width: i32,
+    height: i32,
+    blocked: &[Position],
+    head: Position,
+    budget: usize,
+) -> Option<Vec<Position>> {
+    let mut taken = vec![false; (width * height) as usize];
+    for p in blocked.iter().chain(std::iter::once(&head)) {
+        taken[(p.y * width + p.x) as usize] = true;
+    }
+    let remaining = taken.iter().filter(|t| !**t).count();
+
+    let mut search = FillSearch {
+        width,
+        height,
+        taken,
+        remaining,
+        budget,
+    };
+    let mut path = vec![head];
+
+    if search.extend(&mut path) {
+        path.remove(0);
+        Some(path)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_is_cycle(cycle: &HamiltonianCycle, width: i32, height: i32) {
+        let cells = cycle.cells();
+        let expected_len = (width * height) as usize - cycle.skipped.iter().count();
+
+        assert_eq!(cells.len(), expected_len, "{}x{}", width, height);
+        for (i, cell) in cells.iter().enumerate() {
+            assert_eq!(
+                cycle.index_of(cell),
+                Some(i),
+                "{}x{} repeats {:?}",
+                width,
+                height,
+                cell
+            );
+
+            let next = cells[(i + 1) % cells.len()];
+            assert!(
+                are_adjacent(cell, &next),
+                "{}x{} jumps from {:?} to {:?}",
+                width,
+                height,
+                cell,
+                next
+            );
+        }
+    }
+
+    #[test]
+    fn test_cycles_cover_the_board() {
+        for (width, height) in [(7, 7), (8, 7), (7, 8), (10, 10), (11, 11), (19, 19), (3, 3)] {
+            let cycle = HamiltonianCycle::for_board(width, height).unwrap();
+            assert_is_cycle(&cycle, width, height);
+
+            let odd = width % 2 == 1 && height % 2 == 1;
+            assert_eq!(cycle.skipped.is_some(), odd);
+        }
+
+        assert!(HamiltonianCycle::for_board(1, 5).is_none());
+    }
+
+    #[test]
+    fn test_next_step_cuts_across_toward_food() {
+        let cycle = HamiltonianCycle::for_board(11, 11).unwrap();
+        let body = vec![Position { x: 5, y: 0 }];
+
+        // No food, so we just follow the cycle along the bottom row
+        assert_eq!(cycle.next_step(&body, &[]), Some(Position { x: 6, y: 0 }));
+
+        // The cycle goes all the way to the right edge and back before it gets to row one,
+        // so going straight up skips ahead
+        let food = [Position { x: 5, y: 3 }];
+        assert_eq!(cycle.next_step(&body, &food), Some(Position { x: 5, y: 1 }));
+    }
+
+    #[test]
+    fn test_next_step_never_passes_the_tail() {
+        let cycle = HamiltonianCycle::for_board(11, 11).unwrap();
+        let cells = cycle.cells();
+        // Head at the start of the cycle and the tail a couple of cells in front of it
+        let n = cells.len();
+        let body: Vec<Position> = (0..(n - 3)).map(|i| cells[(n - i) % n]).collect();
+
+        assert_eq!(
+            cycle.next_step(&body, &[Position { x: 0, y: 4 }]),
+            Some(cells[1])
+        );
+    }
+
+    #[test]
+    fn test_full_board_path_checks_parity() {
+        // From the middle of an edge there are more corners left than edges, so we can't
+        // alternate between them
+        assert_eq!(
+            full_board_path(3, 3, &[], Position { x: 1, y: 0 }, BACKTRACKING_BUDGET),
+            None
+        );
+
+        let path =
+            full_board_path(5, 5, &[], Position { x: 0, y: 0 }, BACKTRACKING_BUDGET).unwrap();
+        assert_eq!(path.len(), 24);
+        assert!(are_adjacent(&Position { x: 0, y: 0 }, &path[0]));
+        for step in path.windows(2) {
+            assert!(are_adjacent(&step[0], &step[1]));
+        }
+    }
+
+    /// Small xorshift so the solo games play out the same every time
+    fn next_random(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    /// Plays a solo game where food shows up like it does on the real server, only following
+    /// `next_step`, until we fill the whole board
+    fn fills_solo_board(width: i32, height: i32, seed: u64) {
+        let mut random = seed * 2 + 1;
+        let cycle = HamiltonianCycle::for_board(width, height).unwrap();
+
+        let mut body = vec![Position { x: 1, y: 1 }; 3];
+        let mut food: Vec<Position> = vec![];
+        let mut health = 100;
+
+        // On odd boards the last cell is the skipped one, which is up to the full board search
+        while body.len() < cycle.len() {
+            let free: Vec<Position> = (0..width)
+                .flat_map(|x| (0..height).map(move |y| Position { x, y }))
+                .filter(|p| !body.contains(p) && !food.contains(p))
+                .collect();
+            if !free.is_empty() && (food.is_empty() || next_random(&mut random) % 100 < 15) {
+                food.push(free[next_random(&mut random) as usize % free.len()]);
+            }
+
+            let next = cycle
+                .next_step(&body, &food)
+                .expect("the cycle should always have somewhere to go");
+            let tail_moves = body[body.len() - 1] != body[body.len() - 2];
+            let in_the_way = if tail_moves {
+                &body[..body.len() - 1]
+            } else {
+                &body[..]
+            };
+            assert!(!in_the_way.contains(&next));
+
+            body.insert(0, next);
+            body.pop();
+            health -= 1;
+            if let Some(i) = food.iter().position(|f| *f == next) {
+                food.remove(i);
+                health = 100;
+                body.push(*body.last().unwrap());
+            }
+            assert!(
+                health > 0,
+                "starved on a {}x{} board at length {}",
+                width,
+                height,
+                body.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_fills_solo_boards() {
+        for seed in 0..3 {
+            fills_solo_board(11, 11, seed);
+            fills_solo_board(19, 19, seed);
+        }
+    }
+}
@@ -0,0 +1,501 @@
+use crate::devious_devin_mutable::Instruments;
+use crate::safety::{HazardQueryable, SafetyAnalysis};
+use crate::search_deadline::SearchDeadline;
+use crate::*;
+
+use battlesnake_game_types::compact_representation::{BestCellBoard, ToBestCellBoard};
+use battlesnake_game_types::types::*;
+use battlesnake_game_types::wire_representation::NestedGame;
+
+use rand::prelude::*;
+use std::time::Duration;
+use tracing::{info, info_span};
+
+/// How many turns a random playout runs before we score the board as is
+const ROLLOUT_DEPTH: usize = 50;
+
+const EXPLORATION_CONSTANT: f64 = std::f64::consts::SQRT_2;
+
+pub struct HaphazardHank<T> {
+    game: T,
+    game_info: NestedGame,
+    turn: i32,
+    rules: Rules,
+}
+
+#[derive(Debug, Clone)]
+struct MoveStats {
+    dir: Move,
+    visits: u32,
+    total_reward: f64,
+}
+
+impl MoveStats {
+    fn uct(&self, parent_visits: u32) -> f64 {
+        if self.visits == 0 {
+            return f64::INFINITY;
+        }
+
+        let visits = self.visits as f64;
+        self.total_reward / visits
+            + EXPLORATION_CONSTANT * ((parent_visits as f64).ln() / visits).sqrt()
+    }
+}
+
+type Rewards<T> = Vec<(<T as SnakeIDGettableGame>::SnakeIDType, f64)>;
+type JointMove<T> = Vec<(<T as SnakeIDGettableGame>::SnakeIDType, Move)>;
+
+/// A node in a decoupled UCT tree
+///
+/// Every snake that is alive at this node keeps its own statistics for each of its moves and
+/// picks from them independently. Together their picks make up the joint move that selects the
+/// child, so no snake gets to see what the others chose before making its own choice
+struct MctsNode<T: SnakeIDGettableGame> {
+    game: T,
+    visits: u32,
+    stats: Vec<(T::SnakeIDType, Vec<MoveStats>)>,
+    children: Vec<(JointMove<T>, MctsNode<T>)>,
+}
+
+impl<T> MctsNode<T>
+where
+    T: SnakeIDGettableGame
+        + HealthGettableGame
+        + HeadGettableGame
+        + PositionGettableGame
+        + NeighborDeterminableGame
+        + VictorDeterminableGame
+        + RandomReasonableMovesGame
+        + SimulableGame<Instruments>
+        + Clone,
+{
+    fn new(game: T) -> Self {
+        let stats = game
+            .get_snake_ids()
+            .into_iter()
+            .filter(|id| game.is_alive(id))
+            .map(|id| {
+                let mut moves: Vec<_> = game
+                    .possible_moves(&game.get_head_as_native_position(&id))
+                    .into_iter()
+                    .map(|(dir, _)| dir)
+                    .collect();
+                if moves.is_empty() {
+                    moves = Move::all();
+                }
+
+                let stats = moves
+                    .into_iter()
+                    .map(|dir| MoveStats {
+                        dir,
+                        visits: 0,
+                        total_reward: 0.0,
+                    })
+                    .collect();
+
+                (id, stats)
+            })
+            .collect();
+
+        Self {
+            game,
+            visits: 0,
+            stats,
+            children: vec![],
+        }
+    }
+
+    fn select_joint_move(&self, rng: &mut impl Rng) -> JointMove<T> {
+        self.stats
+            .iter()
+            .map(|(id, stats)| {
+                let best = stats
+                    .iter()
+                    .map(|s| s.uct(self.visits))
+                    .fold(f64::NEG_INFINITY, f64::max);
+                let dir = stats
+                    .iter()
+                    .filter(|s| s.uct(self.visits) >= best)
+                    .map(|s| s.dir)
+                    .choose(&mut *rng)
+                    .expect("There is always at least one move to choose from");
+
+                (id.clone(), dir)
+            })
+            .collect()
+    }
+
+    fn backpropagate(&mut self, joint_move: &[(T::SnakeIDType, Move)], rewards: &Rewards<T>) {
+        self.visits += 1;
+
+        for (id, dir) in joint_move {
+            let reward = reward_for::<T>(rewards, id);
+
+            if let Some(s) = self
+                .stats
+                .iter_mut()
+                .find(|(s_id, _)| s_id == id)
+                .and_then(|(_, stats)| stats.iter_mut().find(|s| s.dir == *dir))
+            {
+                s.visits += 1;
+                s.total_reward += reward;
+            }
+        }
+    }
+
+    /// Runs a single select, expand, rollout and backpropagate pass starting at this node
+    fn search(&mut self, rng: &mut impl Rng) -> Rewards<T> {
+        if self.game.is_over() {
+            let rewards = terminal_rewards(&self.game);
+            self.backpropagate(&[], &rewards);
+            return rewards;
+        }
+
+        let joint_move = self.select_joint_move(rng);
+        let rewards = match self.children.iter().position(|(m, _)| m == &joint_move) {
+            Some(i) => self.children[i].1.search(rng),
+            None => {
+                let mut child = MctsNode::new(simulate_single(&self.game, &joint_move));
+                let rewards = rollout(&child.game);
+                child.backpropagate(&[], &rewards);
+                self.children.push((joint_move.clone(), child));
+
+                rewards
+            }
+        };
+
+        self.backpropagate(&joint_move, &rewards);
+
+        rewards
+    }
+
+    /// `None` until the move has actually been searched, an unvisited move is no better than
+    /// a guess
+    fn most_visited_move(&self, snake_id: &T::SnakeIDType) -> Option<Move> {
+        self.stats
+            .iter()
+            .find(|(id, _)| id == snake_id)
+            .and_then(|(_, stats)| stats.iter().max_by_key(|s| s.visits))
+            .filter(|s| s.visits > 0)
+            .map(|s| s.dir)
+    }
+}
+
+fn reward_for<T: SnakeIDGettableGame>(rewards: &Rewards<T>, snake_id: &T::SnakeIDType) -> f64 {
+    rewards
+        .iter()
+        .find(|(id, _)| id == snake_id)
+        .map(|(_, r)| *r)
+        .unwrap_or(0.0)
+}
+
+fn simulate_single<T>(game: &T, joint_move: &[(T::SnakeIDType, Move)]) -> T
+where
+    T: SnakeIDGettableGame + SimulableGame<Instruments>,
+{
+    let moves = joint_move
+        .iter()
+        .map(|(id, dir)| (id.clone(), vec![*dir]))
+        .collect();
+
+    game.simulate_with_moves(&Instruments, moves)
+        .into_iter()
+        .next()
+        .map(|(_, board)| board)
+        .expect("Simulating a single move per snake gives exactly one board")
+}
+
+/// Winners take the whole reward. If the game isn't over yet the snakes that are still alive
+/// split it, so surviving in a crowded game is worth less than surviving a duel
+fn terminal_rewards<T>(game: &T) -> Rewards<T>
+where
+    T: SnakeIDGettableGame + HealthGettableGame + VictorDeterminableGame,
+{
+    let snake_ids = game.get_snake_ids();
+
+    if game.is_over() {
+        let winner = game.get_winner();
+        let tie_reward = if winner.is_none() { 0.5 } else { 0.0 };
+
+        return snake_ids
+            .into_iter()
+            .map(|id| {
+                let reward = if Some(&id) == winner.as_ref() {
+                    1.0
+                } else {
+                    tie_reward
+                };
+                (id, reward)
+            })
+            .collect();
+    }
+
+    let alive_count = snake_ids
+        .iter()
+        .filter(|id| game.is_alive(id))
+        .count()
+        .max(1);
+
+    snake_ids
+        .into_iter()
+        .map(|id| {
+            let reward = if game.is_alive(&id) {
+                1.0 / alive_count as f64
+            } else {
+                0.0
+            };
+            (id, reward)
+        })
+        .collect()
+}
+
+fn rollout<T>(game: &T) -> Rewards<T>
+where
+    T: SnakeIDGettableGame
+        + HealthGettableGame
+        + VictorDeterminableGame
+        + RandomReasonableMovesGame
+        + SimulableGame<Instruments>
+        + Clone,
+{
+    let mut game = game.clone();
+
+    for _ in 0..ROLLOUT_DEPTH {
+        if game.is_over() {
+            break;
+        }
+
+        let joint_move: JointMove<T> = game
+            .random_reasonable_move_for_each_snake()
+            .into_iter()
+            .filter(|(id, _)| game.is_alive(id))
+            .collect();
+
+        game = simulate_single(&game, &joint_move);
+    }
+
+    terminal_rewards(&game)
+}
+
+impl<T> HaphazardHank<T>
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + HealthGettableGame
+        + HeadGettableGame
+        + PositionGettableGame
+        + NeighborDeterminableGame
+        + VictorDeterminableGame
+        + RandomReasonableMovesGame
+        + SimulableGame<Instruments>
+        + Clone,
+{
+    fn time_limit_ms(&self) -> i64 {
+        const NETWORK_LATENCY_PADDING: i64 = 100;
+        self.game_info.timeout - NETWORK_LATENCY_PADDING
+    }
+
+    fn max_duration(&self) -> Duration {
+        Duration::from_millis(self.time_limit_ms().max(0) as u64)
+    }
+
+    fn monte_carlo_tree_search(&self) -> Option<Move> {
+        let deadline = SearchDeadline::new(self.max_duration());
+        let mut rng = thread_rng();
+        let mut root = MctsNode::new(self.game.clone());
+
+        // Every pass would stop at the root, so there is nothing to learn
+        if self.game.is_over() {
+            return None;
+        }
+
+        while !deadline.is_expired() {
+            root.search(&mut rng);
+        }
+
+        let chosen = root.most_visited_move(self.game.you_id());
+        info!(iterations = root.visits, direction = ?chosen, "Finished monte_carlo_tree_search");
+
+        chosen
+    }
+}
+
+impl<T> BattlesnakeAI for HaphazardHank<T>
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + HealthGettableGame
+        + HeadGettableGame
+        + PositionGettableGame
+        + NeighborDeterminableGame
+        + VictorDeterminableGame
+        + RandomReasonableMovesGame
+        + SimulableGame<Instruments>
+        + LengthGettableGame
+        + SnakeBodyGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame
+        + HazardQueryable
+        + Clone,
+{
+    fn make_move(&self) -> Result<MoveOutput, Box<dyn std::error::Error + Send + Sync>> {
+        let chosen =
+            info_span!("monte_carlo_tree_search", game_id = %&self.game_info.id, turn = self.turn, ruleset_name = %self.game_info.ruleset.name, ruleset_version = %self.game_info.ruleset.version).in_scope(|| self.monte_carlo_tree_search());
+
+        // The rollouts are random, so a move that was barely searched can still look good
+        let analysis = SafetyAnalysis::for_you(&self.game, &self.rules);
+        let dir = match chosen {
+            Some(dir) => analysis.choose(dir),
+            None => analysis.best_move(),
+        };
+
+        Ok(MoveOutput {
+            r#move: format!("{}", dir),
+            shout: None,
+        })
+    }
+}
+
+pub struct HaphazardHankFactory;
+
+impl BattlesnakeFactory for HaphazardHankFactory {
+    fn name(&self) -> String {
+        "haphazard-hank".to_owned()
+    }
+
+    fn from_wire_game(&self, game: Game) -> BoxedSnake {
+        self.from_wire_game_with_context(game, GameContext::default())
+    }
+
+    fn from_wire_game_with_context(&self, game: Game, context: GameContext) -> BoxedSnake {
+        let game_info = game.game.clone();
+        let turn = game.turn;
+        let rules = context.rules;
+
+        let best_board = game.to_best_cell_board().unwrap();
+        let inner: BoxedSnake = match best_board {
+            BestCellBoard::Standard(b) => Box::new(HaphazardHank {
+                game_info,
+                turn,
+                game: *b,
+                rules,
+            }),
+            BestCellBoard::LargestU8(b) => Box::new(HaphazardHank {
+                game_info,
+                turn,
+                game: *b,
+                rules,
+            }),
+            BestCellBoard::Large(b) => Box::new(HaphazardHank {
+                game_info,
+                turn,
+                game: *b,
+                rules,
+            }),
+            BestCellBoard::Silly(b) => Box::new(HaphazardHank {
+                game_info,
+                turn,
+                game: *b,
+                rules,
+            }),
+        };
+
+        inner
+    }
+
+    fn about(&self) -> AboutMe {
+        AboutMe {
+            author: Some("coreyja".to_owned()),
+            color: Some("#33B5E5".to_owned()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::start_of_game;
+    use battlesnake_game_types::compact_representation::{CellBoard, CellBoard4Snakes11x11};
+
+    fn board(game: Game) -> CellBoard4Snakes11x11 {
+        let id_map = build_snake_id_map(&game);
+
+        CellBoard::convert_from_game(game, &id_map).unwrap()
+    }
+
+    /// On our own in the corner at (0, 0) with our body off to the right, so up is the only
+    /// move that doesn't kill us
+    fn cornered() -> Game {
+        serde_json::from_str(r#"{"game":{"id":"","ruleset":{"name":"standard","version":"v1.0.17"},"timeout":200},"turn":10,"board":{"height":11,"width":11,"food":[],"hazards":[],"snakes":[{"id":"you","name":"","health":90,"body":[{"x":0,"y":0},{"x":1,"y":0},{"x":2,"y":0}],"latency":0,"head":{"x":0,"y":0},"length":3,"shout":""}]},"you":{"id":"you","name":"","health":90,"body":[{"x":0,"y":0},{"x":1,"y":0},{"x":2,"y":0}],"latency":0,"head":{"x":0,"y":0},"length":3,"shout":""}}"#).unwrap()
+    }
+
+    #[test]
+    fn test_living_snakes_split_the_reward_until_someone_wins() {
+        let game = board(start_of_game());
+        let rewards = terminal_rewards(&game);
+
+        assert_eq!(rewards.len(), 3);
+        for (_, reward) in rewards.iter() {
+            assert!((reward - 1.0 / 3.0).abs() < 1e-9);
+        }
+
+        let solo = board(cornered());
+        let rewards = terminal_rewards(&solo);
+        assert!(solo.is_over());
+        assert_eq!(
+            reward_for::<CellBoard4Snakes11x11>(&rewards, solo.you_id()),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_rollout_rewards_add_up_to_at_most_one() {
+        let game = board(start_of_game());
+        let rewards = rollout(&game);
+        let total: f64 = rewards.iter().map(|(_, r)| r).sum();
+
+        assert_eq!(rewards.len(), 3);
+        assert!(rewards.iter().all(|(_, r)| (0.0..=1.0).contains(r)));
+        assert!(total <= 1.0 + 1e-9);
+    }
+
+    #[test]
+    fn test_searches_and_picks_a_move() {
+        let mut root = MctsNode::new(board(start_of_game()));
+        let mut rng = thread_rng();
+        let you = *root.game.you_id();
+
+        assert_eq!(root.most_visited_move(&you), None);
+        for _ in 0..200 {
+            root.search(&mut rng);
+        }
+
+        assert_eq!(root.visits, 200);
+        assert!(root.most_visited_move(&you).is_some());
+    }
+
+    #[test]
+    fn test_never_walks_into_a_wall_when_there_is_nothing_to_search() {
+        let output = HaphazardHankFactory
+            .from_wire_game(cornered())
+            .make_move()
+            .unwrap();
+
+        assert_eq!(output.direction(), Some(Move::Up));
+    }
+
+    #[test]
+    fn test_long_timeouts_fit_in_the_time_limit() {
+        let mut game = start_of_game();
+        game.game.timeout = 10_000;
+        let hank = HaphazardHank {
+            game_info: game.game.clone(),
+            turn: game.turn,
+            game: board(game),
+            rules: Rules::default(),
+        };
+
+        assert_eq!(hank.max_duration(), Duration::from_millis(9_900));
+    }
+}
@@ -1,7 +1,7 @@
 #[macro_use]
 extern crate serde_derive;
 
-use std::{collections::HashSet, convert::TryInto, fmt::Debug};
+use std::{collections::HashSet, convert::TryInto, fmt::Debug, sync::Arc};
 
 pub use battlesnake_game_types::compact_representation::CellBoard4Snakes11x11;
 pub use battlesnake_game_types::types::Move;
@@ -9,14 +9,35 @@ pub use battlesnake_game_types::wire_representation::Game;
 
 pub mod a_prime;
 pub mod amphibious_arthur;
+pub mod bitboard;
 pub mod bombastic_bob;
+pub mod chambers;
 pub mod constant_carter;
 pub mod devious_devin_eval;
 pub mod devious_devin_full;
 pub mod devious_devin_mutable;
+pub mod distance_field;
 pub mod eremetic_eric;
+pub mod evaluator;
 pub mod famished_frank;
+pub mod fingerprint;
+pub mod flood_fill;
+pub mod game_state;
 pub mod gigantic_george;
+pub mod hamiltonian;
+pub mod haphazard_hank;
+pub mod matrix_game;
+pub mod opening_book;
+pub mod opponent_model;
+pub mod rules;
+pub mod safety;
+pub mod safety_net;
+pub mod search_deadline;
+pub mod tablebase;
+pub mod transposition_table;
+
+#[cfg(test)]
+mod test_helpers;
 
 #[derive(Serialize)]
 pub struct AboutMe {
@@ -46,11 +67,16 @@ use battlesnake_game_types::{
     wire_representation::Position,
 };
 
+use crate::game_state::{GameState, GameStateStore};
+use crate::opening_book::OpeningBook;
+use crate::rules::Rules;
+use crate::safety_net::SafetyNetFactory;
+use crate::tablebase::Tablebase;
 use crate::{
     amphibious_arthur::AmphibiousArthurFactory, bombastic_bob::BombasticBobFactory,
     constant_carter::ConstantCarterFactory, devious_devin_full::FullDeviousDevinFactory,
     eremetic_eric::EremeticEricFactory, famished_frank::FamishedFrankFactory,
-    gigantic_george::GiganticGeorgeFactory,
+    gigantic_george::GiganticGeorgeFactory, haphazard_hank::HaphazardHankFactory,
 };
 
 pub enum MoveResult {
@@ -76,6 +102,7 @@ pub trait MoveableGame: SnakeIDGettableGame + PositionGettableGame {
         &mut self,
         coor: &Self::NativePositionType,
         snake_id: &Self::SnakeIDType,
+        rules: &Rules,
     ) -> SnakeMove<Self::SnakeIDType>;
     fn reverse_move(&mut self, m: SnakeMove<Self::SnakeIDType>);
 
@@ -88,6 +115,7 @@ impl MoveableGame for Game {
         &mut self,
         coor: &Position,
         snake_id: &Self::SnakeIDType,
+        rules: &Rules,
     ) -> SnakeMove<Self::SnakeIDType> {
         let to_move = self
             .board
@@ -103,7 +131,7 @@ impl MoveableGame for Game {
         let move_result = MoveResult::MovedTail(old_health, to_move.body.pop_back().unwrap());
 
         if self.board.hazards.contains(coor) {
-            to_move.health -= 15;
+            to_move.health -= rules.hazard_damage_per_turn;
         }
 
         let snake_id = snake_id.to_owned();
@@ -172,12 +200,25 @@ impl MoveableGame for Game {
     }
 }
 
+/// Every move a snake can make, in the order the engine lists them
+pub const ALL_MOVES: [Move; 4] = [Move::Up, Move::Down, Move::Left, Move::Right];
+
 #[derive(Serialize, Debug)]
 pub struct MoveOutput {
     r#move: String,
     shout: Option<String>,
 }
 
+impl MoveOutput {
+    /// The move this output is for, `None` if it isn't one of the four the engine accepts
+    pub fn direction(&self) -> Option<Move> {
+        ALL_MOVES
+            .iter()
+            .copied()
+            .find(|m| format!("{}", m) == self.r#move)
+    }
+}
+
 pub type BoxedSnake = Box<dyn BattlesnakeAI + Send + Sync>;
 pub type BoxedFactory = Box<dyn BattlesnakeFactory + Send + Sync>;
 
@@ -186,10 +227,60 @@ pub trait BattlesnakeAI {
     fn make_move(&self) -> Result<MoveOutput, Box<dyn std::error::Error + Send + Sync>>;
 }
 
+/// Everything we know about a game that doesn't fit in the wire `Game`
+#[derive(Debug, Clone, Default)]
+pub struct GameContext {
+    pub rules: Rules,
+    /// What this snake saved on earlier turns of the same game. Servers fill this in from
+    /// their `GameStateStore`, otherwise it starts empty every request
+    pub state: GameState,
+}
+
+impl GameContext {
+    pub fn from_request_body(body: &serde_json::Value) -> Self {
+        Self {
+            rules: Rules::from_request_body(body),
+            state: GameState::default(),
+        }
+    }
+
+    pub fn with_state_from(self, store: &GameStateStore, game: &Game, snake_name: &str) -> Self {
+        Self {
+            state: store.state_for(&game.game.id, snake_name),
+            ..self
+        }
+    }
+}
+
+/// Parses a `/move` or `/end` body into the wire `Game` and the context that goes with it
+pub fn parse_request_body(body: &str) -> Result<(Game, GameContext), serde_json::Error> {
+    let value: serde_json::Value = serde_json::from_str(body)?;
+    let context = GameContext::from_request_body(&value);
+    let game = serde_json::from_value(value)?;
+
+    Ok((game, context))
+}
+
 pub trait BattlesnakeFactory {
     fn name(&self) -> String;
     fn from_wire_game(&self, game: Game) -> BoxedSnake;
 
+    /// Snakes that care about the ruleset settings override this, everyone else gets
+    /// `from_wire_game`
+    fn from_wire_game_with_context(&self, game: Game, _context: GameContext) -> BoxedSnake {
+        self.from_wire_game(game)
+    }
+
+    /// Called with the `/start` body before our first move. Anything worth working out once per
+    /// game can be done here and saved in `context.state`
+    fn start(&self, _game: &Game, _context: &GameContext) {}
+
+    /// Called with the `/end` body. Servers forget the game's state once this returns
+    fn end(&self, game: &Game, context: &GameContext) {
+        self.from_wire_game_with_context(game.clone(), context.clone())
+            .end();
+    }
+
     fn about(&self) -> AboutMe {
         Default::default()
     }
@@ -211,15 +302,31 @@ impl SnakeTailPushableGame for Game {
     }
 }
 
-pub fn all_factories() -> Vec<BoxedFactory> {
+/// The snakes that pick their move straight from the board, without looking any turns ahead.
+/// These are cheap enough for Devin to replay for each opponent every turn
+pub fn simple_factories() -> Vec<BoxedFactory> {
     vec![
-        Box::new(AmphibiousArthurFactory {}),
         Box::new(BombasticBobFactory {}),
         Box::new(ConstantCarterFactory {}),
-        // Box::new(FullDeviousDevinFactory {}),
-        Box::new(devious_devin_eval::DeviousDevinFactory {}),
         Box::new(EremeticEricFactory {}),
         Box::new(FamishedFrankFactory {}),
-        Box::new(GiganticGeorgeFactory {}),
     ]
 }
+
+pub fn all_factories() -> Vec<BoxedFactory> {
+    // Box::new(FullDeviousDevinFactory {}),
+    let devin: BoxedFactory = Box::new(devious_devin_eval::DeviousDevinFactory {
+        opening_book: OpeningBook::from_env().map(Arc::new),
+        tablebase: Tablebase::from_env().map(Arc::new),
+        fingerprint_candidates: Arc::new(simple_factories()),
+    });
+
+    let mut factories = simple_factories();
+    factories.push(Box::new(AmphibiousArthurFactory {}));
+    factories.push(Box::new(GiganticGeorgeFactory {}));
+    factories.push(Box::new(HaphazardHankFactory {}));
+    factories.push(devin);
+    factories.sort_by_key(|f| f.name());
+
+    factories.into_iter().map(SafetyNetFactory::wrap).collect()
+}
@@ -0,0 +1,128 @@
+/// How many rounds of regret matching we play before reading off the average strategies
+pub const REGRET_MATCHING_ITERATIONS: usize = 2_000;
+
+/// A mixed strategy for each side of a zero sum matrix game
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixedStrategies {
+    /// Probability of picking each row, for the player trying to maximize the payoff
+    pub rows: Vec<f64>,
+    /// Probability of picking each column, for the player trying to minimize the payoff
+    pub columns: Vec<f64>,
+}
+
+fn strategy_from_regrets(regrets: &[f64]) -> Vec<f64> {
+    let positive_total: f64 = regrets.iter().map(|r| r.max(0.0)).sum();
+
+    if positive_total > 0.0 {
+        regrets
+            .iter()
+            .map(|r| r.max(0.0) / positive_total)
+            .collect()
+    } else {
+        vec![1.0 / regrets.len() as f64; regrets.len()]
+    }
+}
+
+fn normalized(totals: Vec<f64>) -> Vec<f64> {
+    let sum: f64 = totals.iter().sum();
+
+    if sum > 0.0 {
+        totals.into_iter().map(|t| t / sum).collect()
+    } else {
+        let len = totals.len();
+        vec![1.0 / len as f64; len]
+    }
+}
+
+/// Approximates a Nash equilibrium of the game by having both players play regret matching
+/// against each other and averaging the strategies they used
+///
+/// `payoffs[row][column]` is the payoff to the row player. Every row must have the same,
+/// non-zero, number of columns
+pub fn solve_with_regret_matching(payoffs: &[Vec<f64>], iterations: usize) -> MixedStrategies {
+    let row_count = payoffs.len();
+    let column_count = payoffs[0].len();
+
+    let mut row_regrets = vec![0.0; row_count];
+    let mut column_regrets = vec![0.0; column_count];
+    let mut row_totals = vec![0.0; row_count];
+    let mut column_totals = vec![0.0; column_count];
+
+    for _ in 0..iterations {
+        let row_strategy = strategy_from_regrets(&row_regrets);
+        let column_strategy = strategy_from_regrets(&column_regrets);
+
+        let row_values: Vec<f64> = payoffs
+            .iter()
+            .map(|row| row.iter().zip(&column_strategy).map(|(p, c)| p * c).sum())
+            .collect();
+        let column_values: Vec<f64> = (0..column_count)
+            .map(|c| {
+                payoffs
+                    .iter()
+                    .zip(&row_strategy)
+                    .map(|(row, r)| row[c] * r)
+                    .sum()
+            })
+            .collect();
+        let expected: f64 = row_values
+            .iter()
+            .zip(&row_strategy)
+            .map(|(v, r)| v * r)
+            .sum();
+
+        for (regret, value) in row_regrets.iter_mut().zip(&row_values) {
+            *regret += value - expected;
+        }
+        // The column player wants the payoff to be small, so its regret is flipped
+        for (regret, value) in column_regrets.iter_mut().zip(&column_values) {
+            *regret += expected - value;
+        }
+
+        for (total, r) in row_totals.iter_mut().zip(&row_strategy) {
+            *total += r;
+        }
+        for (total, c) in column_totals.iter_mut().zip(&column_strategy) {
+            *total += c;
+        }
+    }
+
+    MixedStrategies {
+        rows: normalized(row_totals),
+        columns: normalized(column_totals),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a - e).abs() < 0.05,
+                "{:?} is not close to {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_matching_pennies_is_a_coinflip() {
+        let payoffs = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        let solved = solve_with_regret_matching(&payoffs, REGRET_MATCHING_ITERATIONS);
+
+        assert_close(&solved.rows, &[0.5, 0.5]);
+        assert_close(&solved.columns, &[0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_dominant_row_is_played_every_time() {
+        let payoffs = vec![vec![0.0, 0.5], vec![0.5, 1.0], vec![0.0, 0.0]];
+        let solved = solve_with_regret_matching(&payoffs, REGRET_MATCHING_ITERATIONS);
+
+        assert_close(&solved.rows, &[0.0, 1.0, 0.0]);
+        assert_close(&solved.columns, &[1.0, 0.0]);
+    }
+}
@@ -0,0 +1,359 @@
+use battlesnake_game_types::types::{Move, SizeDeterminableGame};
+use battlesnake_game_types::wire_representation::{Game, Position};
+
+use crate::rules::Rules;
+use crate::ALL_MOVES;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+fn move_vector(m: Move) -> (i32, i32) {
+    match m {
+        Move::Up => (0, 1),
+        Move::Down => (0, -1),
+        Move::Left => (-1, 0),
+        Move::Right => (1, 0),
+    }
+}
+
+fn parse_move(name: &str) -> Option<Move> {
+    ALL_MOVES.iter().copied().find(|m| format!("{}", m) == name)
+}
+
+/// One of the 8 ways to turn and flip a board onto itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Symmetry {
+    /// Mirror the x axis before turning
+    reflect: bool,
+    /// How many times to turn the board a quarter counter clockwise
+    quarter_turns: u8,
+}
+
+impl Symmetry {
+    /// Quarter turns swap the width and height, so boards that aren't square only have half of
+    /// the symmetries
+    fn all_for(width: u32, height: u32) -> impl Iterator<Item = Symmetry> {
+        let square = width == height;
+
+        [false, true]
+            .iter()
+            .flat_map(|reflect| {
+                (0..4).map(move |quarter_turns| Symmetry {
+                    reflect: *reflect,
+                    quarter_turns,
+                })
+            })
+            .filter(move |s| square || s.quarter_turns % 2 == 0)
+    }
+
+    fn apply_vector(&self, (mut x, mut y): (i32, i32)) -> (i32, i32) {
+        if self.reflect {
+            x = -x;
+        }
+        for _ in 0..self.quarter_turns {
+            let turned = (-y, x);
+            x = turned.0;
+            y = turned.1;
+        }
+
+        (x, y)
+    }
+
+    /// Turns around the center of the board. Working in doubled coordinates keeps the center
+    /// on a whole number for boards with an even side
+    fn apply_position(&self, pos: &Position, width: u32, height: u32) -> Position {
+        let (width, height) = (width as i32, height as i32);
+        let (x, y) = self.apply_vector((2 * pos.x - (width - 1), 2 * pos.y - (height - 1)));
+        let (new_width, new_height) = if self.quarter_turns % 2 == 0 {
+            (width, height)
+        } else {
+            (height, width)
+        };
+
+        Position {
+            x: (x + new_width - 1) / 2,
+            y: (y + new_height - 1) / 2,
+        }
+    }
+
+    fn apply_move(&self, m: Move) -> Move {
+        let turned = self.apply_vector(move_vector(m));
+
+        ALL_MOVES
+            .iter()
+            .copied()
+            .find(|candidate| move_vector(*candidate) == turned)
+            .expect("Turning a move always gives another move")
+    }
+
+    fn undo_move(&self, m: Move) -> Move {
+        ALL_MOVES
+            .iter()
+            .copied()
+            .find(|candidate| self.apply_move(*candidate) == m)
+            .expect("Every symmetry can be undone")
+    }
+}
+
+/// Writes the board out as seen through `symmetry`. Bodies keep their order, everything else
+/// is sorted so the key doesn't depend on the order the engine sent things in
+fn board_key(game: &Game, symmetry: &Symmetry) -> String {
+    let (width, height) = (game.get_width(), game.get_height());
+    let cells = |positions: &mut dyn Iterator<Item = &Position>| -> Vec<(i32, i32)> {
+        positions
+            .map(|p| symmetry.apply_position(p, width, height))
+            .map(|p| (p.x, p.y))
+            .collect()
+    };
+    let sorted_cells = |positions: &mut dyn Iterator<Item = &Position>| {
+        let mut cells = cells(positions);
+        cells.sort_unstable();
+        cells
+    };
+
+    let you = cells(&mut game.you.body.iter());
+    let mut others: Vec<_> = game
+        .board
+        .snakes
+        .iter()
+        .filter(|s| s.id != game.you.id)
+        .map(|s| (cells(&mut s.body.iter()), s.health))
+        .collect();
+    others.sort();
+
+    let (width, height) = if symmetry.quarter_turns % 2 == 0 {
+        (width, height)
+    } else {
+        (height, width)
+    };
+
+    format!(
+        "{}x{};you:{:?},{};others:{:?};food:{:?};hazards:{:?}",
+        width,
+        height,
+        you,
+        game.you.health,
+        others,
+        sorted_cells(&mut game.board.food.iter()),
+        sorted_cells(&mut game.board.hazards.iter()),
+    )
+}
+
+/// The ruleset and the settings that change how a board plays out, so a move worked out for
+/// one ruleset is never played in another
+fn rules_key(game: &Game, rules: &Rules) -> String {
+    format!(
+        "{};food:{},{};hazard:{}",
+        game.game.ruleset.name,
+        rules.food_spawn_chance,
+        rules.minimum_food,
+        rules.hazard_damage_per_turn,
+    )
+}
+
+/// The smallest key out of every symmetry of the board, along with the symmetry that gives it
+fn canonical_key(game: &Game, rules: &Rules) -> (String, Symmetry) {
+    let rules_key = rules_key(game, rules);
+
+    Symmetry::all_for(game.get_width(), game.get_height())
+        .map(|symmetry| {
+            (
+                format!("{};{}", rules_key, board_key(game, &symmetry)),
+                symmetry,
+            )
+        })
+        .min_by(|(a, _), (b, _)| a.cmp(b))
+        .expect("Every board has at least the identity symmetry")
+}
+
+/// The book's move for one position
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct BookEntry {
+    /// In the canonical orientation
+    #[serde(rename = "move")]
+    direction: String,
+    /// How many turns, starting with this one, were filled in from this position
+    turns: usize,
+}
+
+/// Moves worked out ahead of time for positions that come up at the start of games
+///
+/// Positions are stored turned and flipped into a canonical orientation, so one entry covers
+/// every rotation and reflection of the same spawn layout. They are keyed by the ruleset and
+/// its settings too, since the same board can call for a different move under other rules
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct OpeningBook {
+    entries: BTreeMap<String, BookEntry>,
+}
+
+impl OpeningBook {
+    /// Where servers look for a book to load when they start up
+    pub const ENV_VAR: &'static str = "OPENING_BOOK_PATH";
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self::from_json(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        std::fs::write(path, self.to_json()?)?;
+
+        Ok(())
+    }
+
+    /// The book at `OPENING_BOOK_PATH`, if that is set and points at a book we can read
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var(Self::ENV_VAR).ok()?;
+
+        match Self::load(&path) {
+            Ok(book) => Some(book),
+            Err(e) => {
+                tracing::warn!(path = %path, error = %e, "Couldn't load the opening book");
+                None
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// How many turns from this position the book was filled in for, `None` when it isn't in
+    /// the book at all
+    pub fn turns_filled(&self, game: &Game, rules: &Rules) -> Option<usize> {
+        self.entries
+            .get(&canonical_key(game, rules).0)
+            .map(|entry| entry.turns)
+    }
+
+    /// Adds the move for this board, along with how many turns from here have been filled in
+    pub fn insert(&mut self, game: &Game, rules: &Rules, m: Move, turns: usize) {
+        let (key, symmetry) = canonical_key(game, rules);
+
+        self.entries.insert(
+            key,
+            BookEntry {
+                direction: format!("{}", symmetry.apply_move(m)),
+                turns,
+            },
+        );
+    }
+
+    /// The book move for this board, turned back to match the way the board is facing
+    pub fn lookup(&self, game: &Game, rules: &Rules) -> Option<Move> {
+        let (key, symmetry) = canonical_key(game, rules);
+        let m = parse_move(&self.entries.get(&key)?.direction)?;
+
+        Some(symmetry.undo_move(m))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::start_of_game;
+
+    /// The same board turned and flipped by `symmetry`
+    fn transformed(game: &Game, symmetry: &Symmetry) -> Game {
+        let (width, height) = (game.get_width(), game.get_height());
+        let mut game = game.clone();
+        let turn = |p: &mut Position| *p = symmetry.apply_position(p, width, height);
+
+        game.you.body.iter_mut().for_each(turn);
+        game.board.food.iter_mut().for_each(turn);
+        game.board.hazards.iter_mut().for_each(turn);
+        for snake in game.board.snakes.iter_mut() {
+            snake.body.iter_mut().for_each(turn);
+        }
+
+        game
+    }
+
+    #[test]
+    fn test_symmetries_move_cells_around_the_center() {
+        let turn = Symmetry {
+            reflect: false,
+            quarter_turns: 1,
+        };
+        assert_eq!(
+            turn.apply_position(&Position { x: 10, y: 0 }, 11, 11),
+            Position { x: 10, y: 10 }
+        );
+        assert_eq!(turn.apply_move(Move::Right), Move::Up);
+
+        let flip = Symmetry {
+            reflect: true,
+            quarter_turns: 0,
+        };
+        assert_eq!(
+            flip.apply_position(&Position { x: 1, y: 3 }, 8, 8),
+            Position { x: 6, y: 3 }
+        );
+        assert_eq!(flip.undo_move(Move::Left), Move::Right);
+    }
+
+    #[test]
+    fn test_lookup_works_from_every_orientation() {
+        let game = start_of_game();
+        let rules = Rules::default();
+        let mut book = OpeningBook::default();
+        book.insert(&game, &rules, Move::Up, 1);
+
+        for symmetry in Symmetry::all_for(11, 11) {
+            let turned = transformed(&game, &symmetry);
+
+            assert_eq!(
+                book.lookup(&turned, &rules),
+                Some(symmetry.apply_move(Move::Up))
+            );
+        }
+        assert_eq!(book.len(), 1);
+    }
+
+    #[test]
+    fn test_round_trips_through_json() {
+        let game = start_of_game();
+        let rules = Rules::default();
+        let mut book = OpeningBook::default();
+        book.insert(&game, &rules, Move::Left, 3);
+
+        let loaded = OpeningBook::from_json(&book.to_json().unwrap()).unwrap();
+
+        assert_eq!(loaded, book);
+        assert_eq!(loaded.lookup(&game, &rules), Some(Move::Left));
+        assert_eq!(loaded.turns_filled(&game, &rules), Some(3));
+    }
+
+    #[test]
+    fn test_other_rulesets_and_settings_miss_the_book() {
+        let game = start_of_game();
+        let rules = Rules::default();
+        let mut book = OpeningBook::default();
+        book.insert(&game, &rules, Move::Up, 1);
+
+        let mut royale = game.clone();
+        royale.game.ruleset.name = "royale".to_owned();
+        assert_eq!(book.lookup(&royale, &rules), None);
+
+        let more_food = Rules {
+            minimum_food: 3,
+            ..rules
+        };
+        assert_eq!(book.lookup(&game, &more_food), None);
+        assert_eq!(book.lookup(&game, &rules), Some(Move::Up));
+    }
+}
@@ -0,0 +1,366 @@
+use battlesnake_game_types::types::*;
+use battlesnake_game_types::wire_representation::Game;
+
+use crate::a_prime::dist_between_new;
+
+use std::collections::HashMap;
+
+/// We need to have seen an opponent make this many moves before we trust the model enough to
+/// stop searching the moves it doesn't expect
+pub const MIN_OBSERVATIONS_TO_PRUNE: u32 = 10;
+
+/// Moves the model gives less of a chance than this are left out of the search
+pub const PRUNE_BELOW: f64 = 0.05;
+
+/// Running into a body is almost always a mistake, but we don't rule it out completely since
+/// tails move out of the way
+const BODY_WEIGHT: f64 = 0.01;
+
+/// Something a move can do that some snakes go for more than others
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tendency {
+    SeeksFood,
+    ChasesHeads,
+    HugsWalls,
+    ChasesTail,
+}
+
+const ALL_TENDENCIES: [Tendency; 4] = [
+    Tendency::SeeksFood,
+    Tendency::ChasesHeads,
+    Tendency::HugsWalls,
+    Tendency::ChasesTail,
+];
+
+/// Which tendencies a single move shows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MoveFeatures {
+    /// Ends up closer to the nearest food
+    pub seeks_food: bool,
+    /// Ends up closer to the nearest head of another snake
+    pub chases_heads: bool,
+    /// Ends up on the edge of the board
+    pub hugs_walls: bool,
+    /// Ends up closer to our own tail
+    pub chases_tail: bool,
+}
+
+impl MoveFeatures {
+    pub fn has(&self, tendency: Tendency) -> bool {
+        match tendency {
+            Tendency::SeeksFood => self.seeks_food,
+            Tendency::ChasesHeads => self.chases_heads,
+            Tendency::HugsWalls => self.hugs_walls,
+            Tendency::ChasesTail => self.chases_tail,
+        }
+    }
+}
+
+/// What moving `snake_id` onto `destination` would do
+pub fn move_features<T>(
+    node: &T,
+    snake_id: &T::SnakeIDType,
+    destination: &T::NativePositionType,
+) -> MoveFeatures
+where
+    T: SnakeIDGettableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + FoodGettableGame
+        + SnakeBodyGettableGame
+        + NeighborDeterminableGame,
+{
+    let head = node.get_head_as_native_position(snake_id);
+    let nearest = |from: &T::NativePositionType, targets: &[T::NativePositionType]| {
+        targets
+            .iter()
+            .map(|target| dist_between_new(node, from, target))
+            .min()
+    };
+    let closer_to = |targets: &[T::NativePositionType]| match (
+        nearest(&head, targets),
+        nearest(destination, targets),
+    ) {
+        (Some(before), Some(after)) => after < before,
+        _ => false,
+    };
+
+    let food = node.get_all_food_as_native_positions();
+    let heads: Vec<_> = node
+        .get_snake_ids()
+        .into_iter()
+        .filter(|s| s != snake_id && node.is_alive(s))
+        .map(|s| node.get_head_as_native_position(&s))
+        .collect();
+    let tail: Vec<_> = node
+        .get_snake_body_vec(snake_id)
+        .last()
+        .cloned()
+        .into_iter()
+        .collect();
+
+    MoveFeatures {
+        seeks_food: closer_to(&food),
+        chases_heads: closer_to(&heads),
+        // Off board neighbors aren't returned, so cells on the edge have fewer of them
+        hugs_walls: node.neighbors(destination).len() < 4,
+        chases_tail: closer_to(&tail),
+    }
+}
+
+/// How one opponent has played so far this game
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tendencies {
+    observed: u32,
+    /// How often the snake had a choice between moves with and without each tendency
+    chances: [u32; 4],
+    /// How many of those times it took a move with the tendency
+    followed: [u32; 4],
+}
+
+impl Tendencies {
+    /// How many of this snake's moves we have learned from
+    pub fn observed(&self) -> u32 {
+        self.observed
+    }
+
+    /// How likely the snake is to pick a move with `tendency` when it has the choice. This
+    /// starts at even odds and moves towards what we have seen it do
+    pub fn rate(&self, tendency: Tendency) -> f64 {
+        let i = tendency as usize;
+
+        (self.followed[i] as f64 + 1.0) / (self.chances[i] as f64 + 2.0)
+    }
+
+    /// Turns where every option shares a tendency don't tell us anything about it
+    fn record(&mut self, options: &[MoveFeatures], chosen: &MoveFeatures) {
+        self.observed += 1;
+
+        for tendency in ALL_TENDENCIES.iter() {
+            let with = options.iter().filter(|f| f.has(*tendency)).count();
+            if with == 0 || with == options.len() {
+                continue;
+            }
+
+            let i = *tendency as usize;
+            self.chances[i] += 1;
+            if chosen.has(*tendency) {
+                self.followed[i] += 1;
+            }
+        }
+    }
+
+    /// How likely the snake is to make each of its moves on this board. The chances add up to
+    /// one
+    pub fn move_probabilities<T>(&self, node: &T, snake_id: &T::SnakeIDType) -> Vec<(Move, f64)>
+    where
+        T: SnakeIDGettableGame
+            + PositionGettableGame
+            + HeadGettableGame
+            + HealthGettableGame
+            + FoodGettableGame
+            + SnakeBodyGettableGame
+            + NeighborDeterminableGame,
+    {
+        let weights: Vec<_> = node
+            .possible_moves(&node.get_head_as_native_position(snake_id))
+            .into_iter()
+            .map(|(dir, destination)| {
+                if node.position_is_snake_body(destination.clone()) {
+                    return (dir, BODY_WEIGHT);
+                }
+
+                let features = move_features(node, snake_id, &destination);
+                let weight = ALL_TENDENCIES
+                    .iter()
+                    .map(|t| {
+                        if features.has(*t) {
+                            self.rate(*t)
+                        } else {
+                            1.0 - self.rate(*t)
+                        }
+                    })
+                    .product();
+
+                (dir, weight)
+            })
+            .collect();
+        let total: f64 = weights.iter().map(|(_, w)| w).sum();
+
+        weights
+            .into_iter()
+            .map(|(dir, w)| (dir, w / total))
+            .collect()
+    }
+
+    /// The moves worth searching, most likely first. Once we have seen enough of the snake the
+    /// ones it is unlikely to make are dropped, but we always keep at least one
+    pub fn order_moves<T>(
+        &self,
+        node: &T,
+        snake_id: &T::SnakeIDType,
+        possible_moves: Vec<(Move, T::NativePositionType)>,
+    ) -> Vec<(Move, T::NativePositionType)>
+    where
+        T: SnakeIDGettableGame
+            + PositionGettableGame
+            + HeadGettableGame
+            + HealthGettableGame
+            + FoodGettableGame
+            + SnakeBodyGettableGame
+            + NeighborDeterminableGame,
+    {
+        let probabilities = self.move_probabilities(node, snake_id);
+        let probability = |dir: &Move| {
+            probabilities
+                .iter()
+                .find(|(m, _)| m == dir)
+                .map_or(0.0, |(_, p)| *p)
+        };
+
+        let mut ranked: Vec<_> = possible_moves
+            .into_iter()
+            .map(|m| (probability(&m.0), m))
+            .collect();
+        ranked.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+
+        let prune = self.observed >= MIN_OBSERVATIONS_TO_PRUNE;
+        ranked
+            .into_iter()
+            .enumerate()
+            .filter(|(i, (p, _))| *i == 0 || !prune || *p >= PRUNE_BELOW)
+            .map(|(_, (_, m))| m)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl Tendencies {
+    /// A snake we have watched go for `tendency` every one of `observed` times it could
+    pub(crate) fn always(tendency: Tendency, observed: u32) -> Self {
+        let mut tendencies = Self {
+            observed,
+            ..Self::default()
+        };
+        tendencies.chances[tendency as usize] = observed;
+        tendencies.followed[tendency as usize] = observed;
+
+        tendencies
+    }
+}
+
+/// The move each opponent made between two boards, for the ones still on both of them
+pub fn inferred_moves(before: &Game, after: &Game) -> Vec<(String, Move)> {
+    after
+        .board
+        .snakes
+        .iter()
+        .filter(|snake| snake.id != after.you.id)
+        .filter(|snake| before.board.snakes.iter().any(|s| s.id == snake.id))
+        .filter_map(|snake| {
+            before
+                .possible_moves(&before.get_head_as_native_position(&snake.id))
+                .into_iter()
+                .find(|(_, destination)| *destination == snake.head)
+                .map(|(dir, _)| (snake.id.clone(), dir))
+        })
+        .collect()
+}
+
+/// What we have learned about the opponents in one game, from watching the boards we get sent
+/// each turn
+#[derive(Debug, Clone, Default)]
+pub struct OpponentModel {
+    snakes: HashMap<String, Tendencies>,
+    last_seen: Option<Game>,
+}
+
+impl OpponentModel {
+    /// Learns from whatever every opponent did since the last board we saw. When a turn was
+    /// skipped we can't tell what happened, so that board is only remembered for next time
+    pub fn observe(&mut self, game: &Game) {
+        if let Some(before) = self.last_seen.take() {
+            if before.game.id == game.game.id && before.turn + 1 == game.turn {
+                for (snake_id, dir) in inferred_moves(&before, game) {
+                    let options: Vec<_> = before
+                        .possible_moves(&before.get_head_as_native_position(&snake_id))
+                        .into_iter()
+                        .map(|(m, destination)| {
+                            (m, move_features(&before, &snake_id, &destination))
+                        })
+                        .collect();
+                    let chosen = match options.iter().find(|(m, _)| *m == dir) {
+                        Some((_, features)) => *features,
+                        None => continue,
+                    };
+                    let features: Vec<_> = options.iter().map(|(_, f)| *f).collect();
+
+                    self.snakes
+                        .entry(snake_id)
+                        .or_default()
+                        .record(&features, &chosen);
+                }
+            }
+        }
+
+        self.last_seen = Some(game.clone());
+    }
+
+    pub fn tendencies(&self, snake_id: &str) -> Option<&Tendencies> {
+        self.snakes.get(snake_id)
+    }
+
+    /// Snakes we haven't seen move yet get even odds for every tendency
+    pub fn move_probabilities(&self, game: &Game, snake_id: &str) -> Vec<(Move, f64)> {
+        self.tendencies(snake_id)
+            .cloned()
+            .unwrap_or_default()
+            .move_probabilities(game, &snake_id.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{moved, start_of_game};
+
+    #[test]
+    fn test_infers_the_moves_opponents_made() {
+        let before = start_of_game();
+        let after = moved(&before, "#FF6444", Move::Left);
+
+        assert_eq!(
+            inferred_moves(&before, &after),
+            vec![("#FF6444".to_owned(), Move::Left)]
+        );
+    }
+
+    #[test]
+    fn test_learns_which_moves_an_opponent_likes() {
+        let before = start_of_game();
+        // Towards the food at (0, 2), and away from the wall Left and Down would hug
+        let after = moved(&before, "#FF6444", Move::Up);
+        let mut model = OpponentModel::default();
+
+        for _ in 0..MIN_OBSERVATIONS_TO_PRUNE {
+            model.observe(&before);
+            model.observe(&after);
+        }
+
+        let tendencies = model.tendencies("#FF6444").unwrap();
+        assert_eq!(tendencies.observed(), MIN_OBSERVATIONS_TO_PRUNE);
+        assert!(tendencies.rate(Tendency::SeeksFood) > 0.9);
+        assert!(model.tendencies("#FF6c96").is_none());
+
+        let probabilities = model.move_probabilities(&before, "#FF6444");
+        let total: f64 = probabilities.iter().map(|(_, p)| p).sum();
+        let most_likely = probabilities
+            .iter()
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .unwrap();
+
+        assert!((total - 1.0).abs() < 1e-9);
+        assert_eq!(most_likely.0, Move::Up);
+    }
+}
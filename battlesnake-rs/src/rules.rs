@@ -0,0 +1,137 @@
+use serde_json::Value;
+
+pub const WRAPPED_RULESET_NAME: &str = "wrapped";
+
+/// The `game.ruleset.settings` a game was started with
+///
+/// The wire `Game` only keeps the ruleset's name and version, so these are parsed separately
+/// from the raw request body. Anything missing falls back to the engine's defaults
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Rules {
+    /// Percent chance that a new piece of food spawns each turn
+    pub food_spawn_chance: i32,
+    /// Food spawns until there is at least this much on the board
+    pub minimum_food: i32,
+    /// Extra health lost by a snake whose head is in a hazard, on top of the usual point a turn
+    pub hazard_damage_per_turn: i32,
+    pub royale: RoyaleSettings,
+    pub squad: SquadSettings,
+    /// Moving off an edge of the board brings you back on the opposite edge. This comes from
+    /// the ruleset's name rather than its settings
+    #[serde(skip)]
+    pub wrapped: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RoyaleSettings {
+    /// How often the hazards move in by another row or column
+    pub shrink_every_n_turns: i32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SquadSettings {
+    pub allow_body_collisions: bool,
+    pub shared_elimination: bool,
+    pub shared_health: bool,
+    pub shared_length: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            food_spawn_chance: 15,
+            minimum_food: 1,
+            hazard_damage_per_turn: 14,
+            royale: RoyaleSettings::default(),
+            squad: SquadSettings::default(),
+            wrapped: false,
+        }
+    }
+}
+
+impl Default for RoyaleSettings {
+    fn default() -> Self {
+        Self {
+            shrink_every_n_turns: 25,
+        }
+    }
+}
+
+impl Rules {
+    /// Reads the settings out of a request body, using the defaults if they aren't there or
+    /// don't look like we expect
+    pub fn from_request_body(body: &Value) -> Self {
+        let mut rules: Self = body
+            .pointer("/game/ruleset/settings")
+            .and_then(|settings| serde_json::from_value(settings.clone()).ok())
+            .unwrap_or_default();
+        rules.wrapped = body.pointer("/game/ruleset/name").and_then(Value::as_str)
+            == Some(WRAPPED_RULESET_NAME);
+
+        rules
+    }
+
+    /// Whether the compact boards' own simulation plays by these rules. They always take the
+    /// default hazard damage, so boards with hazards need the wire game's `MoveableGame` otherwise
+    pub fn simulated_by_compact_boards(&self) -> bool {
+        self.hazard_damage_per_turn == Self::default().hazard_damage_per_turn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_royale_settings_are_parsed() {
+        let body: Value = serde_json::from_str(
+            r#"{"game":{"id":"","ruleset":{"name":"royale","version":"v1.0.22","settings":{"foodSpawnChance":25,"minimumFood":2,"hazardDamagePerTurn":20,"royale":{"shrinkEveryNTurns":10},"squad":{"allowBodyCollisions":true,"sharedElimination":false,"sharedHealth":true,"sharedLength":false}}},"timeout":500}}"#,
+        )
+        .unwrap();
+        let rules = Rules::from_request_body(&body);
+
+        assert_eq!(rules.food_spawn_chance, 25);
+        assert_eq!(rules.minimum_food, 2);
+        assert_eq!(rules.hazard_damage_per_turn, 20);
+        assert_eq!(rules.royale.shrink_every_n_turns, 10);
+        assert!(rules.squad.allow_body_collisions);
+        assert!(!rules.squad.shared_elimination);
+        assert!(rules.squad.shared_health);
+        assert!(!rules.squad.shared_length);
+        assert!(!rules.wrapped);
+        assert!(!rules.simulated_by_compact_boards());
+    }
+
+    #[test]
+    fn test_wrapped_comes_from_the_ruleset_name() {
+        let body: Value = serde_json::from_str(
+            r#"{"game":{"id":"","ruleset":{"name":"wrapped","version":"v1.0.22","settings":{}},"timeout":500}}"#,
+        )
+        .unwrap();
+
+        assert!(Rules::from_request_body(&body).wrapped);
+    }
+
+    #[test]
+    fn test_missing_settings_use_the_defaults() {
+        let body: Value = serde_json::from_str(
+            r#"{"game":{"id":"","ruleset":{"name":"standard","version":"v1.0.17","settings":{"minimumFood":3}},"timeout":500}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Rules::from_request_body(&body),
+            Rules {
+                minimum_food: 3,
+                ..Rules::default()
+            }
+        );
+        assert_eq!(
+            Rules::from_request_body(&serde_json::json!({})),
+            Rules::default()
+        );
+    }
+}
@@ -0,0 +1,426 @@
+use battlesnake_game_types::compact_representation::{CellBoard, CellNum};
+use battlesnake_game_types::types::*;
+use battlesnake_game_types::wire_representation::Game;
+
+use crate::a_prime::wrapped_destinations;
+use crate::rules::Rules;
+use crate::ALL_MOVES;
+
+use rustc_hash::FxHashSet;
+
+/// Whether the last piece of `body` is out of the way next turn. A snake that just ate has its
+/// tail stacked on the piece in front of it, so that piece stays put for a turn
+pub fn tail_moves<P: PartialEq>(body: &[P]) -> bool {
+    body.len() > 1 && body[body.len() - 1] != body[body.len() - 2]
+}
+
+/// Boards that know which cells are hazards
+pub trait HazardQueryable: PositionGettableGame {
+    fn is_hazard(&self, cell: &Self::NativePositionType) -> bool;
+}
+
+impl HazardQueryable for Game {
+    fn is_hazard(&self, cell: &Self::NativePositionType) -> bool {
+        self.board.hazards.contains(cell)
+    }
+}
+
+impl<T: CellNum, const BOARD_SIZE: usize, const MAX_SNAKES: usize> HazardQueryable
+    for CellBoard<T, BOARD_SIZE, MAX_SNAKES>
+{
+    fn is_hazard(&self, cell: &Self::NativePositionType) -> bool {
+        self.cell_is_hazard(*cell)
+    }
+}
+
+/// How a move can go wrong on the very next turn, worst first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MoveSafety {
+    /// Off the board, into a body that won't have moved, or out of health
+    CertainDeath,
+    /// Next to the head of a snake at least as long as us, which dies with us or kills us if
+    /// it moves onto the same cell
+    LosingHeadToHead,
+    Safe,
+    /// Next to the head of a shorter snake, which dies if it moves onto the same cell
+    WinningHeadToHead,
+}
+
+impl MoveSafety {
+    /// Whether we live through the next turn no matter what the opponents do
+    pub fn is_safe(&self) -> bool {
+        matches!(self, MoveSafety::Safe | MoveSafety::WinningHeadToHead)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveAnalysis {
+    pub direction: Move,
+    pub safety: MoveSafety,
+    /// Cells we can get to after making the move, counting the one we move onto. Tails that
+    /// are about to move count as free
+    pub reachable_area: usize,
+}
+
+impl MoveAnalysis {
+    /// What we compare moves by. Living comes first, then having room for our whole body,
+    /// then dodging head to heads. Winning a head to head is no better than not having one,
+    /// since the other snake can always just not take it
+    fn tier(&self, length: usize) -> (bool, bool, bool) {
+        (
+            self.safety != MoveSafety::CertainDeath,
+            self.reachable_area >= length,
+            self.safety.is_safe(),
+        )
+    }
+}
+
+fn on_board<T>(game: &T, cell: &T::NativePositionType) -> bool
+where
+    T: PositionGettableGame + SizeDeterminableGame,
+{
+    let p = game.position_from_native(cell.clone());
+
+    p.x >= 0 && p.y >= 0 && (p.x as u32) < game.get_width() && (p.y as u32) < game.get_height()
+}
+
+/// The moves from `cell` that stay on the board, with the cell each lands on. On a wrapped
+/// board moving off an edge lands on the opposite one
+fn destinations<T>(
+    game: &T,
+    cell: &T::NativePositionType,
+    wrapped: bool,
+) -> Vec<(Move, T::NativePositionType)>
+where
+    T: PositionGettableGame + NeighborDeterminableGame + SizeDeterminableGame,
+{
+    if wrapped {
+        let (width, height) = (game.get_width() as i32, game.get_height() as i32);
+        return wrapped_destinations(&game.position_from_native(cell.clone()), width, height)
+            .into_iter()
+            .map(|(m, p)| (m, game.native_from_position(p)))
+            .collect();
+    }
+
+    game.possible_moves(cell)
+        .into_iter()
+        .filter(|(_, c)| on_board(game, c))
+        .collect()
+}
+
+/// The cells we can get to from `start` without going through anything `blocked`
+fn reachable_area<T>(
+    game: &T,
+    start: &T::NativePositionType,
+    blocked: &FxHashSet<T::NativePositionType>,
+    wrapped: bool,
+) -> usize
+where
+    T: PositionGettableGame + NeighborDeterminableGame + SizeDeterminableGame,
+{
+    let mut seen = FxHashSet::default();
+    seen.insert(start.clone());
+    let mut frontier = vec![start.clone()];
+
+    while let Some(cell) = frontier.pop() {
+        for (_, neighbor) in destinations(game, &cell, wrapped) {
+            if !blocked.contains(&neighbor) && seen.insert(neighbor.clone()) {
+                frontier.push(neighbor);
+            }
+        }
+    }
+
+    seen.len()
+}
+
+/// How safe each of one snake's four moves is on the next turn
+///
+/// This only looks one turn ahead, so it is cheap enough for any snake to run every turn
+/// before trusting whatever move it came up with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SafetyAnalysis {
+    moves: Vec<MoveAnalysis>,
+    length: usize,
+}
+
+impl SafetyAnalysis {
+    pub fn new<T>(game: &T, snake_id: &T::SnakeIDType, rules: &Rules) -> Self
+    where
+        T: SnakeIDGettableGame
+            + PositionGettableGame
+            + HeadGettableGame
+            + HealthGettableGame
+            + LengthGettableGame
+            + SnakeBodyGettableGame
+            + FoodGettableGame
+            + NeighborDeterminableGame
+            + SizeDeterminableGame
+            + HazardQueryable,
+    {
+        let head = game.get_head_as_native_position(snake_id);
+        let length = game.get_length_i64(snake_id).max(0) as usize;
+        let health = game.get_health_i64(snake_id);
+        let food = game.get_all_food_as_native_positions();
+
+        let others: Vec<_> = game
+            .get_snake_ids()
+            .into_iter()
+            .filter(|s| s != snake_id && game.is_alive(s))
+            .map(|s| {
+                (
+                    game.get_head_as_native_position(&s),
+                    game.get_length_i64(&s).max(0) as usize,
+                )
+            })
+            .collect();
+
+        // Tails move out of the way, unless the snake just ate and grew onto its own tail
+        let mut blocked = FxHashSet::default();
+        for s in game
+            .get_snake_ids()
+            .into_iter()
+            .filter(|s| game.is_alive(s))
+        {
+            let body = game.get_snake_body_vec(&s);
+            let kept = if tail_moves(&body) {
+                body.len() - 1
+            } else {
+                body.len()
+            };
+            blocked.extend(body.into_iter().take(kept));
+        }
+
+        let destinations = destinations(game, &head, rules.wrapped);
+
+        let moves = ALL_MOVES
+            .iter()
+            .map(|dir| {
+                let cell = match destinations.iter().find(|(m, _)| m == dir) {
+                    Some((_, cell)) => cell,
+                    None => {
+                        return MoveAnalysis {
+                            direction: *dir,
+                            safety: MoveSafety::CertainDeath,
+                            reachable_area: 0,
+                        }
+                    }
+                };
+
+                let damage = if food.contains(cell) {
+                    0
+                } else if game.is_hazard(cell) {
+                    1 + rules.hazard_damage_per_turn as i64
+                } else {
+                    1
+                };
+                let neighbors = destinations(game, cell, rules.wrapped);
+                let rival_lengths: Vec<_> = others
+                    .iter()
+                    .filter(|(other_head, _)| neighbors.iter().any(|(_, n)| n == other_head))
+                    .map(|(_, other_length)| *other_length)
+                    .collect();
+
+                let safety = if blocked.contains(cell) || health - damage <= 0 {
+                    MoveSafety::CertainDeath
+                } else if rival_lengths.iter().any(|l| *l >= length) {
+                    MoveSafety::LosingHeadToHead
+                } else if !rival_lengths.is_empty() {
+                    MoveSafety::WinningHeadToHead
+                } else {
+                    MoveSafety::Safe
+                };
+
+                MoveAnalysis {
+                    direction: *dir,
+                    safety,
+                    reachable_area: if blocked.contains(cell) {
+                        0
+                    } else {
+                        reachable_area(game, cell, &blocked, rules.wrapped)
+                    },
+                }
+            })
+            .collect();
+
+        Self { moves, length }
+    }
+
+    /// The analysis for the snake we are playing as
+    pub fn for_you<T>(game: &T, rules: &Rules) -> Self
+    where
+        T: SnakeIDGettableGame
+            + YouDeterminableGame
+            + PositionGettableGame
+            + HeadGettableGame
+            + HealthGettableGame
+            + LengthGettableGame
+            + SnakeBodyGettableGame
+            + FoodGettableGame
+            + NeighborDeterminableGame
+            + SizeDeterminableGame
+            + HazardQueryable,
+    {
+        Self::new(game, game.you_id(), rules)
+    }
+
+    /// All four moves, in the order up, down, left, right
+    pub fn moves(&self) -> &[MoveAnalysis] {
+        &self.moves
+    }
+
+    pub fn get(&self, direction: Move) -> &MoveAnalysis {
+        self.moves
+            .iter()
+            .find(|m| m.direction == direction)
+            .expect("Every move gets analysed")
+    }
+
+    /// The safest move, with the most room to break ties
+    pub fn best_move(&self) -> Move {
+        self.moves
+            .iter()
+            .max_by_key(|m| (m.tier(self.length), m.reachable_area))
+            .map(|m| m.direction)
+            .expect("Every move gets analysed")
+    }
+
+    /// The `candidates` that are as safe as any of them can be, in the order they were given
+    pub fn filter_moves(&self, candidates: &[Move]) -> Vec<Move> {
+        let best_tier = candidates
+            .iter()
+            .map(|m| self.get(*m).tier(self.length))
+            .max();
+
+        candidates
+            .iter()
+            .copied()
+            .filter(|m| Some(self.get(*m).tier(self.length)) == best_tier)
+            .collect()
+    }
+
+    /// Keeps `preferred` unless some other move is in a safer tier
+    pub fn choose(&self, preferred: Move) -> Move {
+        let best = self.best_move();
+
+        if self.get(best).tier(self.length) > self.get(preferred).tier(self.length) {
+            best
+        } else {
+            preferred
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(you: &str, other: &str, food: &str, hazards: &str) -> Game {
+        serde_json::from_str(&format!(
+            r#"{{"game":{{"id":"","ruleset":{{"name":"standard","version":"v1.0.17"}},"timeout":500}},"turn":10,"board":{{"height":11,"width":11,"food":[{}],"hazards":[{}],"snakes":[{},{}]}},"you":{}}}"#,
+            food, hazards, you, other, you
+        ))
+        .unwrap()
+    }
+
+    fn snake(id: &str, health: i32, body: &str) -> String {
+        format!(
+            r#"{{"id":"{}","name":"","health":{},"body":[{}],"latency":0,"head":{},"length":{},"shout":""}}"#,
+            id,
+            health,
+            body,
+            body.split('}').next().unwrap().to_owned() + "}",
+            body.matches('{').count()
+        )
+    }
+
+    #[test]
+    fn test_classifies_every_move() {
+        // We are in the bottom left corner with our body going right. The other snake is the
+        // same length and sitting just above where moving up would take us
+        let you = snake("you", 90, r#"{"x":0,"y":0},{"x":1,"y":0},{"x":2,"y":0}"#);
+        let other = snake("other", 90, r#"{"x":0,"y":2},{"x":0,"y":3},{"x":0,"y":4}"#);
+        let analysis = SafetyAnalysis::for_you(&game(&you, &other, "", ""), &Rules::default());
+
+        assert_eq!(analysis.get(Move::Left).safety, MoveSafety::CertainDeath);
+        assert_eq!(analysis.get(Move::Down).safety, MoveSafety::CertainDeath);
+        assert_eq!(analysis.get(Move::Right).safety, MoveSafety::CertainDeath);
+        assert_eq!(analysis.get(Move::Up).safety, MoveSafety::LosingHeadToHead);
+        assert!(analysis.get(Move::Up).reachable_area > 100);
+        assert_eq!(analysis.best_move(), Move::Up);
+    }
+
+    #[test]
+    fn test_tails_move_and_shorter_snakes_lose() {
+        // Our tail is next to our head, so we can follow it around. The other snake is
+        // shorter than us and right next to where following our tail takes us
+        let you = snake(
+            "you",
+            90,
+            r#"{"x":5,"y":5},{"x":5,"y":6},{"x":6,"y":6},{"x":6,"y":5}"#,
+        );
+        let other = snake("other", 90, r#"{"x":7,"y":5},{"x":8,"y":5}"#);
+        let analysis = SafetyAnalysis::for_you(&game(&you, &other, "", ""), &Rules::default());
+
+        assert_eq!(
+            analysis.get(Move::Right).safety,
+            MoveSafety::WinningHeadToHead
+        );
+        assert_eq!(analysis.get(Move::Up).safety, MoveSafety::CertainDeath);
+        assert_eq!(analysis.get(Move::Left).safety, MoveSafety::Safe);
+        assert_eq!(
+            analysis.filter_moves(&[Move::Up, Move::Right, Move::Left]),
+            vec![Move::Right, Move::Left]
+        );
+        assert_eq!(analysis.choose(Move::Up), analysis.best_move());
+        assert_eq!(analysis.choose(Move::Right), Move::Right);
+    }
+
+    #[test]
+    fn test_hazards_can_starve_us() {
+        let you = snake("you", 10, r#"{"x":5,"y":5},{"x":5,"y":4},{"x":5,"y":3}"#);
+        let other = snake("other", 90, r#"{"x":0,"y":0}"#);
+        let hazards = r#"{"x":5,"y":6},{"x":4,"y":5}"#;
+        let food = r#"{"x":4,"y":5}"#;
+        let analysis =
+            SafetyAnalysis::for_you(&game(&you, &other, food, hazards), &Rules::default());
+
+        assert_eq!(analysis.get(Move::Up).safety, MoveSafety::CertainDeath);
+        // Eating in a hazard fills us back up
+        assert_eq!(analysis.get(Move::Left).safety, MoveSafety::Safe);
+        assert_eq!(analysis.get(Move::Right).safety, MoveSafety::Safe);
+    }
+
+    #[test]
+    fn test_wrapped_boards_let_us_cross_the_edge() {
+        // Our own body is above and to the right of us and the other snake's body is below, so
+        // the only way out is off the left edge
+        let you = snake(
+            "you",
+            90,
+            r#"{"x":0,"y":5},{"x":1,"y":5},{"x":1,"y":6},{"x":0,"y":6},{"x":0,"y":7}"#,
+        );
+        let other = snake(
+            "other",
+            90,
+            r#"{"x":2,"y":4},{"x":1,"y":4},{"x":0,"y":4},{"x":0,"y":3}"#,
+        );
+        let game = game(&you, &other, "", "");
+
+        let standard = SafetyAnalysis::for_you(&game, &Rules::default());
+        assert_eq!(standard.get(Move::Left).safety, MoveSafety::CertainDeath);
+
+        let wrapped = SafetyAnalysis::for_you(
+            &game,
+            &Rules {
+                wrapped: true,
+                ..Rules::default()
+            },
+        );
+        assert_eq!(wrapped.get(Move::Left).safety, MoveSafety::Safe);
+        assert!(wrapped.get(Move::Left).reachable_area > 100);
+        assert_eq!(wrapped.get(Move::Up).safety, MoveSafety::CertainDeath);
+        assert_eq!(wrapped.get(Move::Down).safety, MoveSafety::CertainDeath);
+        assert_eq!(wrapped.get(Move::Right).safety, MoveSafety::CertainDeath);
+        assert_eq!(wrapped.best_move(), Move::Left);
+    }
+}
@@ -0,0 +1,236 @@
+use battlesnake_game_types::wire_representation::Game;
+
+use crate::rules::Rules;
+use crate::safety::SafetyAnalysis;
+use crate::{
+    AboutMe, BattlesnakeAI, BattlesnakeFactory, BoxedFactory, BoxedSnake, GameContext, MoveOutput,
+};
+
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use tracing::warn;
+
+/// Left over from the engine's timeout for the response to make it back. The snakes we wrap
+/// leave themselves more room than this, so we only step in once they have really run over
+const NETWORK_LATENCY_PADDING: Duration = Duration::from_millis(50);
+
+type BuildSnake = Arc<dyn Fn() -> BoxedSnake + Send + Sync>;
+
+/// Runs a snake on its own thread and makes sure we always answer with a real move
+///
+/// Panics, errors, moves the engine wouldn't understand and running past `deadline` all fall
+/// back to the safest move we can find without searching. A snake that runs out of time keeps
+/// going in the background, we just stop waiting for it
+pub struct SafetyNet {
+    name: String,
+    game: Game,
+    rules: Rules,
+    deadline: Duration,
+    build: BuildSnake,
+}
+
+impl SafetyNet {
+    /// `build` is called on the worker thread, so panics while setting the snake up are caught
+    /// too
+    pub fn new(
+        name: String,
+        game: Game,
+        rules: Rules,
+        deadline: Duration,
+        build: impl Fn() -> BoxedSnake + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name,
+            game,
+            rules,
+            deadline,
+            build: Arc::new(build),
+        }
+    }
+
+    /// Whatever the snake answered with, or why we couldn't use it
+    fn run_snake(&self) -> Result<MoveOutput, String> {
+        let build = self.build.clone();
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let result = catch_unwind(AssertUnwindSafe(|| build().make_move()));
+            // Nobody is listening anymore if we ran out of time
+            let _ = tx.send(result);
+        });
+
+        match rx.recv_timeout(self.deadline) {
+            Ok(Ok(Ok(output))) if output.direction().is_some() => Ok(output),
+            Ok(Ok(Ok(output))) => Err(format!("not a move: {:?}", output.r#move)),
+            Ok(Ok(Err(e))) => Err(format!("error: {}", e)),
+            Ok(Err(payload)) => Err(format!("panic: {}", panic_message(&*payload))),
+            Err(mpsc::RecvTimeoutError::Timeout) => Err("ran out of time".to_owned()),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err("worker went away".to_owned()),
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+        .unwrap_or("unknown")
+}
+
+impl BattlesnakeAI for SafetyNet {
+    fn make_move(&self) -> Result<MoveOutput, Box<dyn std::error::Error + Send + Sync>> {
+        match self.run_snake() {
+            Ok(output) => Ok(output),
+            Err(reason) => {
+                let direction = SafetyAnalysis::for_you(&self.game, &self.rules).best_move();
+                warn!(snake = %self.name, game_id = %self.game.game.id, turn = self.game.turn, reason = %reason, direction = ?direction, "Falling back to a safe move");
+
+                Ok(MoveOutput {
+                    r#move: format!("{}", direction),
+                    shout: None,
+                })
+            }
+        }
+    }
+}
+
+/// Wraps every snake a factory makes in a `SafetyNet`, with a deadline from the game's timeout
+pub struct SafetyNetFactory {
+    inner: Arc<BoxedFactory>,
+}
+
+impl SafetyNetFactory {
+    pub fn wrap(inner: BoxedFactory) -> BoxedFactory {
+        Box::new(Self {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// The hooks don't answer with a move, so all we can do is keep a panic from taking the
+    /// request down with it
+    fn guard(&self, hook: &str, game: &Game, f: impl FnOnce()) {
+        if let Err(payload) = catch_unwind(AssertUnwindSafe(f)) {
+            warn!(snake = %self.inner.name(), game_id = %game.game.id, hook = %hook, reason = %panic_message(&*payload), "Hook panicked");
+        }
+    }
+}
+
+impl BattlesnakeFactory for SafetyNetFactory {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn from_wire_game(&self, game: Game) -> BoxedSnake {
+        self.from_wire_game_with_context(game, GameContext::default())
+    }
+
+    fn from_wire_game_with_context(&self, game: Game, context: GameContext) -> BoxedSnake {
+        let timeout = Duration::from_millis(game.game.timeout.max(0) as u64);
+        let deadline = timeout
+            .checked_sub(NETWORK_LATENCY_PADDING)
+            .unwrap_or(timeout);
+
+        let inner = self.inner.clone();
+        let inner_game = game.clone();
+        let rules = context.rules;
+
+        Box::new(SafetyNet::new(
+            self.name(),
+            game,
+            rules,
+            deadline,
+            move || inner.from_wire_game_with_context(inner_game.clone(), context.clone()),
+        ))
+    }
+
+    fn start(&self, game: &Game, context: &GameContext) {
+        self.guard("start", game, || self.inner.start(game, context));
+    }
+
+    fn end(&self, game: &Game, context: &GameContext) {
+        self.guard("end", game, || self.inner.end(game, context));
+    }
+
+    fn about(&self) -> AboutMe {
+        self.inner.about()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battlesnake_game_types::types::Move;
+
+    /// Cornered at (0, 0) with our body off to the right, so up is the only move that doesn't
+    /// kill us
+    fn cornered() -> Game {
+        serde_json::from_str(r#"{"game":{"id":"","ruleset":{"name":"standard","version":"v1.0.17"},"timeout":500},"turn":10,"board":{"height":11,"width":11,"food":[],"hazards":[],"snakes":[{"id":"you","name":"","health":90,"body":[{"x":0,"y":0},{"x":1,"y":0},{"x":2,"y":0}],"latency":0,"head":{"x":0,"y":0},"length":3,"shout":""}]},"you":{"id":"you","name":"","health":90,"body":[{"x":0,"y":0},{"x":1,"y":0},{"x":2,"y":0}],"latency":0,"head":{"x":0,"y":0},"length":3,"shout":""}}"#).unwrap()
+    }
+
+    struct Fixed(Result<&'static str, &'static str>);
+
+    impl BattlesnakeAI for Fixed {
+        fn make_move(&self) -> Result<MoveOutput, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(MoveOutput {
+                r#move: self.0?.to_owned(),
+                shout: None,
+            })
+        }
+    }
+
+    struct Panics;
+
+    impl BattlesnakeAI for Panics {
+        fn make_move(&self) -> Result<MoveOutput, Box<dyn std::error::Error + Send + Sync>> {
+            panic!("TODO")
+        }
+    }
+
+    struct Slow;
+
+    impl BattlesnakeAI for Slow {
+        fn make_move(&self) -> Result<MoveOutput, Box<dyn std::error::Error + Send + Sync>> {
+            thread::sleep(Duration::from_secs(5));
+
+            Ok(MoveOutput {
+                r#move: "right".to_owned(),
+                shout: None,
+            })
+        }
+    }
+
+    fn chosen(build: impl Fn() -> BoxedSnake + Send + Sync + 'static) -> Option<Move> {
+        SafetyNet::new(
+            "test".to_owned(),
+            cornered(),
+            Rules::default(),
+            Duration::from_millis(50),
+            build,
+        )
+        .make_move()
+        .unwrap()
+        .direction()
+    }
+
+    #[test]
+    fn test_passes_good_moves_through() {
+        assert_eq!(chosen(|| Box::new(Fixed(Ok("right")))), Some(Move::Right));
+    }
+
+    #[test]
+    fn test_falls_back_to_a_safe_move() {
+        assert_eq!(chosen(|| Box::new(Fixed(Err("no moves")))), Some(Move::Up));
+        assert_eq!(chosen(|| Box::new(Fixed(Ok("TODO")))), Some(Move::Up));
+        assert_eq!(chosen(|| Box::new(Panics)), Some(Move::Up));
+        assert_eq!(
+            chosen(|| panic!("couldn't build the snake")),
+            Some(Move::Up)
+        );
+        assert_eq!(chosen(|| Box::new(Slow)), Some(Move::Up));
+    }
+}
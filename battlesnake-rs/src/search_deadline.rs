@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Shared between the thread that answers the request and the thread doing the search
+///
+/// The search checks this at every node so that it can stop shortly after the deadline, or as
+/// soon as the answering thread no longer needs any more results, instead of finishing a
+/// depth that nobody is going to read
+#[derive(Debug, Clone)]
+pub struct SearchDeadline {
+    ends_at: Option<Instant>,
+    aborted: Arc<AtomicBool>,
+}
+
+impl SearchDeadline {
+    pub fn new(max_duration: Duration) -> Self {
+        Self {
+            ends_at: Some(Instant::now() + max_duration),
+            aborted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// A deadline that only expires when it is explicitly aborted
+    pub fn unbounded() -> Self {
+        Self {
+            ends_at: None,
+            aborted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn abort(&self) {
+        self.aborted.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_expired(&self) -> bool {
+        if self.aborted.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        self.ends_at
+            .map_or(false, |ends_at| Instant::now() >= ends_at)
+    }
+
+    /// Time left before the deadline, `None` if this deadline is unbounded
+    pub fn remaining(&self) -> Option<Duration> {
+        self.ends_at
+            .map(|ends_at| ends_at.saturating_duration_since(Instant::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abort_is_shared_between_clones() {
+        let deadline = SearchDeadline::unbounded();
+        let worker = deadline.clone();
+
+        assert!(!worker.is_expired());
+        deadline.abort();
+        assert!(worker.is_expired());
+    }
+
+    #[test]
+    fn test_expires_after_duration() {
+        let deadline = SearchDeadline::new(Duration::from_millis(0));
+
+        assert!(deadline.is_expired());
+        assert_eq!(deadline.remaining(), Some(Duration::from_millis(0)));
+    }
+}
@@ -0,0 +1,549 @@
+use battlesnake_game_types::compact_representation::MoveEvaluatableGame;
+use battlesnake_game_types::types::*;
+
+use crate::a_prime::{dist_between, wrapped_dist_between};
+use crate::rules::Rules;
+use crate::search_deadline::SearchDeadline;
+use crate::transposition_table::{splitmix64, zobrist_hash};
+use crate::ALL_MOVES;
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::path::Path;
+
+/// Duels with this few free cells left are small enough to try solving exactly
+pub const TABLEBASE_FREE_CELLS: usize = 12;
+
+/// How many positions a single solve is allowed to visit before giving up
+pub const SOLVE_NODE_BUDGET: usize = 500_000;
+
+const MAGIC: &[u8; 4] = b"BSTB";
+const FORMAT_VERSION: u8 = 1;
+/// Key, outcome kind, distance and move
+const ENTRY_BYTES: usize = 8 + 1 + 2 + 1;
+const NO_MOVE: u8 = u8::MAX;
+
+/// The result of a duel with perfect play from both sides, from the first player's point of
+/// view, and how many turns until it happens
+///
+/// The opponent is assumed to see our move before it picks its own, so a win here can't be
+/// taken away no matter what the opponent does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Win(u16),
+    Draw(u16),
+    Loss(u16),
+}
+
+impl Outcome {
+    /// Winning sooner is better, and losing later gives the opponent more chances to go wrong
+    fn rank(&self) -> (u8, i32) {
+        match self {
+            Outcome::Loss(d) => (0, *d as i32),
+            Outcome::Draw(d) => (1, *d as i32),
+            Outcome::Win(d) => (2, -(*d as i32)),
+        }
+    }
+
+    /// The same outcome seen from a turn earlier
+    fn one_turn_earlier(self) -> Self {
+        match self {
+            Outcome::Win(d) => Outcome::Win(d.saturating_add(1)),
+            Outcome::Draw(d) => Outcome::Draw(d.saturating_add(1)),
+            Outcome::Loss(d) => Outcome::Loss(d.saturating_add(1)),
+        }
+    }
+
+    pub fn distance(&self) -> u16 {
+        match self {
+            Outcome::Win(d) | Outcome::Draw(d) | Outcome::Loss(d) => *d,
+        }
+    }
+}
+
+impl Ord for Outcome {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for Outcome {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TablebaseEntry {
+    pub outcome: Outcome,
+    /// `None` for positions where the game is already over
+    pub best_move: Option<Move>,
+}
+
+/// The transposition table hash, with exact health and the board size mixed in. The table
+/// buckets health, but a single point of it decides who starves first in an ending
+pub fn position_key<T>(node: &T, players: &[T::SnakeIDType]) -> u64
+where
+    T: SnakeIDGettableGame
+        + PositionGettableGame
+        + SnakeBodyGettableGame
+        + HealthGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame,
+{
+    let mut key = zobrist_hash(node, players);
+
+    for (i, snake_id) in players.iter().enumerate() {
+        let health = node.get_health_i64(snake_id) as u32 as u64;
+        key ^= splitmix64(((i as u64) << 32) | health);
+    }
+
+    key ^ splitmix64((1 << 63) | ((node.get_width() as u64) << 32) | node.get_height() as u64)
+}
+
+/// Cells that no living snake is in
+pub fn free_cells<T>(node: &T, players: &[T::SnakeIDType]) -> usize
+where
+    T: SnakeIDGettableGame + SnakeBodyGettableGame + HealthGettableGame + SizeDeterminableGame,
+{
+    let taken: usize = players
+        .iter()
+        .filter(|s| node.is_alive(s))
+        .map(|s| node.get_snake_body_vec(s).len())
+        .sum();
+
+    ((node.get_width() * node.get_height()) as usize).saturating_sub(taken)
+}
+
+/// How many turns out a solved outcome can be and still hold under these `rules`
+///
+/// Food spawns at the end of a turn, so the soonest anyone can eat it is the turn after. While
+/// there is a chance of food spawning that is the very next turn, otherwise nothing spawns
+/// until a snake eats the board below the minimum
+pub fn trusted_distance<T>(node: &T, players: &[T::SnakeIDType], rules: &Rules) -> u16
+where
+    T: SnakeIDGettableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame,
+{
+    let food: Vec<_> = node
+        .get_all_food_as_native_positions()
+        .into_iter()
+        .map(|f| node.position_from_native(f))
+        .collect();
+    if rules.food_spawn_chance > 0 || (food.len() as i32) < rules.minimum_food {
+        return 1;
+    }
+    if rules.minimum_food <= 0 {
+        return u16::MAX;
+    }
+
+    let (width, height) = (node.get_width() as i32, node.get_height() as i32);
+    players
+        .iter()
+        .map(|s| node.position_from_native(node.get_head_as_native_position(s)))
+        .flat_map(|head| {
+            food.iter().map(move |f| {
+                if rules.wrapped {
+                    wrapped_dist_between(&head, f, width, height)
+                } else {
+                    dist_between(&head, f)
+                }
+            })
+        })
+        .min()
+        .map_or(u16::MAX, |turns| {
+            turns.max(1).try_into().unwrap_or(u16::MAX)
+        })
+}
+
+/// Exact results for duel positions, found by searching every line to the end of the game
+///
+/// Food that hasn't spawned yet can't be known, so positions are solved as if no more will
+/// spawn. Without new food everyone starves eventually, which keeps every line finite. Only
+/// outcomes within the `trusted_distance` for the game's rules can be played on
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tablebase {
+    entries: HashMap<u64, TablebaseEntry>,
+}
+
+impl Tablebase {
+    /// Where servers look for a tablebase to load when they start up
+    pub const ENV_VAR: &'static str = "TABLEBASE_PATH";
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: u64) -> Option<&TablebaseEntry> {
+        self.entries.get(&key)
+    }
+
+    pub fn probe<T>(&self, node: &T, players: &[T::SnakeIDType]) -> Option<TablebaseEntry>
+    where
+        T: SnakeIDGettableGame
+            + PositionGettableGame
+            + SnakeBodyGettableGame
+            + HealthGettableGame
+            + FoodGettableGame
+            + SizeDeterminableGame,
+    {
+        self.get(position_key(node, players)).copied()
+    }
+
+    /// Adds every entry from `other` that we don't already have
+    pub fn merge(&mut self, other: &Tablebase) {
+        for (key, entry) in other.entries.iter() {
+            self.entries.entry(*key).or_insert(*entry);
+        }
+    }
+
+    /// Solves the duel between the two `players`, the first of which is us
+    ///
+    /// Positions already in `known` aren't searched again. Everything solved along the way is
+    /// kept, so a solve that runs out of budget or time still makes the next one cheaper.
+    /// `None` when we ran out before finishing the root
+    pub fn solve<T>(
+        &mut self,
+        node: &T,
+        players: &[T::SnakeIDType],
+        known: Option<&Tablebase>,
+        deadline: &SearchDeadline,
+        budget: usize,
+    ) -> Option<TablebaseEntry>
+    where
+        T: SnakeIDGettableGame
+            + PositionGettableGame
+            + SnakeBodyGettableGame
+            + HealthGettableGame
+            + FoodGettableGame
+            + SizeDeterminableGame
+            + HeadGettableGame
+            + NeighborDeterminableGame
+            + VictorDeterminableGame
+            + MoveEvaluatableGame,
+    {
+        if players.len() != 2 {
+            return None;
+        }
+
+        let mut solver = Solver {
+            players,
+            known,
+            deadline,
+            budget,
+            table: self,
+        };
+        solver.value(node)?;
+
+        self.probe(node, players)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut keys: Vec<_> = self.entries.keys().copied().collect();
+        keys.sort_unstable();
+
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 5 + keys.len() * ENTRY_BYTES);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(FORMAT_VERSION);
+        bytes.extend_from_slice(&(keys.len() as u32).to_le_bytes());
+
+        for key in keys {
+            let entry = &self.entries[&key];
+            let (kind, distance) = match entry.outcome {
+                Outcome::Loss(d) => (0, d),
+                Outcome::Draw(d) => (1, d),
+                Outcome::Win(d) => (2, d),
+            };
+            let m = entry
+                .best_move
+                .and_then(|m| ALL_MOVES.iter().position(|x| *x == m))
+                .map_or(NO_MOVE, |i| i as u8);
+
+            bytes.extend_from_slice(&key.to_le_bytes());
+            bytes.push(kind);
+            bytes.extend_from_slice(&distance.to_le_bytes());
+            bytes.push(m);
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let header = MAGIC.len() + 5;
+        if bytes.len() < header || !bytes.starts_with(MAGIC) {
+            return Err("Not a tablebase file".into());
+        }
+        if bytes[MAGIC.len()] != FORMAT_VERSION {
+            return Err("Unsupported tablebase version".into());
+        }
+
+        let count = u32::from_le_bytes(bytes[MAGIC.len() + 1..header].try_into()?) as usize;
+        let body = &bytes[header..];
+        if body.len() != count * ENTRY_BYTES {
+            return Err("Tablebase file is truncated".into());
+        }
+
+        let mut entries = HashMap::with_capacity(count);
+        for chunk in body.chunks_exact(ENTRY_BYTES) {
+            let key = u64::from_le_bytes(chunk[..8].try_into()?);
+            let distance = u16::from_le_bytes(chunk[9..11].try_into()?);
+            let outcome = match chunk[8] {
+                0 => Outcome::Loss(distance),
+                1 => Outcome::Draw(distance),
+                2 => Outcome::Win(distance),
+                _ => return Err("Unknown tablebase outcome".into()),
+            };
+            let best_move = ALL_MOVES.get(chunk[11] as usize).copied();
+
+            entries.insert(key, TablebaseEntry { outcome, best_move });
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn save(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        std::fs::write(path, self.to_bytes())?;
+
+        Ok(())
+    }
+
+    /// The tablebase at `TABLEBASE_PATH`, if that is set and points at one we can read
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var(Self::ENV_VAR).ok()?;
+
+        match Self::load(&path) {
+            Ok(tablebase) => Some(tablebase),
+            Err(e) => {
+                tracing::warn!(path = %path, error = %e, "Couldn't load the tablebase");
+                None
+            }
+        }
+    }
+}
+
+struct Solver<'a, T: SnakeIDGettableGame> {
+    players: &'a [T::SnakeIDType],
+    known: Option<&'a Tablebase>,
+    deadline: &'a SearchDeadline,
+    budget: usize,
+    table: &'a mut Tablebase,
+}
+
+impl<'a, T> Solver<'a, T>
+where
+    T: SnakeIDGettableGame
+        + PositionGettableGame
+        + SnakeBodyGettableGame
+        + HealthGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame
+        + HeadGettableGame
+        + NeighborDeterminableGame
+        + VictorDeterminableGame
+        + MoveEvaluatableGame,
+{
+    /// We pick the move whose worst reply is best for us. Only finished values go in the
+    /// table, so the only cutoffs are when a move can't possibly be beaten
+    fn value(&mut self, node: &T) -> Option<Outcome> {
+        let key = position_key(node, self.players);
+        if let Some(entry) = self
+            .table
+            .get(key)
+            .or_else(|| self.known.and_then(|k| k.get(key)))
+        {
+            return Some(entry.outcome);
+        }
+
+        if self.budget == 0 || self.deadline.is_expired() {
+            return None;
+        }
+        self.budget -= 1;
+
+        let (me, them) = (&self.players[0], &self.players[1]);
+
+        let entry = if node.is_over() {
+            let outcome = match node.get_winner() {
+                Some(winner) if &winner == me => Outcome::Win(0),
+                Some(_) => Outcome::Loss(0),
+                None => Outcome::Draw(0),
+            };
+
+            TablebaseEntry {
+                outcome,
+                best_move: None,
+            }
+        } else {
+            let our_moves = node.possible_moves(&node.get_head_as_native_position(me));
+            let their_moves = node.possible_moves(&node.get_head_as_native_position(them));
+            let mut best: Option<(Outcome, Move)> = None;
+
+            for (ours, _) in our_moves {
+                let mut worst: Option<Outcome> = None;
+
+                for (theirs, _) in their_moves.iter() {
+                    let joint_move = vec![(me.clone(), ours), (them.clone(), *theirs)];
+                    let value = self
+                        .value(&node.evaluate_moves(&joint_move))?
+                        .one_turn_earlier();
+
+                    worst = Some(worst.map_or(value, |w| w.min(value)));
+                    if value == Outcome::Loss(1) {
+                        break;
+                    }
+                }
+
+                if let Some(worst) = worst {
+                    if best.map_or(true, |(b, _)| worst > b) {
+                        best = Some((worst, ours));
+                    }
+                    if worst == Outcome::Win(1) {
+                        break;
+                    }
+                }
+            }
+
+            let (outcome, best_move) = best?;
+
+            TablebaseEntry {
+                outcome,
+                best_move: Some(best_move),
+            }
+        };
+
+        self.table.entries.insert(key, entry);
+
+        Some(entry.outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CellBoard4Snakes11x11;
+    use battlesnake_game_types::compact_representation::CellBoard;
+    use battlesnake_game_types::wire_representation::Game;
+
+    /// A 7x7 duel with no food, where we have `our_health` and the opponent `their_health`
+    fn duel(
+        our_health: i32,
+        their_health: i32,
+    ) -> (
+        CellBoard4Snakes11x11,
+        Vec<<CellBoard4Snakes11x11 as SnakeIDGettableGame>::SnakeIDType>,
+    ) {
+        let snake = |id: &str, health: i32, x: i32| {
+            format!(
+                r#"{{"id":"{}","name":"","health":{},"body":[{{"x":{},"y":3}},{{"x":{},"y":2}},{{"x":{},"y":1}}],"latency":0,"head":{{"x":{},"y":3}},"length":3,"shout":""}}"#,
+                id, health, x, x, x, x
+            )
+        };
+        let you = snake("you", our_health, 1);
+        let json = format!(
+            r#"{{"game":{{"id":"","ruleset":{{"name":"standard","version":"v1.0.17"}},"timeout":500}},"turn":100,"board":{{"height":7,"width":7,"food":[],"hazards":[],"snakes":[{},{}]}},"you":{}}}"#,
+            you,
+            snake("them", their_health, 5),
+            you
+        );
+        let game: Game = serde_json::from_str(&json).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let node: CellBoard4Snakes11x11 = CellBoard::convert_from_game(game, &id_map).unwrap();
+
+        let mut players = node.get_snake_ids();
+        players.sort_by_key(|s| if s == node.you_id() { -1 } else { 1 });
+
+        (node, players)
+    }
+
+    #[test]
+    fn test_solves_who_starves_first() {
+        let deadline = SearchDeadline::unbounded();
+
+        let (node, players) = duel(2, 1);
+        let mut table = Tablebase::default();
+        let entry = table
+            .solve(&node, &players, None, &deadline, SOLVE_NODE_BUDGET)
+            .unwrap();
+        assert_eq!(entry.outcome, Outcome::Win(1));
+        assert!(entry.best_move.is_some());
+
+        let (node, players) = duel(1, 1);
+        let entry = Tablebase::default()
+            .solve(&node, &players, None, &deadline, SOLVE_NODE_BUDGET)
+            .unwrap();
+        assert_eq!(entry.outcome, Outcome::Draw(1));
+
+        let (node, players) = duel(1, 3);
+        let entry = Tablebase::default()
+            .solve(&node, &players, None, &deadline, SOLVE_NODE_BUDGET)
+            .unwrap();
+        assert_eq!(entry.outcome, Outcome::Loss(1));
+    }
+
+    #[test]
+    fn test_round_trips_through_bytes() {
+        let (node, players) = duel(3, 2);
+        let mut table = Tablebase::default();
+        table
+            .solve(
+                &node,
+                &players,
+                None,
+                &SearchDeadline::unbounded(),
+                SOLVE_NODE_BUDGET,
+            )
+            .unwrap();
+
+        let loaded = Tablebase::from_bytes(&table.to_bytes()).unwrap();
+
+        assert_eq!(loaded, table);
+        assert_eq!(loaded.probe(&node, &players), table.probe(&node, &players));
+        assert!(Tablebase::from_bytes(b"nope").is_err());
+    }
+
+    #[test]
+    fn test_runs_out_of_budget() {
+        let (node, players) = duel(3, 3);
+        let mut table = Tablebase::default();
+
+        assert_eq!(
+            table.solve(&node, &players, None, &SearchDeadline::unbounded(), 3),
+            None
+        );
+        assert!(table.len() <= 3);
+    }
+
+    #[test]
+    fn test_food_spawns_limit_how_far_we_trust_outcomes() {
+        let (node, players) = duel(3, 3);
+
+        assert_eq!(trusted_distance(&node, &players, &Rules::default()), 1);
+        // Under the minimum food spawns on the next turn, chance or not
+        let no_chance = Rules {
+            food_spawn_chance: 0,
+            ..Rules::default()
+        };
+        assert_eq!(trusted_distance(&node, &players, &no_chance), 1);
+
+        let no_food = Rules {
+            food_spawn_chance: 0,
+            minimum_food: 0,
+            ..Rules::default()
+        };
+        assert_eq!(trusted_distance(&node, &players, &no_food), u16::MAX);
+    }
+}
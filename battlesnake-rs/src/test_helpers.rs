@@ -0,0 +1,32 @@
+//! Boards shared by the tests of more than one module
+
+use battlesnake_game_types::types::*;
+use battlesnake_game_types::wire_representation::Game;
+
+pub fn start_of_game() -> Game {
+    serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap()
+}
+
+/// The board a turn later, with only `snake_id` moved in `dir`
+pub fn moved(game: &Game, snake_id: &str, dir: Move) -> Game {
+    let head = game
+        .possible_moves(&game.get_head_as_native_position(&snake_id.to_owned()))
+        .into_iter()
+        .find(|(m, _)| *m == dir)
+        .unwrap()
+        .1;
+    let mut game = game.clone();
+    game.turn += 1;
+
+    let snake = game
+        .board
+        .snakes
+        .iter_mut()
+        .find(|s| s.id == snake_id)
+        .unwrap();
+    snake.body.push_front(head);
+    snake.body.pop_back();
+    snake.head = head;
+
+    game
+}
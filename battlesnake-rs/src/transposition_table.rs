@@ -0,0 +1,375 @@
+use battlesnake_game_types::types::*;
+use battlesnake_game_types::wire_representation::Position;
+
+use crate::devious_devin_mutable::ScoreEndState;
+use crate::evaluator::StandardScore;
+
+/// Health is bucketed before hashing so that positions which only differ by a
+/// point or two of health share a table entry
+const HEALTH_BUCKET_SIZE: i64 = 5;
+
+const FEATURE_HEAD: u64 = 1;
+const FEATURE_BODY: u64 = 2;
+const FEATURE_TAIL: u64 = 3;
+const FEATURE_LENGTH: u64 = 4;
+const FEATURE_HEALTH: u64 = 5;
+const FEATURE_FOOD: u64 = 6;
+const FEATURE_DEAD: u64 = 7;
+
+pub const DEFAULT_TRANSPOSITION_TABLE_SIZE: usize = 1 << 18;
+
+pub(crate) fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// The Zobrist key for a single feature of the board.
+///
+/// Instead of keeping a table of random numbers around we derive each key by
+/// mixing the feature, the snake it belongs to and the cell it is on. This gives
+/// us the same independence properties without needing to know the board size
+/// up front.
+fn zobrist_key(feature: u64, snake_index: u64, x: i32, y: i32) -> u64 {
+    let cell = ((x as u32 as u64) << 32) | (y as u32 as u64);
+
+    splitmix64(splitmix64(splitmix64(feature) ^ snake_index) ^ cell)
+}
+
+/// Hashes everything the search cares about for a position: heads, bodies,
+/// lengths, bucketed health and food
+///
+/// `players` is used to give each snake a stable index, so the same ordering
+/// must be used for every hash that is compared. This walks the whole board, so
+/// the search only calls it once at the root and then uses
+/// `update_zobrist_hash` for every move it makes
+pub fn zobrist_hash<T>(node: &T, players: &[T::SnakeIDType]) -> u64
+where
+    T: SnakeIDGettableGame
+        + PositionGettableGame
+        + SnakeBodyGettableGame
+        + HealthGettableGame
+        + FoodGettableGame,
+{
+    let mut hash = 0;
+
+    for (i, snake_id) in players.iter().enumerate() {
+        hash ^= snake_hash(node, snake_id, i as u64);
+    }
+
+    for food in node.get_all_food_as_native_positions() {
+        let p = node.position_from_native(food);
+        hash ^= zobrist_key(FEATURE_FOOD, 0, p.x, p.y);
+    }
+
+    hash
+}
+
+/// Everything in the hash that belongs to a single snake
+fn snake_hash<T>(node: &T, snake_id: &T::SnakeIDType, i: u64) -> u64
+where
+    T: SnakeIDGettableGame + PositionGettableGame + SnakeBodyGettableGame + HealthGettableGame,
+{
+    if !node.is_alive(snake_id) {
+        return zobrist_key(FEATURE_DEAD, i, 0, 0);
+    }
+
+    let mut hash = 0;
+
+    let body = node.get_snake_body_vec(snake_id);
+    for (j, native) in body.iter().enumerate() {
+        let p = node.position_from_native(native.clone());
+        let feature = if j == 0 { FEATURE_HEAD } else { FEATURE_BODY };
+        hash ^= zobrist_key(feature, i, p.x, p.y);
+    }
+
+    if let Some(tail) = body.last() {
+        let p = node.position_from_native(tail.clone());
+        hash ^= zobrist_key(FEATURE_TAIL, i, p.x, p.y);
+    }
+
+    hash ^= zobrist_key(FEATURE_LENGTH, i, body.len() as i32, 0);
+    hash ^= health_key(node, snake_id, i);
+
+    hash
+}
+
+fn health_key<T>(node: &T, snake_id: &T::SnakeIDType, i: u64) -> u64
+where
+    T: SnakeIDGettableGame + HealthGettableGame,
+{
+    let health_bucket = node.get_health_i64(snake_id) / HEALTH_BUCKET_SIZE;
+
+    zobrist_key(FEATURE_HEALTH, i, health_bucket as i32, 0)
+}
+
+/// The hash of `after`, given the `hash` of `before` and that `after` is one
+/// turn later
+///
+/// Only the features a move touches are rehashed: the new head, the tail that
+/// moved off, the food that was eaten and the health bucket. None of the game
+/// traits hand us the tail on its own, so we still get the body, but only the
+/// few cells at either end are keyed. Turns where a snake died are rare enough
+/// that we just hash `after` from scratch
+pub fn update_zobrist_hash<T>(hash: u64, before: &T, after: &T, players: &[T::SnakeIDType]) -> u64
+where
+    T: SnakeIDGettableGame
+        + PositionGettableGame
+        + SnakeBodyGettableGame
+        + HealthGettableGame
+        + FoodGettableGame,
+{
+    if players
+        .iter()
+        .any(|s| before.is_alive(s) && !after.is_alive(s))
+    {
+        return zobrist_hash(after, players);
+    }
+
+    let mut hash = hash;
+    let mut eaten: Vec<Position> = vec![];
+
+    for (i, snake_id) in players.iter().enumerate() {
+        if !before.is_alive(snake_id) {
+            continue;
+        }
+        let i = i as u64;
+
+        let old: Vec<_> = before
+            .get_snake_body_vec(snake_id)
+            .into_iter()
+            .map(|native| before.position_from_native(native))
+            .collect();
+        let new: Vec<_> = after
+            .get_snake_body_vec(snake_id)
+            .into_iter()
+            .map(|native| after.position_from_native(native))
+            .collect();
+        let key = |feature, p: &Position| zobrist_key(feature, i, p.x, p.y);
+
+        // Behind the new head is the old body shifted down one, so only the
+        // cells at the tail end where the two stop lining up change keys
+        let shared = new
+            .iter()
+            .skip(1)
+            .zip(old.iter())
+            .take_while(|(n, o)| n == o)
+            .count();
+        if shared == 0 {
+            hash ^= snake_hash(before, snake_id, i) ^ snake_hash(after, snake_id, i);
+            continue;
+        }
+
+        hash ^= key(FEATURE_HEAD, &old[0]) ^ key(FEATURE_BODY, &old[0]);
+        hash ^= key(FEATURE_HEAD, &new[0]);
+        for p in &old[shared..] {
+            hash ^= key(FEATURE_BODY, p);
+        }
+        for p in &new[shared + 1..] {
+            hash ^= key(FEATURE_BODY, p);
+        }
+
+        hash ^= key(FEATURE_TAIL, &old[old.len() - 1]) ^ key(FEATURE_TAIL, &new[new.len() - 1]);
+
+        if new.len() != old.len() {
+            hash ^= zobrist_key(FEATURE_LENGTH, i, old.len() as i32, 0);
+            hash ^= zobrist_key(FEATURE_LENGTH, i, new.len() as i32, 0);
+
+            // Growing is the only way to tell which food went, without looking
+            // at every cell on the board
+            if !eaten.contains(&new[0]) {
+                eaten.push(new[0]);
+            }
+        }
+
+        hash ^= health_key(before, snake_id, i) ^ health_key(after, snake_id, i);
+    }
+
+    for p in eaten {
+        hash ^= zobrist_key(FEATURE_FOOD, 0, p.x, p.y);
+    }
+
+    hash
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The stored score is the exact minimax value
+    Exact,
+    /// The search failed high, the real value is at least the stored score
+    Lower,
+    /// The search failed low, the real value is at most the stored score
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TranspositionEntry<S = StandardScore> {
+    hash: u64,
+    pub remaining_depth: usize,
+    pub bound: Bound,
+    pub score: ScoreEndState<S>,
+    pub best_move: Option<Move>,
+}
+
+impl<S: Ord + Copy> TranspositionEntry<S> {
+    /// Returns the stored score if it is deep enough and its bound lets us cut
+    /// off a search with the given window
+    pub fn cutoff(
+        &self,
+        remaining_depth: usize,
+        alpha: ScoreEndState<S>,
+        beta: ScoreEndState<S>,
+    ) -> Option<ScoreEndState<S>> {
+        if self.remaining_depth < remaining_depth {
+            return None;
+        }
+
+        match self.bound {
+            Bound::Exact => Some(self.score),
+            Bound::Lower if self.score >= beta => Some(self.score),
+            Bound::Upper if self.score <= alpha => Some(self.score),
+            _ => None,
+        }
+    }
+}
+
+/// A fixed size, depth preferred transposition table
+///
+/// Entries are indexed by the low bits of their Zobrist hash. On a collision the
+/// entry that was searched deeper wins, so the table stays useful across
+/// iterative deepening runs without growing unbounded
+pub struct TranspositionTable<S = StandardScore> {
+    entries: Vec<Option<TranspositionEntry<S>>>,
+    mask: u64,
+}
+
+impl<S: Copy> TranspositionTable<S> {
+    /// `capacity` is rounded up to the next power of two
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1).next_power_of_two();
+
+        Self {
+            entries: vec![None; capacity],
+            mask: (capacity - 1) as u64,
+        }
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        (hash & self.mask) as usize
+    }
+
+    pub fn get(&self, hash: u64) -> Option<&TranspositionEntry<S>> {
+        self.entries[self.index(hash)]
+            .as_ref()
+            .filter(|e| e.hash == hash)
+    }
+
+    pub fn insert(
+        &mut self,
+        hash: u64,
+        remaining_depth: usize,
+        bound: Bound,
+        score: ScoreEndState<S>,
+        best_move: Option<Move>,
+    ) {
+        let index = self.index(hash);
+        let should_replace = match &self.entries[index] {
+            None => true,
+            Some(existing) => existing.hash == hash || existing.remaining_depth <= remaining_depth,
+        };
+
+        if should_replace {
+            self.entries[index] = Some(TranspositionEntry {
+                hash,
+                remaining_depth,
+                bound,
+                score,
+                best_move,
+            });
+        }
+    }
+}
+
+impl<S: Copy> Default for TranspositionTable<S> {
+    fn default() -> Self {
+        Self::new(DEFAULT_TRANSPOSITION_TABLE_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use battlesnake_game_types::compact_representation::{
+        CellBoard, CellBoard4Snakes11x11, MoveEvaluatableGame,
+    };
+    use battlesnake_game_types::wire_representation::Game;
+
+    #[test]
+    fn test_updating_the_hash_matches_hashing_from_scratch() {
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let mut node: CellBoard4Snakes11x11 = CellBoard::convert_from_game(game, &id_map).unwrap();
+        let players = node.get_snake_ids();
+        let mut hash = zobrist_hash(&node, &players);
+
+        // Long enough for the stacked tails to unwind and for someone to eat
+        for _ in 0..12 {
+            let joint_move: Vec<_> = players
+                .iter()
+                .filter(|s| node.is_alive(s))
+                .map(|s| {
+                    let dir = node
+                        .possible_moves(&node.get_head_as_native_position(s))
+                        .into_iter()
+                        .find(|(_, c)| !node.position_is_snake_body(c.clone()))
+                        .map_or(Move::Up, |(dir, _)| dir);
+                    (s.clone(), dir)
+                })
+                .collect();
+            let next = node.evaluate_moves(&joint_move);
+
+            hash = update_zobrist_hash(hash, &node, &next, &players);
+            assert_eq!(hash, zobrist_hash(&next, &players));
+
+            node = next;
+        }
+    }
+
+    #[test]
+    fn test_deeper_entries_are_kept_on_collision() {
+        let mut table: TranspositionTable = TranspositionTable::new(1);
+
+        table.insert(1, 4, Bound::Exact, ScoreEndState::Tie(1), Some(Move::Up));
+        table.insert(2, 2, Bound::Exact, ScoreEndState::Tie(2), Some(Move::Down));
+
+        assert!(table.get(2).is_none());
+        assert_eq!(table.get(1).unwrap().best_move, Some(Move::Up));
+
+        table.insert(2, 6, Bound::Exact, ScoreEndState::Tie(2), Some(Move::Down));
+
+        assert!(table.get(1).is_none());
+        assert_eq!(table.get(2).unwrap().best_move, Some(Move::Down));
+    }
+
+    #[test]
+    fn test_cutoff_respects_bounds() {
+        let mut table: TranspositionTable = TranspositionTable::new(16);
+        table.insert(7, 4, Bound::Lower, ScoreEndState::Tie(3), None);
+        let entry = table.get(7).unwrap();
+
+        assert_eq!(
+            entry.cutoff(4, ScoreEndState::Lose(0), ScoreEndState::Tie(2)),
+            Some(ScoreEndState::Tie(3))
+        );
+        assert_eq!(
+            entry.cutoff(4, ScoreEndState::Lose(0), ScoreEndState::Win(0)),
+            None
+        );
+        assert_eq!(
+            entry.cutoff(5, ScoreEndState::Lose(0), ScoreEndState::Tie(2)),
+            None
+        );
+    }
+}
@@ -6,7 +6,7 @@ use lambda_http::{
 
 use serde_json::json;
 
-use battlesnake_rs::{all_factories, BoxedFactory, Game};
+use battlesnake_rs::{all_factories, game_state::GameStateStore, parse_request_body, BoxedFactory};
 
 use tracing_subscriber::EnvFilter;
 
@@ -22,6 +22,9 @@ async fn main() -> Result<(), Error> {
         .init();
 
     let factories: Vec<_> = all_factories().into_iter().map(Arc::new).collect();
+    // Lambda keeps warm containers around between requests, so games that keep landing on the
+    // same one get to remember things. Anything else just starts over
+    let store = GameStateStore::default();
 
     lambda_runtime::run(handler(move |request: Request, context: Context| {
         let path = request.uri().path();
@@ -32,7 +35,7 @@ async fn main() -> Result<(), Error> {
             .find(|s| snake_name == Some(&s.name()))
             .cloned();
 
-        api_move(factory, request, context)
+        api_move(factory, store.clone(), request, context)
     }))
     .await?;
 
@@ -41,6 +44,7 @@ async fn main() -> Result<(), Error> {
 
 async fn api_move(
     factory: Option<Arc<BoxedFactory>>,
+    store: GameStateStore,
     request: Request,
     _context: Context,
 ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
@@ -57,15 +61,25 @@ async fn api_move(
 
     match action {
         None => Ok(json!(factory.about())),
-        Some(&"start") => Ok(json!("Nothing to do in start")),
-        Some(&"end") | Some(&"move") => {
+        Some(&"start") | Some(&"end") | Some(&"move") => {
             let string_body = string_body.ok_or("Body was not a string")?;
-            let state: Game = serde_json::from_str(string_body)?;
-            let snake = factory.from_wire_game(state);
+            let (state, context) = parse_request_body(string_body)?;
+            let context = context.with_state_from(&store, &state, &factory.name());
 
             match action {
-                Some(&"end") => Ok(json!(snake.end())),
-                Some(&"move") => Ok(serde_json::to_value(snake.make_move()?)?),
+                Some(&"start") => {
+                    factory.start(&state, &context);
+                    Ok(json!(()))
+                }
+                Some(&"end") => {
+                    factory.end(&state, &context);
+                    store.end_game(&state.game.id, &factory.name());
+                    Ok(json!(()))
+                }
+                Some(&"move") => {
+                    let snake = factory.from_wire_game_with_context(state, context);
+                    Ok(serde_json::to_value(snake.make_move()?)?)
+                }
                 _ => unreachable!("Nested matches mean this is impossible if bad code"),
             }
         }
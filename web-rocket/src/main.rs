@@ -7,28 +7,46 @@ use tracing_subscriber::layer::SubscriberExt;
 
 use rocket::http::Status;
 
-use battlesnake_rs::{all_factories, AboutMe, BoxedFactory, Game, MoveOutput};
+use battlesnake_rs::{
+    all_factories, game_state::GameStateStore, parse_request_body, AboutMe, BoxedFactory,
+    MoveOutput,
+};
 
 use rocket::State;
 
 use rocket_contrib::json::Json;
 
-#[post("/<_snake>/start")]
-fn api_start(_snake: String) -> Status {
-    Status::NoContent
+#[post("/<snake>/start", data = "<game_state>")]
+fn api_start(
+    snake: String,
+    factories: State<Vec<BoxedFactory>>,
+    store: State<GameStateStore>,
+    game_state: String,
+) -> Option<Status> {
+    let (game, context) = parse_request_body(&game_state).ok()?;
+    let context = context.with_state_from(&store, &game, &snake);
+    factories
+        .iter()
+        .find(|s| s.name() == snake)?
+        .start(&game, &context);
+
+    Some(Status::NoContent)
 }
 
 #[post("/<snake>/end", data = "<game_state>")]
 fn api_end(
     snake: String,
     factories: State<Vec<BoxedFactory>>,
-    game_state: Json<Game>,
+    store: State<GameStateStore>,
+    game_state: String,
 ) -> Option<Status> {
-    let snake_ai = factories
+    let (game, context) = parse_request_body(&game_state).ok()?;
+    let context = context.with_state_from(&store, &game, &snake);
+    factories
         .iter()
         .find(|s| s.name() == snake)?
-        .from_wire_game(game_state.into_inner());
-    snake_ai.end();
+        .end(&game, &context);
+    store.end_game(&game.game.id, &snake);
 
     Some(Status::NoContent)
 }
@@ -37,12 +55,15 @@ fn api_end(
 fn api_move(
     snake: String,
     factories: State<Vec<BoxedFactory>>,
-    game_state: Json<Game>,
+    store: State<GameStateStore>,
+    game_state: String,
 ) -> Option<Json<MoveOutput>> {
+    let (game, context) = parse_request_body(&game_state).ok()?;
+    let context = context.with_state_from(&store, &game, &snake);
     let snake_ai = factories
         .iter()
         .find(|s| s.name() == snake)?
-        .from_wire_game(game_state.into_inner());
+        .from_wire_game_with_context(game, context);
     let m = snake_ai.make_move().ok()?;
 
     Some(Json(m))
@@ -65,6 +86,7 @@ fn main() {
 
     rocket::ignite()
         .manage(all_factories())
+        .manage(GameStateStore::default())
         .attach(cors)
         .mount("/", routes![api_start, api_end, api_move, api_about])
         .launch();